@@ -0,0 +1,66 @@
+// 字符集检测：文本文档、歌词、外挂字幕和旧音频标签里常见 GBK / Shift_JIS / UTF-16，
+// 按 BOM → 无 BOM 的 UTF-16 特征（大量 0x00）→ chardetng 猜测的顺序确定编码
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+
+/// 无 BOM 的 UTF-16：ASCII 字符的高字节为 0，集中出现在奇数位（LE）或偶数位（BE）
+fn sniff_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let units = sample.len() / 2;
+    if units < 8 {
+        return None;
+    }
+    let even = sample.iter().step_by(2).take(units).filter(|&&b| b == 0).count();
+    let odd = sample.iter().skip(1).step_by(2).take(units).filter(|&&b| b == 0).count();
+    if odd * 10 > units * 4 && even * 20 < units {
+        Some(UTF_16LE)
+    } else if even * 10 > units * 4 && odd * 20 < units {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// 检测字符集，返回 (编码, BOM 长度)；is_whole 表示 sample 已是完整文件
+pub fn detect_encoding(sample: &[u8], is_whole: bool) -> (&'static Encoding, usize) {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(sample) {
+        return (encoding, bom_len);
+    }
+    if let Some(encoding) = sniff_utf16(sample) {
+        return (encoding, 0);
+    }
+    let mut detector = chardetng::EncodingDetector::new();
+    detector.feed(sample, is_whole);
+    (detector.guess(None, true), 0)
+}
+
+/// 整段读入的小文本（歌词、字幕等）：检测编码并转为 UTF-8，去掉 BOM
+pub fn decode_detected(bytes: &[u8]) -> String {
+    let (encoding, bom_len) = detect_encoding(bytes, true);
+    encoding.decode_without_bom_handling(&bytes[bom_len..]).0.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_encoding() {
+        let (encoding, bom) = detect_encoding(b"\xEF\xBB\xBFhello", true);
+        assert_eq!((encoding.name(), bom), ("UTF-8", 3));
+
+        let utf16: Vec<u8> = "plain ascii text".encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+        assert_eq!(detect_encoding(&utf16, true).0, UTF_16LE);
+
+        let (gbk, _, _) = encoding_rs::GBK.encode("这是一段用于检测字符集的中文文本，包含常见的汉字和标点符号。");
+        assert_eq!(detect_encoding(&gbk, true).0.name(), "GBK");
+
+        let (sjis, _, _) = encoding_rs::SHIFT_JIS.encode("これは文字コードを判定するための日本語のテキストです。");
+        assert_eq!(detect_encoding(&sjis, true).0.name(), "Shift_JIS");
+    }
+
+    #[test]
+    fn test_decode_detected() {
+        assert_eq!(decode_detected(b"\xEF\xBB\xBF[00:01.00]hi"), "[00:01.00]hi");
+        let (gbk, _, _) = encoding_rs::GBK.encode("[00:01.00]故事的小黄花，从出生那年就飘着");
+        assert_eq!(decode_detected(&gbk), "[00:01.00]故事的小黄花，从出生那年就飘着");
+    }
+}
@@ -101,7 +101,10 @@ pub const SUPPORTED_EXTENSIONS: &[&str] = &[
     "MP4", "MOV", "AVI", "MKV", "WEBM",
     "heic", "HEIC", "heif", "HEIF",
     "clip", "CLIP",
+    "psd", "PSD", "psb", "PSB", "kra", "KRA", "ora", "ORA", "svg", "SVG",
+    "dng", "DNG", "cr2", "CR2", "cr3", "CR3", "nef", "NEF", "arw", "ARW", "raf", "RAF", "orf", "ORF",
     "pdf", "PDF",
+    "epub", "EPUB",
     "mp3", "MP3", "wav", "WAV", "aac", "AAC", "flac", "FLAC",
     "m4a", "M4A", "ogg", "OGG", "wma", "WMA",
 ];
@@ -80,12 +80,18 @@ pub fn init_db() -> SqliteResult<()> {
         [],
     )?;
 
-    // 修复 .clip 文件分类：旧版 indexer 将 .clip 归为 "other"，应为 "image"
+    // 修复设计源文件分类：旧版 indexer 将 .clip / .psd / .kra / .ora 归为 "other"，应为 "image"
     conn.execute(
-        "UPDATE file_index SET file_type = 'image' WHERE extension = 'clip' AND file_type = 'other'",
+        "UPDATE file_index SET file_type = 'image' WHERE lower(extension) IN ('clip', 'psd', 'psb', 'kra', 'ora') AND file_type = 'other'",
         [],
     ).ok(); // file_index 表可能尚未创建，忽略错误
 
+    // 相机 RAW 同样改为 "image"（缩略图取内嵌预览图）
+    conn.execute(
+        "UPDATE file_index SET file_type = 'image' WHERE lower(extension) IN ('dng', 'cr2', 'cr3', 'nef', 'arw', 'raf', 'orf') AND file_type = 'other'",
+        [],
+    ).ok();
+
     // 创建下载历史表
     conn.execute(
         "CREATE TABLE IF NOT EXISTS download_history (
@@ -145,6 +151,11 @@ pub fn init_db() -> SqliteResult<()> {
 
     // 迁移：为 file_index 添加 source_url 列（已有数据库兼容）
     let _ = conn.execute("ALTER TABLE file_index ADD COLUMN source_url TEXT", []);
+    // 迁移：为 file_index 添加 blurhash 列（缩略图占位）
+    let _ = conn.execute("ALTER TABLE file_index ADD COLUMN blurhash TEXT", []);
+    // 迁移：为 file_index 添加动图标记和帧数（按文件头检测，已有记录在下次扫描文件夹时补齐）
+    let _ = conn.execute("ALTER TABLE file_index ADD COLUMN animated INTEGER NOT NULL DEFAULT 0", []);
+    let _ = conn.execute("ALTER TABLE file_index ADD COLUMN frame_count INTEGER", []);
 
     // 创建文件夹索引表
     conn.execute(
@@ -238,6 +249,109 @@ pub fn init_db() -> SqliteResult<()> {
     // 迁移：为 metrics_history 添加 system_uptime_seconds 列
     let _ = conn.execute("ALTER TABLE metrics_history ADD COLUMN system_uptime_seconds INTEGER NOT NULL DEFAULT 0", []);
 
+    // 文件播放统计表（播放次数 / 最近播放 / 评分，供加权随机播放使用）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS file_stats (
+            file_uuid TEXT PRIMARY KEY,
+            play_count INTEGER NOT NULL DEFAULT 0,
+            last_played_at TEXT,
+            rating INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+
+    // 服务端随机播放会话表（order_list 为本轮播放顺序的 UUID JSON 数组）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS playlist_sessions (
+            id TEXT PRIMARY KEY,
+            folder_path TEXT NOT NULL,
+            file_type TEXT,
+            mode TEXT NOT NULL,
+            weight_by TEXT,
+            seed INTEGER NOT NULL,
+            cycle INTEGER NOT NULL DEFAULT 0,
+            cursor INTEGER NOT NULL DEFAULT 0,
+            order_list TEXT NOT NULL DEFAULT '[]',
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // 阅读进度表（PDF / CBZ / EPUB 等分页文档，按文件 UUID 跨设备同步）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS reading_progress (
+            file_uuid TEXT PRIMARY KEY,
+            page INTEGER NOT NULL DEFAULT 1,
+            total_pages INTEGER NOT NULL DEFAULT 0,
+            finished INTEGER NOT NULL DEFAULT 0,
+            position TEXT,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_reading_progress_updated ON reading_progress(updated_at)", [])?;
+
+    // 响度分析表（EBU R128，按文件 UUID；integrated_lufs 为 NULL 表示没有音轨）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS loudness (
+            file_uuid TEXT PRIMARY KEY,
+            integrated_lufs REAL,
+            true_peak_dbtp REAL,
+            lra REAL,
+            modified_at TEXT NOT NULL,
+            analyzed_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // 阅读书签表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS reading_bookmarks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            file_uuid TEXT NOT NULL,
+            page INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            position TEXT,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_reading_bookmarks_file ON reading_bookmarks(file_uuid)", [])?;
+
+    // 音频标签表（扫描时读取；modified_at 与 file_index 不一致时重读）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audio_tags (
+            file_uuid TEXT PRIMARY KEY,
+            title TEXT,
+            artist TEXT,
+            album TEXT,
+            album_artist TEXT,
+            track_number INTEGER,
+            disc_number INTEGER,
+            year INTEGER,
+            genre TEXT,
+            modified_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_audio_tags_album ON audio_tags(album)", [])?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_audio_tags_artist ON audio_tags(COALESCE(album_artist, artist))", [])?;
+
+    // 迁移：EPUB 原先归为 other，改为 ebook（未变化的文件不会被重新扫描）
+    let _ = conn.execute("UPDATE file_index SET file_type = 'ebook' WHERE file_type = 'other' AND lower(extension) = 'epub'", []);
+
+    // 迁移：文本文档原先归为 other，改为 document
+    let document_exts = crate::preview::models::DOCUMENT_EXTENSIONS
+        .iter()
+        .map(|e| format!("'{}'", e))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let _ = conn.execute(
+        &format!("UPDATE file_index SET file_type = 'document' WHERE file_type = 'other' AND lower(extension) IN ({})", document_exts),
+        [],
+    );
+
     // 执行数据迁移（从旧 JSON 文件）
     migrate_from_json(&conn)?;
 
@@ -0,0 +1,370 @@
+// 视频剪辑功能：按入点 / 出点导出片段（无损流复制 / 重编码 MP4 / GIF / 动画 WebP）
+// 后台任务执行 ffmpeg，输出写到源文件同目录并立即编入索引
+use actix_web::{web, HttpResponse, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::sync::Mutex;
+use super::models::{ClipFormat, ClipRequest, ClipStatus, ClipTask, ClipTaskListResponse};
+use super::utils::get_unique_path;
+
+/// GIF / WebP 默认帧率与上限
+const DEFAULT_FPS: u32 = 12;
+const MAX_FPS: u32 = 30;
+
+/// GIF / WebP 默认宽度；输出宽度范围
+const DEFAULT_ANIMATION_WIDTH: u32 = 480;
+const WIDTH_RANGE: (u32, u32) = (16, 3840);
+
+/// GIF / WebP 最长时长（秒），再长体积失控
+const MAX_ANIMATION_SECONDS: f64 = 60.0;
+
+/// 剪辑任务管理器
+pub struct ClipTaskManager {
+    tasks: Arc<Mutex<HashMap<String, ClipTask>>>,
+    cancel_flags: Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>,
+}
+
+/// 秒数格式化为文件名片段：83.5 → 1m23s，3723 → 1h02m03s
+fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (h, m, s) = (total / 3600, total / 60 % 60, total % 60);
+    if h > 0 {
+        format!("{}h{:02}m{:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m{:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+/// 输出文件名：<源文件名>_<入点>-<出点>.<扩展名>
+fn clip_file_name(stem: &str, start: f64, end: f64, ext: &str) -> String {
+    format!("{}_{}-{}.{}", stem, format_timestamp(start), format_timestamp(end), ext)
+}
+
+/// 输出扩展名：流复制保持源容器
+fn output_extension(format: ClipFormat, source_ext: &str) -> String {
+    match format {
+        ClipFormat::Copy => source_ext.to_string(),
+        ClipFormat::Mp4 => "mp4".to_string(),
+        ClipFormat::Gif => "gif".to_string(),
+        ClipFormat::Webp => "webp".to_string(),
+    }
+}
+
+/// 组装 ffmpeg 参数；进度以 key=value 行写到 stdout
+fn ffmpeg_args(source: &str, output: &str, format: ClipFormat, start: f64, end: f64, fps: u32, width: Option<u32>) -> Vec<String> {
+    let duration = format!("{:.3}", end - start);
+    let mut args: Vec<String> = [
+        "-hide_banner", "-nostdin", "-loglevel", "error", "-progress", "pipe:1", "-nostats",
+        "-ss", &format!("{:.3}", start), "-t", &duration, "-i", source,
+    ].iter().map(|s| s.to_string()).collect();
+
+    let scale = |w: u32| format!("scale={}:-2:flags=lanczos", w);
+    match format {
+        ClipFormat::Copy => {
+            args.extend(["-map", "0:v?", "-map", "0:a?", "-c", "copy", "-avoid_negative_ts", "make_zero"].map(String::from));
+        }
+        ClipFormat::Mp4 => {
+            args.extend(["-map", "0:v:0", "-map", "0:a:0?"].map(String::from));
+            if let Some(w) = width {
+                args.extend(["-vf".to_string(), scale(w)]);
+            }
+            args.extend([
+                "-c:v", "libx264", "-preset", "veryfast", "-crf", "20", "-pix_fmt", "yuv420p",
+                "-c:a", "aac", "-b:a", "160k", "-movflags", "+faststart",
+            ].map(String::from));
+        }
+        ClipFormat::Gif => {
+            // 单次滤镜图内生成调色板再套用，比默认 256 色网页调色板清晰得多
+            let filter = format!(
+                "fps={},{},split[a][b];[a]palettegen=stats_mode=diff[p];[b][p]paletteuse=dither=bayer:bayer_scale=5",
+                fps, scale(width.unwrap_or(DEFAULT_ANIMATION_WIDTH))
+            );
+            args.extend(["-filter_complex".to_string(), filter, "-an".to_string(), "-loop".to_string(), "0".to_string()]);
+        }
+        ClipFormat::Webp => {
+            let filter = format!("fps={},{}", fps, scale(width.unwrap_or(DEFAULT_ANIMATION_WIDTH)));
+            args.extend(["-vf".to_string(), filter]);
+            args.extend(["-an", "-c:v", "libwebp_anim", "-quality", "80", "-loop", "0"].map(String::from));
+        }
+    }
+    args.extend(["-y".to_string(), output.to_string()]);
+    args
+}
+
+/// 解析 -progress 输出的已处理时长（秒）；out_time_ms 实际单位也是微秒
+fn parse_progress_seconds(line: &str) -> Option<f64> {
+    let (key, value) = line.trim().split_once('=')?;
+    if key != "out_time_us" && key != "out_time_ms" {
+        return None;
+    }
+    value.parse::<f64>().ok().map(|us| us / 1_000_000.0)
+}
+
+impl ClipTaskManager {
+    /// 创建新的任务管理器
+    pub fn new() -> Self {
+        ClipTaskManager {
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            cancel_flags: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 校验请求、预占输出文件名并在后台启动 ffmpeg
+    pub async fn create_task(&self, req: ClipRequest) -> std::result::Result<String, String> {
+        if !(req.start >= 0.0 && req.end > req.start) {
+            return Err("出点必须晚于入点".to_string());
+        }
+        let animated = matches!(req.format, ClipFormat::Gif | ClipFormat::Webp);
+        if animated && req.end - req.start > MAX_ANIMATION_SECONDS {
+            return Err(format!("GIF / WebP 最长 {} 秒", MAX_ANIMATION_SECONDS));
+        }
+        let fps = req.fps.unwrap_or(DEFAULT_FPS).clamp(1, MAX_FPS);
+        let width = req.width.map(|w| w.clamp(WIDTH_RANGE.0, WIDTH_RANGE.1));
+
+        let uuid = req.uuid.clone();
+        let (source_path, output_path) = tokio::task::spawn_blocking(move || {
+            let file = crate::indexer::storage::get_file_by_uuid(&uuid)
+                .map_err(|e| format!("查询索引失败: {}", e))?
+                .ok_or("UUID 对应的文件不存在")?;
+            if file.file_type != "video" {
+                return Err("仅支持视频文件".to_string());
+            }
+            let source_path = file.current_path.ok_or("文件路径为空")?;
+            let source = Path::new(&source_path);
+            if !source.is_file() {
+                return Err("文件不存在".to_string());
+            }
+            let dir = source.parent().ok_or("无法获取文件目录")?;
+            let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("clip");
+            let name = clip_file_name(stem, req.start, req.end, &output_extension(req.format, &file.extension));
+            // 先创建空文件占住名字，避免并发任务拿到同一个路径
+            let output = get_unique_path(dir, &name);
+            std::fs::File::create(&output).map_err(|e| format!("无法创建输出文件: {}", e))?;
+            Ok((source_path, output.to_string_lossy().to_string()))
+        }).await.map_err(|e| format!("任务执行失败: {}", e))??;
+
+        let task_id = uuid::Uuid::new_v4().to_string();
+        let task = ClipTask {
+            id: task_id.clone(),
+            source_uuid: req.uuid.clone(),
+            source_path: source_path.clone(),
+            format: req.format,
+            start: req.start,
+            end: req.end,
+            status: ClipStatus::Running,
+            progress: 0.0,
+            output_path: output_path.clone(),
+            file_uuid: None,
+            error: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        };
+        self.tasks.lock().await.insert(task_id.clone(), task);
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.cancel_flags.lock().await.insert(task_id.clone(), cancel.clone());
+
+        let args = ffmpeg_args(&source_path, &output_path, req.format, req.start, req.end, fps, width);
+        let tasks = self.tasks.clone();
+        let cancel_flags = self.cancel_flags.clone();
+        let id = task_id.clone();
+        actix_web::rt::spawn(async move {
+            let result = Self::run_ffmpeg(&tasks, &id, args, req.end - req.start, &cancel).await;
+            cancel_flags.lock().await.remove(&id);
+            Self::finish(&tasks, &id, result, source_path, output_path).await;
+        });
+
+        Ok(task_id)
+    }
+
+    /// 运行 ffmpeg 并按 -progress 输出更新进度；取消时结束进程
+    async fn run_ffmpeg(
+        tasks: &Arc<Mutex<HashMap<String, ClipTask>>>,
+        task_id: &str,
+        args: Vec<String>,
+        duration: f64,
+        cancel: &AtomicBool,
+    ) -> std::result::Result<(), Option<String>> {
+        let mut child = tokio::process::Command::new(crate::preview::utils::get_ffmpeg_path())
+            .args(&args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| Some(format!("ffmpeg 启动失败: {}", e)))?;
+
+        let mut stderr = child.stderr.take().ok_or(Some("无法获取 stderr".to_string()))?;
+        let stderr_handle = tokio::spawn(async move {
+            let mut buf = String::new();
+            let _ = stderr.read_to_string(&mut buf).await;
+            buf
+        });
+
+        let stdout = child.stdout.take().ok_or(Some("无法获取 stdout".to_string()))?;
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if cancel.load(Ordering::Relaxed) {
+                let _ = child.kill().await;
+                return Err(None);
+            }
+            if let Some(done) = parse_progress_seconds(&line) {
+                if let Some(task) = tasks.lock().await.get_mut(task_id) {
+                    task.progress = (done / duration * 100.0).clamp(0.0, 99.0) as f32;
+                }
+            }
+        }
+
+        let status = child.wait().await.map_err(|e| Some(format!("等待 ffmpeg 失败: {}", e)))?;
+        if cancel.load(Ordering::Relaxed) {
+            return Err(None);
+        }
+        if !status.success() {
+            let stderr = stderr_handle.await.unwrap_or_default();
+            let last = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("").trim().to_string();
+            return Err(Some(format!("ffmpeg 处理失败: {}", last)));
+        }
+        Ok(())
+    }
+
+    /// 收尾：成功时编入索引；失败 / 取消时删除不完整的输出文件
+    /// result 的 Err(None) 表示已取消
+    async fn finish(
+        tasks: &Arc<Mutex<HashMap<String, ClipTask>>>,
+        task_id: &str,
+        result: std::result::Result<(), Option<String>>,
+        source_path: String,
+        output_path: String,
+    ) {
+        let (status, file_uuid, error) = match result {
+            Ok(()) => {
+                let output = output_path.clone();
+                let indexed = tokio::task::spawn_blocking(move || {
+                    let source_folder = crate::indexer::storage::find_source_folder(&source_path)
+                        .or_else(|| Path::new(&output).parent().map(|p| p.to_string_lossy().to_string()))
+                        .unwrap_or_default();
+                    crate::indexer::scanner::index_single_file(&output, &source_folder, None)
+                        .map(|f| f.uuid)
+                        .map_err(|e| e.to_string())
+                }).await.map_err(|e| e.to_string()).and_then(|r| r);
+                // 索引失败不影响输出文件，下次扫描会补上
+                match indexed {
+                    Ok(uuid) => (ClipStatus::Completed, Some(uuid), None),
+                    Err(e) => {
+                        eprintln!("[clip] 索引失败: {} - {}", output_path, e);
+                        (ClipStatus::Completed, None, None)
+                    }
+                }
+            }
+            Err(error) => {
+                let _ = tokio::fs::remove_file(&output_path).await;
+                match error {
+                    Some(e) => (ClipStatus::Failed, None, Some(e)),
+                    None => (ClipStatus::Cancelled, None, None),
+                }
+            }
+        };
+
+        if let Some(task) = tasks.lock().await.get_mut(task_id) {
+            if status == ClipStatus::Completed {
+                task.progress = 100.0;
+            }
+            task.status = status;
+            task.file_uuid = file_uuid;
+            task.error = error;
+        }
+    }
+
+    /// 获取单个任务
+    pub async fn get_task(&self, task_id: &str) -> Option<ClipTask> {
+        self.tasks.lock().await.get(task_id).cloned()
+    }
+
+    /// 获取所有任务（按创建时间倒序）
+    pub async fn get_all_tasks(&self) -> Vec<ClipTask> {
+        let mut tasks: Vec<ClipTask> = self.tasks.lock().await.values().cloned().collect();
+        tasks.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        tasks
+    }
+
+    /// 删除任务：进行中的任务标记取消（后台结束 ffmpeg 并删除输出），已结束的从列表移除
+    pub async fn delete_task(&self, task_id: &str) -> bool {
+        if let Some(cancel) = self.cancel_flags.lock().await.get(task_id) {
+            cancel.store(true, Ordering::Relaxed);
+            return true;
+        }
+        self.tasks.lock().await.remove(task_id).is_some()
+    }
+}
+
+impl Default for ClipTaskManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// POST /api/file/clip
+/// 创建剪辑任务，立即返回任务 ID
+pub async fn create_clip(
+    req: web::Json<ClipRequest>,
+    manager: web::Data<ClipTaskManager>,
+) -> Result<HttpResponse> {
+    let task_id = manager
+        .create_task(req.into_inner())
+        .await
+        .map_err(actix_web::error::ErrorBadRequest)?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "task_id": task_id })))
+}
+
+/// GET /api/file/clip/tasks
+pub async fn get_clip_tasks(manager: web::Data<ClipTaskManager>) -> Result<HttpResponse> {
+    let tasks = manager.get_all_tasks().await;
+    Ok(HttpResponse::Ok().json(ClipTaskListResponse { tasks }))
+}
+
+/// GET /api/file/clip/task/{task_id}
+pub async fn get_clip_task(
+    path: web::Path<String>,
+    manager: web::Data<ClipTaskManager>,
+) -> Result<HttpResponse> {
+    match manager.get_task(&path.into_inner()).await {
+        Some(task) => Ok(HttpResponse::Ok().json(task)),
+        None => Err(actix_web::error::ErrorNotFound("任务不存在")),
+    }
+}
+
+/// DELETE /api/file/clip/task/{task_id}
+/// 取消进行中的任务，或从列表中移除已结束的任务
+pub async fn delete_clip_task(
+    path: web::Path<String>,
+    manager: web::Data<ClipTaskManager>,
+) -> Result<HttpResponse> {
+    if manager.delete_task(&path.into_inner()).await {
+        Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true })))
+    } else {
+        Err(actix_web::error::ErrorNotFound("任务不存在"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_file_name() {
+        assert_eq!(clip_file_name("stream", 83.5, 105.0, "mp4"), "stream_1m23s-1m45s.mp4");
+        assert_eq!(clip_file_name("vod", 3723.0, 3730.2, "gif"), "vod_1h02m03s-1h02m10s.gif");
+        assert_eq!(clip_file_name("a", 0.0, 9.9, "mkv"), "a_0s-9s.mkv");
+    }
+
+    #[test]
+    fn test_parse_progress_seconds() {
+        assert_eq!(parse_progress_seconds("out_time_us=12500000"), Some(12.5));
+        assert_eq!(parse_progress_seconds("out_time_ms=1000000\n"), Some(1.0));
+        assert_eq!(parse_progress_seconds("out_time_us=N/A"), None);
+        assert_eq!(parse_progress_seconds("frame=120"), None);
+    }
+}
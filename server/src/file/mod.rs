@@ -5,6 +5,9 @@ mod move_file;
 mod delete;
 mod info;
 mod utils;
+mod clip;
+
+pub use clip::ClipTaskManager;
 
 use actix_web::web;
 
@@ -13,5 +16,13 @@ pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/rename").route(web::post().to(rename::rename_file)))
        .service(web::resource("/move").route(web::post().to(move_file::move_file)))
        .service(web::resource("/delete").route(web::post().to(delete::delete_file)))
-       .service(web::resource("/info").route(web::get().to(info::get_file_info)));
+       .service(web::resource("/info").route(web::get().to(info::get_file_info)))
+       // 视频剪辑 / 导出 GIF：后台任务
+       .service(web::resource("/clip").route(web::post().to(clip::create_clip)))
+       .service(web::resource("/clip/tasks").route(web::get().to(clip::get_clip_tasks)))
+       .service(
+           web::resource("/clip/task/{task_id}")
+               .route(web::get().to(clip::get_clip_task))
+               .route(web::delete().to(clip::delete_clip_task))
+       );
 }
@@ -66,6 +66,62 @@ pub struct FileOperationResponse {
     pub new_path: Option<String>,
 }
 
+/// 剪辑输出格式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipFormat {
+    Copy,   // 无损流复制（切点对齐到关键帧，容器与源文件相同）
+    Mp4,    // 重新编码为 H.264 / AAC MP4（切点精确）
+    Gif,    // GIF 动图
+    Webp,   // 动画 WebP
+}
+
+/// 剪辑请求
+#[derive(Debug, Deserialize)]
+pub struct ClipRequest {
+    pub uuid: String,              // 源视频 UUID
+    pub start: f64,                // 入点（秒）
+    pub end: f64,                  // 出点（秒）
+    pub format: ClipFormat,
+    #[serde(default)]
+    pub fps: Option<u32>,          // GIF / WebP 帧率
+    #[serde(default)]
+    pub width: Option<u32>,        // 输出宽度，高度按比例（MP4 缺省保持原尺寸）
+}
+
+/// 剪辑任务状态
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipStatus {
+    Running,    // 处理中
+    Completed,  // 已完成
+    Failed,     // 失败
+    Cancelled,  // 已取消
+}
+
+/// 剪辑任务
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipTask {
+    pub id: String,                    // 任务 ID
+    pub source_uuid: String,           // 源视频 UUID
+    pub source_path: String,
+    pub format: ClipFormat,
+    pub start: f64,
+    pub end: f64,
+    pub status: ClipStatus,
+    pub progress: f32,                 // 进度 (0-100)
+    pub output_path: String,           // 输出文件（源文件同目录，重名自动加序号）
+    pub file_uuid: Option<String>,     // 输出文件索引后的 UUID
+    pub error: Option<String>,
+    pub created_at: String,
+}
+
+/// 剪辑任务列表响应
+#[derive(Debug, Serialize)]
+pub struct ClipTaskListResponse {
+    pub tasks: Vec<ClipTask>,
+}
+
 /// 支持的图片格式
 pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "bmp", "tiff", "svg"];
 
@@ -88,6 +88,16 @@ enum FilesResult {
     Err(String),
 }
 
+/// 给文件列表附上阅读进度（查询失败时不影响列表本身）
+fn attach_reading_progress(files: &mut [IndexedFile]) {
+    let uuids: Vec<String> = files.iter().map(|f| f.uuid.clone()).collect();
+    if let Ok(mut progress) = crate::reading::storage::get_progress_map(&uuids) {
+        for file in files.iter_mut() {
+            file.reading = progress.remove(&file.uuid);
+        }
+    }
+}
+
 /// GET /api/indexer/files — 惰性索引分页查询
 pub async fn files(
     query: web::Query<FilesQuery>,
@@ -137,13 +147,17 @@ pub async fn files(
             file_type.as_deref(),
             sort.as_deref(),
         ) {
-            Ok((files, total)) => FilesResult::Ok(PaginatedFilesResponse {
-                files,
-                total,
-                offset,
-                limit,
-                has_more: offset + limit < total,
-            }),
+            Ok((mut files, total)) => {
+                attach_reading_progress(&mut files);
+                storage::attach_loudness(&mut files);
+                FilesResult::Ok(PaginatedFilesResponse {
+                    files,
+                    total,
+                    offset,
+                    limit,
+                    has_more: offset + limit < total,
+                })
+            }
             Err(e) => FilesResult::Err(format!("查询失败: {}", e)),
         }
     }).await
@@ -162,7 +176,12 @@ pub async fn file_by_uuid(
     let uuid = query.uuid.clone();
 
     let result = tokio::task::spawn_blocking(move || {
-        storage::get_file_by_uuid(&uuid)
+        let mut file = storage::get_file_by_uuid(&uuid)?;
+        if let Some(f) = file.as_mut() {
+            f.reading = crate::reading::storage::get_progress(&f.uuid).ok().flatten();
+            storage::attach_loudness(std::slice::from_mut(f));
+        }
+        Ok::<_, rusqlite::Error>(file)
     }).await
         .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
         .map_err(|e| actix_web::error::ErrorInternalServerError(format!("数据库错误: {}", e)))?;
@@ -16,6 +16,31 @@ pub struct IndexedFile {
     pub modified_at: String,
     pub indexed_at: String,
     pub source_url: Option<String>,
+    /// BlurHash 占位图（生成缩略图时顺带计算，文件内容变化后清空）
+    #[serde(default)]
+    pub blurhash: Option<String>,
+    /// 是否为动图（GIF / WebP / APNG / AVIF 按文件头检测，与扩展名无关）
+    #[serde(default)]
+    pub animated: bool,
+    /// 帧数（仅可能为动图的格式填充，静图为 1）
+    #[serde(default)]
+    pub frame_count: Option<i64>,
+    /// 阅读进度（仅文件列表 / 单文件查询时填充，没有进度时不输出）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading: Option<crate::reading::models::ReadingProgress>,
+    /// 响度分析结果（仅音频 / 视频且已分析过时填充，未分析时不输出）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loudness: Option<Loudness>,
+}
+
+/// EBU R128 响度分析结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Loudness {
+    pub integrated_lufs: f64,          // 整体响度（LUFS）
+    pub true_peak_dbtp: Option<f64>,   // 真峰值（dBTP），静音时为 null
+    pub lra: Option<f64>,              // 响度范围（LU）
+    pub gain_db: f64,                  // 建议增益（dB），播放器乘以 10^(gain_db/20) 即可归一
+    pub analyzed_at: String,
 }
 
 /// 索引文件夹记录
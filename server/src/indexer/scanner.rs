@@ -7,6 +7,7 @@ use crate::preview::models::*;
 use rusqlite::params;
 use super::models::{IndexedFile, IndexedFolder};
 use super::storage;
+use crate::music::storage::TagScope;
 
 /// 扫描结果
 pub struct ScanResult {
@@ -27,13 +28,44 @@ pub fn classify_extension(ext: &str) -> String {
         "audio".to_string()
     } else if ext_lower == PDF_EXTENSION {
         "pdf".to_string()
-    } else if ext_lower == CLIP_EXTENSION {
+    } else if EBOOK_EXTENSIONS.contains(&ext_lower.as_str()) {
+        "ebook".to_string()
+    } else if DOCUMENT_EXTENSIONS.contains(&ext_lower.as_str()) {
+        "document".to_string()
+    } else if DESIGN_EXTENSIONS.contains(&ext_lower.as_str()) || RAW_EXTENSIONS.contains(&ext_lower.as_str()) {
         "image".to_string()
     } else {
         "other".to_string()
     }
 }
 
+/// 旧版索引没有检测动图：可能为动图的格式在 frame_count 为空时，即使 mtime 未变也要重新编入
+fn needs_animation_probe(ext: &str, frame_count: Option<i64>) -> bool {
+    frame_count.is_none() && crate::preview::animation::ANIMATABLE_EXTENSIONS.contains(&ext)
+}
+
+/// 单帧 GIF 与普通图片无异，归为 image；只有真正的动图才保留 gif 类型
+fn refine_file_type(file_type: String, animated: bool) -> String {
+    if file_type == "gif" && !animated {
+        "image".to_string()
+    } else {
+        file_type
+    }
+}
+
+/// 按文件头检测动图，返回 (是否动图, 帧数)
+fn probe_animation(path: &Path) -> (bool, Option<i64>) {
+    let frame_count = crate::preview::animation::probe_frame_count(path);
+    (frame_count.unwrap_or(1) > 1, frame_count.map(i64::from))
+}
+
+/// 为新增 / 变更的音频读取标签（按 modified_at 判断，旧版索引的音频在下次扫描时补齐）
+fn sync_audio_tags(scope: TagScope) {
+    if let Err(e) = crate::music::storage::sync_audio_tags(scope) {
+        eprintln!("读取音频标签失败: {}", e);
+    }
+}
+
 /// 判断文件夹是否需要重新扫描
 pub fn needs_rescan(folder_path: &str) -> bool {
     let indexed_at = match storage::get_folder_indexed_at(folder_path) {
@@ -92,6 +124,8 @@ pub fn scan_folder(folder_path: &str, source_folder: &str, skip_mark_missing: bo
         file_size: i64,
         created_at: String,
         modified_at: String,
+        animated: bool,
+        frame_count: Option<i64>,
     }
 
     let mut new_files: Vec<NewFile> = Vec::new();
@@ -118,7 +152,7 @@ pub fn scan_folder(folder_path: &str, source_folder: &str, skip_mark_missing: bo
 
         // mtime 未变 → 跳过（不需要读文件内容、不需要写 DB）
         if let Some(existing) = indexed_map.get(&path_str) {
-            if existing.modified_at == modified_at {
+            if existing.modified_at == modified_at && !needs_animation_probe(&existing.extension, existing.frame_count) {
                 continue;
             }
         }
@@ -137,8 +171,11 @@ pub fn scan_folder(folder_path: &str, source_folder: &str, skip_mark_missing: bo
             .to_string_lossy()
             .to_string();
 
+        let (animated, frame_count) = probe_animation(&entry_path);
         new_files.push(NewFile {
-            file_type: classify_extension(&ext),
+            file_type: refine_file_type(classify_extension(&ext), animated),
+            animated,
+            frame_count,
             path_str,
             ext,
             file_name,
@@ -167,6 +204,11 @@ pub fn scan_folder(folder_path: &str, source_folder: &str, skip_mark_missing: bo
             modified_at: file.modified_at.clone(),
             indexed_at: now.clone(),
             source_url: None,
+            blurhash: None,
+            animated: file.animated,
+            frame_count: file.frame_count,
+            reading: None,
+            loudness: None,
         };
 
         if let Err(e) = storage::fast_upsert_file_with_conn(&tx, &indexed_file) {
@@ -215,6 +257,12 @@ pub fn scan_folder(folder_path: &str, source_folder: &str, skip_mark_missing: bo
     storage::upsert_folder_with_conn(&tx, &folder)?;
 
     tx.commit()?;
+
+    // 提交后再入队，后台 worker 按路径查 UUID 时才能查到
+    for file in &new_files {
+        crate::preview::thumb_cache::enqueue(&file.path_str, &file.file_type);
+    }
+    sync_audio_tags(TagScope::Folder(folder_path));
     Ok(())
 }
 
@@ -247,6 +295,8 @@ pub fn scan_source_folder(source_folder: &str) -> Result<ScanResult, Box<dyn std
     // 单连接复用，避免每个文件都 get_connection()
     let conn = get_connection()?;
     let mut batch_count: u64 = 0;
+    // 本批新增/变更的文件，提交后交给缩略图预生成
+    let mut pregen_batch: Vec<(String, String)> = Vec::new();
 
     // 开启第一个事务
     conn.execute_batch("BEGIN")?;
@@ -325,10 +375,10 @@ pub fn scan_source_folder(source_folder: &str) -> Result<ScanResult, Box<dyn std
 
             // 使用同一连接检查 mtime
             let mut stmt = conn.prepare_cached(
-                "SELECT modified_at FROM file_index WHERE current_path = ?1"
+                "SELECT modified_at, frame_count FROM file_index WHERE current_path = ?1"
             )?;
-            let existing_mtime: Option<String> = stmt.query_row(params![path_str], |row| row.get(0)).ok();
-            if existing_mtime.as_deref() == Some(&modified_at) {
+            let existing: Option<(String, Option<i64>)> = stmt.query_row(params![path_str], |row| Ok((row.get(0)?, row.get(1)?))).ok();
+            if existing.is_some_and(|(mtime, frames)| mtime == modified_at && !needs_animation_probe(&ext, frames)) {
                 scanned_files += 1;
                 continue;
             }
@@ -343,10 +393,12 @@ pub fn scan_source_folder(source_folder: &str) -> Result<ScanResult, Box<dyn std
                 .to_string();
 
             // 不计算指纹 — 全量扫描的目的是快速建索引，指纹可以后续按需计算
+            let (animated, frame_count) = probe_animation(entry_path);
+            let file_type = refine_file_type(file_type, animated);
             let indexed_file = IndexedFile {
                 uuid: uuid::Uuid::new_v4().to_string(),
                 fingerprint: String::new(),
-                current_path: Some(path_str),
+                current_path: Some(path_str.clone()),
                 folder_path: folder_str,
                 file_name,
                 file_type,
@@ -356,9 +408,16 @@ pub fn scan_source_folder(source_folder: &str) -> Result<ScanResult, Box<dyn std
                 modified_at,
                 indexed_at: now.clone(),
                 source_url: None,
+                blurhash: None,
+                animated,
+                frame_count,
+                reading: None,
+                loudness: None,
             };
 
-            let _ = storage::fast_upsert_file_with_conn(&conn, &indexed_file);
+            if storage::fast_upsert_file_with_conn(&conn, &indexed_file).is_ok() {
+                pregen_batch.push((path_str, indexed_file.file_type));
+            }
             scanned_files += 1;
         }
 
@@ -367,11 +426,20 @@ pub fn scan_source_folder(source_folder: &str) -> Result<ScanResult, Box<dyn std
         if batch_count >= 500 {
             conn.execute_batch("COMMIT; BEGIN")?;
             batch_count = 0;
+            for (path, file_type) in pregen_batch.drain(..) {
+                crate::preview::thumb_cache::enqueue(&path, &file_type);
+            }
         }
     }
 
     // 提交最后一批
     conn.execute_batch("COMMIT")?;
+    for (path, file_type) in pregen_batch {
+        crate::preview::thumb_cache::enqueue(&path, &file_type);
+    }
+    // mtime 未变的文件不会入队，还没有 BlurHash 的由回填补上
+    crate::preview::thumb_cache::request_backfill();
+    sync_audio_tags(TagScope::Source(source_folder));
 
     Ok(ScanResult {
         scanned_files,
@@ -381,7 +449,6 @@ pub fn scan_source_folder(source_folder: &str) -> Result<ScanResult, Box<dyn std
 
 /// 单文件索引：上传/下载完成后立即将文件编入索引，避免扫描整个目录
 /// source_url: 下载来源 URL（仅下载任务传入，上传和扫描传 None）
-#[allow(dead_code)]
 pub fn index_single_file(file_path: &str, source_folder: &str, source_url: Option<&str>) -> Result<IndexedFile, Box<dyn std::error::Error + Send + Sync>> {
     let entry_path = Path::new(file_path);
     if !entry_path.is_file() {
@@ -409,7 +476,7 @@ pub fn index_single_file(file_path: &str, source_folder: &str, source_url: Optio
 
     // 如果已索引且 mtime 未变，直接返回
     if let Ok(Some(existing)) = storage::get_file_by_path(file_path) {
-        if existing.modified_at == modified_at {
+        if existing.modified_at == modified_at && !needs_animation_probe(&existing.extension, existing.frame_count) {
             return Ok(existing);
         }
     }
@@ -423,6 +490,8 @@ pub fn index_single_file(file_path: &str, source_folder: &str, source_url: Optio
         .map(|p| p.to_string_lossy().to_string())
         .unwrap_or_default();
 
+    let (animated, frame_count) = probe_animation(entry_path);
+    let file_type = refine_file_type(file_type, animated);
     let indexed_file = IndexedFile {
         uuid: uuid::Uuid::new_v4().to_string(),
         fingerprint: String::new(),
@@ -436,9 +505,18 @@ pub fn index_single_file(file_path: &str, source_folder: &str, source_url: Optio
         modified_at,
         indexed_at: now.clone(),
         source_url: source_url.map(|s| s.to_string()),
+        blurhash: None,
+        animated,
+        frame_count,
+        reading: None,
+        loudness: None,
     };
 
     storage::upsert_file(&indexed_file)?;
+    crate::preview::thumb_cache::enqueue(file_path, &indexed_file.file_type);
+    if indexed_file.file_type == "audio" {
+        sync_audio_tags(TagScope::Folder(&folder_path));
+    }
 
     // 确保文件所在文件夹也在 folder_index 中
     let depth = if folder_path == source_folder {
@@ -1,6 +1,6 @@
 // SQLite CRUD 操作
 use rusqlite::{params, Connection};
-use super::models::{IndexedFile, IndexedFolder, BreadcrumbItem};
+use super::models::{IndexedFile, IndexedFolder, BreadcrumbItem, Loudness};
 use crate::database::get_connection;
 
 /// 插入或更新文件索引
@@ -8,8 +8,8 @@ use crate::database::get_connection;
 pub fn upsert_file(file: &IndexedFile) -> Result<(), rusqlite::Error> {
     let conn = get_connection()?;
     conn.execute(
-        "INSERT INTO file_index (uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        "INSERT INTO file_index (uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url, animated, frame_count)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
          ON CONFLICT(uuid) DO UPDATE SET
             fingerprint = excluded.fingerprint,
             current_path = excluded.current_path,
@@ -20,7 +20,10 @@ pub fn upsert_file(file: &IndexedFile) -> Result<(), rusqlite::Error> {
             file_size = excluded.file_size,
             modified_at = excluded.modified_at,
             indexed_at = excluded.indexed_at,
-            source_url = COALESCE(excluded.source_url, file_index.source_url)",
+            source_url = COALESCE(excluded.source_url, file_index.source_url),
+            animated = excluded.animated,
+            frame_count = excluded.frame_count,
+            blurhash = CASE WHEN excluded.modified_at = file_index.modified_at THEN file_index.blurhash ELSE NULL END",
         params![
             file.uuid,
             file.fingerprint,
@@ -34,6 +37,8 @@ pub fn upsert_file(file: &IndexedFile) -> Result<(), rusqlite::Error> {
             file.modified_at,
             file.indexed_at,
             file.source_url,
+            file.animated,
+            file.frame_count,
         ],
     )?;
     Ok(())
@@ -61,10 +66,142 @@ pub fn complete_pending_file(uuid: &str, file_path: &str, file_name: &str, file_
         .parent()
         .map(|p| p.to_string_lossy().to_string())
         .unwrap_or_default();
+    let frame_count = crate::preview::animation::probe_frame_count(std::path::Path::new(file_path));
+    let animated = frame_count.unwrap_or(1) > 1;
+    conn.execute(
+        "UPDATE file_index SET current_path = ?1, folder_path = ?2, file_name = ?3, file_type = ?4, extension = ?5, file_size = ?6, modified_at = ?7, indexed_at = ?7, blurhash = NULL,
+            animated = ?8, frame_count = ?9
+         WHERE uuid = ?10",
+        params![file_path, folder_path, file_name, file_type, extension, file_size, now, animated, frame_count, uuid],
+    )?;
+    Ok(())
+}
+
+/// 写入 BlurHash（缩略图生成时调用）
+pub fn set_blurhash(uuid: &str, blurhash: &str) -> Result<(), rusqlite::Error> {
+    let conn = get_connection()?;
     conn.execute(
-        "UPDATE file_index SET current_path = ?1, folder_path = ?2, file_name = ?3, file_type = ?4, extension = ?5, file_size = ?6, modified_at = ?7, indexed_at = ?7
-         WHERE uuid = ?8",
-        params![file_path, folder_path, file_name, file_type, extension, file_size, now, uuid],
+        "UPDATE file_index SET blurhash = ?1 WHERE uuid = ?2",
+        params![blurhash, uuid],
+    )?;
+    Ok(())
+}
+
+/// 还没有 BlurHash 的现存文件（按 UUID 分页，供缩略图 worker 回填），返回 (uuid, 路径)
+pub fn get_files_missing_blurhash(
+    after_uuid: &str,
+    file_types: &[&str],
+    limit: i64,
+) -> Result<Vec<(String, String)>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let placeholders = file_types.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+    let query = format!(
+        "SELECT uuid, current_path FROM file_index
+         WHERE blurhash IS NULL AND current_path IS NOT NULL AND uuid > ? AND file_type IN ({})
+         ORDER BY uuid LIMIT ?",
+        placeholders
+    );
+    let mut values: Vec<&dyn rusqlite::ToSql> = vec![&after_uuid];
+    values.extend(file_types.iter().map(|t| t as &dyn rusqlite::ToSql));
+    values.push(&limit);
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(values.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// 待做响度分析的音频 / 视频：没有结果或文件已变更（modified_at 不一致）；force 时全部重做
+pub fn get_files_needing_loudness(source_folder: &str, force: bool) -> Result<Vec<IndexedFile>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let query = format!(
+        "SELECT f.uuid, f.fingerprint, f.current_path, f.folder_path, f.file_name, f.file_type, f.extension, f.file_size, f.created_at, f.modified_at, f.indexed_at, f.source_url, f.blurhash, f.animated, f.frame_count
+         FROM file_index f LEFT JOIN loudness l ON l.file_uuid = f.uuid
+         WHERE (f.folder_path = ?1 OR f.folder_path LIKE ?2) AND f.current_path IS NOT NULL
+           AND f.file_type IN ('audio', 'video'){}
+         ORDER BY f.current_path",
+        if force { "" } else { " AND (l.file_uuid IS NULL OR l.modified_at != f.modified_at)" }
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let files = stmt.query_map(params![source_folder, format!("{}/%", source_folder)], map_file_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(files)
+}
+
+/// 删除失效的响度结果：文件已从索引移除，或分析后又被修改
+pub fn delete_stale_loudness() -> Result<usize, rusqlite::Error> {
+    let conn = get_connection()?;
+    conn.execute(
+        "DELETE FROM loudness WHERE NOT EXISTS (
+             SELECT 1 FROM file_index f WHERE f.uuid = loudness.file_uuid AND f.modified_at = loudness.modified_at
+         )",
+        [],
+    )
+}
+
+/// 保存响度分析结果；integrated 为 None 表示没有音轨（也记录下来，避免每次都重试）
+pub fn save_loudness(
+    uuid: &str,
+    modified_at: &str,
+    integrated: Option<f64>,
+    true_peak: Option<f64>,
+    lra: Option<f64>,
+) -> Result<(), rusqlite::Error> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT OR REPLACE INTO loudness (file_uuid, integrated_lufs, true_peak_dbtp, lra, modified_at, analyzed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![uuid, integrated, true_peak, lra, modified_at, chrono::Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// 批量查询响度（没有音轨的文件不返回；文件分析后又被修改的，旧结果已失效，也不返回）
+pub fn get_loudness_map(uuids: &[String]) -> Result<std::collections::HashMap<String, Loudness>, rusqlite::Error> {
+    if uuids.is_empty() {
+        return Ok(std::collections::HashMap::new());
+    }
+    let conn = get_connection()?;
+    let placeholders = uuids.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+    let query = format!(
+        "SELECT l.file_uuid, l.integrated_lufs, l.true_peak_dbtp, l.lra, l.analyzed_at
+         FROM loudness l INNER JOIN file_index f ON f.uuid = l.file_uuid AND f.modified_at = l.modified_at
+         WHERE l.integrated_lufs IS NOT NULL AND l.file_uuid IN ({})",
+        placeholders
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(uuids.iter()), |row| {
+        let integrated: f64 = row.get(1)?;
+        let true_peak: Option<f64> = row.get(2)?;
+        Ok((row.get::<_, String>(0)?, Loudness {
+            integrated_lufs: integrated,
+            true_peak_dbtp: true_peak,
+            lra: row.get(3)?,
+            gain_db: crate::preview::loudness::suggested_gain(integrated, true_peak),
+            analyzed_at: row.get(4)?,
+        }))
+    })?;
+    rows.collect()
+}
+
+/// 给文件列表附上响度（查询失败时不影响列表本身）
+pub fn attach_loudness(files: &mut [IndexedFile]) {
+    let uuids: Vec<String> = files
+        .iter()
+        .filter(|f| f.file_type == "audio" || f.file_type == "video")
+        .map(|f| f.uuid.clone())
+        .collect();
+    if let Ok(mut loudness) = get_loudness_map(&uuids) {
+        for file in files.iter_mut() {
+            file.loudness = loudness.remove(&file.uuid);
+        }
+    }
+}
+
+/// 更新文件大小（原地重封装后内容长度变化，mtime 保持不变）
+pub fn set_file_size(uuid: &str, file_size: i64) -> Result<(), rusqlite::Error> {
+    let conn = get_connection()?;
+    conn.execute(
+        "UPDATE file_index SET file_size = ?1 WHERE uuid = ?2",
+        params![file_size, uuid],
     )?;
     Ok(())
 }
@@ -84,8 +221,8 @@ pub fn delete_pending_file(uuid: &str) -> Result<(), rusqlite::Error> {
 /// - 已有文件（路径已存在）：更新元数据，保留已有的 uuid、fingerprint、source_url
 pub fn fast_upsert_file_with_conn(conn: &Connection, file: &IndexedFile) -> Result<(), rusqlite::Error> {
     conn.execute(
-        "INSERT INTO file_index (uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        "INSERT INTO file_index (uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url, animated, frame_count)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
          ON CONFLICT(current_path) DO UPDATE SET
             folder_path = excluded.folder_path,
             file_name = excluded.file_name,
@@ -94,7 +231,10 @@ pub fn fast_upsert_file_with_conn(conn: &Connection, file: &IndexedFile) -> Resu
             file_size = excluded.file_size,
             modified_at = excluded.modified_at,
             indexed_at = excluded.indexed_at,
-            source_url = COALESCE(excluded.source_url, file_index.source_url)",
+            source_url = COALESCE(excluded.source_url, file_index.source_url),
+            animated = excluded.animated,
+            frame_count = excluded.frame_count,
+            blurhash = CASE WHEN excluded.modified_at = file_index.modified_at THEN file_index.blurhash ELSE NULL END",
         params![
             file.uuid,
             file.fingerprint,
@@ -108,6 +248,8 @@ pub fn fast_upsert_file_with_conn(conn: &Connection, file: &IndexedFile) -> Resu
             file.modified_at,
             file.indexed_at,
             file.source_url,
+            file.animated,
+            file.frame_count,
         ],
     )?;
     Ok(())
@@ -189,7 +331,7 @@ pub fn get_files_paginated(
 
         // 参数顺序：folder_path, ft, ...ignored_files, limit, offset
         let query = format!(
-            "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url
+            "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url, blurhash, animated, frame_count
              FROM file_index WHERE folder_path = ? AND file_type = ? AND current_path IS NOT NULL{}
              ORDER BY {} LIMIT ? OFFSET ?",
             ignore_clause, order_clause
@@ -220,7 +362,7 @@ pub fn get_files_paginated(
 
         // 参数顺序：folder_path, ...ignored_files, limit, offset
         let query = format!(
-            "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url
+            "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url, blurhash, animated, frame_count
              FROM file_index WHERE folder_path = ? AND current_path IS NOT NULL{}
              ORDER BY {} LIMIT ? OFFSET ?",
             ignore_clause, order_clause
@@ -240,11 +382,31 @@ pub fn get_files_paginated(
     Ok((files, total))
 }
 
+/// 查询源文件夹（含子文件夹）下指定扩展名的所有文件
+pub fn get_files_by_extensions(source_folder: &str, extensions: &[&str]) -> Result<Vec<IndexedFile>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let placeholders = extensions.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+    let query = format!(
+        "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url, blurhash, animated, frame_count
+         FROM file_index WHERE (folder_path = ? OR folder_path LIKE ?) AND current_path IS NOT NULL AND extension IN ({})
+         ORDER BY current_path",
+        placeholders
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let all_params: Vec<String> = [source_folder.to_string(), format!("{}/%", source_folder)]
+        .into_iter()
+        .chain(extensions.iter().map(|e| e.to_string()))
+        .collect();
+    let files = stmt.query_map(rusqlite::params_from_iter(all_params.iter()), map_file_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(files)
+}
+
 /// 通过 UUID 查询文件
 pub fn get_file_by_uuid(uuid: &str) -> Result<Option<IndexedFile>, rusqlite::Error> {
     let conn = get_connection()?;
     let mut stmt = conn.prepare(
-        "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url
+        "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url, blurhash, animated, frame_count
          FROM file_index WHERE uuid = ?1"
     )?;
     let mut rows = stmt.query_map(params![uuid], map_file_row)?;
@@ -260,7 +422,7 @@ pub fn get_file_by_uuid(uuid: &str) -> Result<Option<IndexedFile>, rusqlite::Err
 pub fn get_file_by_path(path: &str) -> Result<Option<IndexedFile>, rusqlite::Error> {
     let conn = get_connection()?;
     let mut stmt = conn.prepare(
-        "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url
+        "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url, blurhash, animated, frame_count
          FROM file_index WHERE current_path = ?1"
     )?;
     let mut rows = stmt.query_map(params![path], map_file_row)?;
@@ -276,7 +438,7 @@ pub fn get_file_by_path(path: &str) -> Result<Option<IndexedFile>, rusqlite::Err
 pub fn get_indexed_files_for_folder(folder_path: &str) -> Result<std::collections::HashMap<String, IndexedFile>, rusqlite::Error> {
     let conn = get_connection()?;
     let mut stmt = conn.prepare(
-        "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url
+        "SELECT uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url, blurhash, animated, frame_count
          FROM file_index WHERE folder_path = ?1 AND current_path IS NOT NULL"
     )?;
     let files = stmt.query_map(params![folder_path], map_file_row)?;
@@ -525,5 +687,10 @@ pub fn map_file_row(row: &rusqlite::Row) -> Result<IndexedFile, rusqlite::Error>
         modified_at: row.get(9)?,
         indexed_at: row.get(10)?,
         source_url: row.get(11)?,
+        blurhash: row.get(12)?,
+        animated: row.get(13)?,
+        frame_count: row.get(14)?,
+        reading: None,
+        loudness: None,
     })
 }
@@ -11,6 +11,8 @@ mod preview;
 mod browser;
 mod indexer;
 mod tag;
+mod reading;
+mod music;
 mod metrics;
 mod playlist;
 
@@ -19,6 +21,7 @@ mod static_files;
 mod auth;
 mod logger;
 mod database;
+mod charset;
 pub mod tools;
 mod updater;
 
@@ -156,6 +159,9 @@ async fn main() -> std::io::Result<()> {
     // 初始化上传器任务管理器
     let upload_task_manager = web::Data::new(transfer::upload::TaskManager::new());
 
+    // 初始化视频剪辑任务管理器
+    let clip_task_manager = web::Data::new(file::ClipTaskManager::new());
+
     // 初始化分片上传会话管理器
     let chunk_upload_manager = web::Data::new(transfer::upload::ChunkUploadManager::new());
 
@@ -166,6 +172,9 @@ async fn main() -> std::io::Result<()> {
     let metrics_state = web::Data::new(Arc::new(RwLock::new(metrics::models::MetricsState::new())));
     metrics::collector::start_collector(metrics_state.get_ref().clone());
 
+    // 启动缩略图后台预生成
+    preview::thumb_cache::start_pregen_worker();
+
     // 读取 API Key (优先级: secret.json > 环境变量 > 随机生成)
     fn load_api_key_from_secret() -> Option<String> {
         use std::fs;
@@ -215,6 +224,7 @@ async fn main() -> std::io::Result<()> {
             .app_data(download_task_manager.clone())
             .app_data(upload_task_manager.clone())
             .app_data(chunk_upload_manager.clone())
+            .app_data(clip_task_manager.clone())
             .app_data(scan_status.clone())
             .app_data(metrics_state.clone())
             // 健康检查 API（不需要认证）
@@ -247,6 +257,10 @@ async fn main() -> std::io::Result<()> {
             .service(web::scope("/api/indexer").configure(indexer::routes))
             // 标签 API 路由
             .service(web::scope("/api/tag").configure(tag::routes))
+            // 阅读进度 API 路由
+            .service(web::scope("/api/reading").configure(reading::routes))
+            // 音乐库 API 路由
+            .service(web::scope("/api/music").configure(music::routes))
             // 文件系统浏览 API 路由
             .service(web::scope("/api/browser").configure(browser::routes))
             // 播放队列 API 路由
@@ -0,0 +1,110 @@
+// 音乐库模块 - API 处理函数
+use actix_web::{web, HttpResponse, Result};
+use std::path::{Path, PathBuf};
+use super::models::*;
+use super::storage;
+
+fn query_error(e: rusqlite::Error) -> HttpResponse {
+    HttpResponse::InternalServerError().json(serde_json::json!({
+        "error": format!("查询音乐库失败: {}", e)
+    }))
+}
+
+/// 艺术家列表
+pub async fn list_artists(query: web::Query<LibraryQuery>) -> Result<HttpResponse> {
+    let source_folder = query.source_folder.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        storage::list_artists(source_folder.as_deref())
+    }).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+
+    match result {
+        Ok(artists) => Ok(HttpResponse::Ok().json(artists)),
+        Err(e) => Ok(query_error(e)),
+    }
+}
+
+/// 专辑列表（可按艺术家筛选）
+pub async fn list_albums(query: web::Query<LibraryQuery>) -> Result<HttpResponse> {
+    let source_folder = query.source_folder.clone();
+    let artist = query.artist.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        storage::list_albums(source_folder.as_deref(), artist.as_deref())
+    }).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+
+    match result {
+        Ok(albums) => Ok(HttpResponse::Ok().json(albums)),
+        Err(e) => Ok(query_error(e)),
+    }
+}
+
+/// 曲目列表（分页，可按艺术家 / 专辑筛选）
+pub async fn list_tracks(query: web::Query<TracksQuery>) -> Result<HttpResponse> {
+    let source_folder = query.source_folder.clone();
+    let artist = query.artist.clone();
+    let album = query.album.clone();
+    let offset = query.offset.unwrap_or(0).max(0);
+    let limit = query.limit.unwrap_or(100).clamp(1, 500);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let (mut items, total) = storage::list_tracks(source_folder.as_deref(), artist.as_deref(), album.as_deref(), offset, limit)?;
+        let uuids: Vec<String> = items.iter().map(|t| t.file.uuid.clone()).collect();
+        if let Ok(mut loudness) = crate::indexer::storage::get_loudness_map(&uuids) {
+            for track in items.iter_mut() {
+                track.file.loudness = loudness.remove(&track.file.uuid);
+            }
+        }
+        Ok::<_, rusqlite::Error>((items, total))
+    }).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+
+    match result {
+        Ok((items, total)) => Ok(HttpResponse::Ok().json(TracksResponse {
+            items,
+            total,
+            offset,
+            limit,
+            has_more: offset + limit < total,
+        })),
+        Err(e) => Ok(query_error(e)),
+    }
+}
+
+/// 同目录下与音频同名的 .lrc（扩展名不区分大小写）
+fn find_lyrics(audio_path: &Path) -> Option<PathBuf> {
+    let dir = audio_path.parent()?;
+    let stem = audio_path.file_stem()?.to_str()?;
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .find(|p| {
+            p.is_file()
+                && p.file_stem().and_then(|s| s.to_str()) == Some(stem)
+                && p.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("lrc"))
+        })
+}
+
+/// 外挂歌词（.lrc 常见 GBK 等旧编码，检测字符集后转为 UTF-8 返回）
+pub async fn get_lyrics(query: web::Query<LyricsQuery>) -> Result<HttpResponse> {
+    let uuid = query.uuid.clone();
+    let file = tokio::task::spawn_blocking(move || crate::indexer::storage::get_file_by_uuid(&uuid))
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("数据库错误: {}", e)))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("UUID 对应的文件未找到"))?;
+    let file_path = file.current_path
+        .ok_or_else(|| actix_web::error::ErrorNotFound("文件已被删除或移动"))?;
+
+    let data = tokio::task::spawn_blocking(move || {
+        find_lyrics(Path::new(&file_path)).map(std::fs::read)
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+    .ok_or_else(|| actix_web::error::ErrorNotFound("没有歌词文件"))?
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("读取歌词失败: {}", e)))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(crate::charset::decode_detected(&data)))
+}
@@ -0,0 +1,15 @@
+// 音乐库模块 — 从音频标签（ID3 / Vorbis Comment / MP4）整理艺术家、专辑、曲目，以及外挂 .lrc 歌词
+pub mod models;
+pub mod storage;
+pub mod tags;
+mod handlers;
+
+use actix_web::web;
+
+/// 注册所有音乐库相关路由
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/artists").route(web::get().to(handlers::list_artists)))
+       .service(web::resource("/albums").route(web::get().to(handlers::list_albums)))
+       .service(web::resource("/tracks").route(web::get().to(handlers::list_tracks)))
+       .service(web::resource("/lyrics").route(web::get().to(handlers::get_lyrics)));
+}
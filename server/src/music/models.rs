@@ -0,0 +1,83 @@
+// 音乐库模块 - 数据模型
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::models::IndexedFile;
+
+/// 音频标签（读取不到的字段为 null）
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+}
+
+impl AudioTags {
+    pub fn is_empty(&self) -> bool {
+        *self == AudioTags::default()
+    }
+}
+
+/// 艺术家（专辑艺术家优先，没有时取艺术家）
+#[derive(Debug, Serialize)]
+pub struct MusicArtist {
+    pub name: String,
+    pub album_count: i64,
+    pub track_count: i64,
+}
+
+/// 专辑：按 (专辑名, 艺术家) 归并
+#[derive(Debug, Serialize)]
+pub struct MusicAlbum {
+    pub name: String,
+    pub artist: Option<String>,
+    pub year: Option<i32>,
+    pub track_count: i64,
+    /// 专辑封面取自该曲目的内嵌封面（/api/preview/thumbnail?uuid=...）
+    pub cover_uuid: String,
+}
+
+/// 曲目：索引信息 + 标签
+#[derive(Debug, Serialize)]
+pub struct MusicTrack {
+    #[serde(flatten)]
+    pub file: IndexedFile,
+    pub tags: AudioTags,
+}
+
+/// 曲目分页响应
+#[derive(Debug, Serialize)]
+pub struct TracksResponse {
+    pub items: Vec<MusicTrack>,
+    pub total: i64,
+    pub offset: i64,
+    pub limit: i64,
+    pub has_more: bool,
+}
+
+/// 艺术家 / 专辑列表查询（source_folder 限定在某个源文件夹内）
+#[derive(Debug, Deserialize)]
+pub struct LibraryQuery {
+    pub source_folder: Option<String>,
+    pub artist: Option<String>,
+}
+
+/// 曲目查询
+#[derive(Debug, Deserialize)]
+pub struct TracksQuery {
+    pub source_folder: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub offset: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// 歌词查询
+#[derive(Debug, Deserialize)]
+pub struct LyricsQuery {
+    pub uuid: String,
+}
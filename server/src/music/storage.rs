@@ -0,0 +1,244 @@
+// 音乐库模块 - 标签入库与查询
+use rusqlite::params;
+
+use crate::database::get_connection;
+use crate::indexer::storage::map_file_row;
+use super::models::*;
+use super::tags;
+
+const FILE_COLUMNS: &str = "f.uuid, f.fingerprint, f.current_path, f.folder_path, f.file_name, f.file_type, f.extension, f.file_size, f.created_at, f.modified_at, f.indexed_at, f.source_url, f.blurhash, f.animated, f.frame_count";
+
+const TAG_COLUMNS: &str = "t.title, t.artist, t.album, t.album_artist, t.track_number, t.disc_number, t.year, t.genre";
+
+/// 艺术家归并键：专辑艺术家优先
+const ARTIST_KEY: &str = "COALESCE(t.album_artist, t.artist)";
+
+/// 曲目顺序：碟号 → 音轨号（没有的排在最后）→ 文件名
+const TRACK_ORDER: &str = "COALESCE(t.disc_number, 1), COALESCE(t.track_number, 1000000), f.file_name";
+
+/// 标签同步范围：单个文件夹（增量扫描）或整个源文件夹（全量扫描）
+pub enum TagScope<'a> {
+    Folder(&'a str),
+    Source(&'a str),
+}
+
+/// 为缺少标签或文件已变更（modified_at 不一致）的音频读取标签
+/// 没有标签的文件也写入一行空记录，避免每次扫描都重读
+pub fn sync_audio_tags(scope: TagScope) -> Result<usize, rusqlite::Error> {
+    let conn = get_connection()?;
+    let mut query = String::from(
+        "SELECT f.uuid, f.current_path, f.modified_at FROM file_index f
+         LEFT JOIN audio_tags t ON t.file_uuid = f.uuid
+         WHERE f.file_type = 'audio' AND f.current_path IS NOT NULL
+           AND (t.file_uuid IS NULL OR t.modified_at != f.modified_at)"
+    );
+    let params: Vec<String> = match scope {
+        TagScope::Folder(folder) => {
+            query.push_str(" AND f.folder_path = ?");
+            vec![folder.to_string()]
+        }
+        TagScope::Source(source) => {
+            query.push_str(" AND (f.folder_path = ? OR f.folder_path LIKE ?)");
+            vec![source.to_string(), format!("{}/%", source)]
+        }
+    };
+    let pending: Vec<(String, String, String)> = {
+        let mut stmt = conn.prepare(&query)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?;
+        rows.collect::<Result<_, _>>()?
+    };
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    // 先读完文件再开事务，避免读大文件时长时间占着写锁
+    let read: Vec<(String, String, AudioTags)> = pending
+        .into_iter()
+        .map(|(uuid, path, modified_at)| {
+            let tags = tags::read_tags(std::path::Path::new(&path));
+            (uuid, modified_at, tags)
+        })
+        .collect();
+
+    let tx = conn.unchecked_transaction()?;
+    for (uuid, modified_at, t) in &read {
+        tx.execute(
+            "INSERT OR REPLACE INTO audio_tags
+                (file_uuid, title, artist, album, album_artist, track_number, disc_number, year, genre, modified_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![uuid, t.title, t.artist, t.album, t.album_artist, t.track_number, t.disc_number, t.year, t.genre, modified_at],
+        )?;
+    }
+    tx.commit()?;
+    Ok(read.len())
+}
+
+fn map_tags_row(row: &rusqlite::Row, offset: usize) -> Result<AudioTags, rusqlite::Error> {
+    Ok(AudioTags {
+        title: row.get(offset)?,
+        artist: row.get(offset + 1)?,
+        album: row.get(offset + 2)?,
+        album_artist: row.get(offset + 3)?,
+        track_number: row.get(offset + 4)?,
+        disc_number: row.get(offset + 5)?,
+        year: row.get(offset + 6)?,
+        genre: row.get(offset + 7)?,
+    })
+}
+
+/// 公共 WHERE：现存的音频文件，可选限定源文件夹
+fn scope_clause(source_folder: Option<&str>, params: &mut Vec<String>) -> String {
+    let mut clause = String::from("f.file_type = 'audio' AND f.current_path IS NOT NULL");
+    if let Some(source) = source_folder {
+        clause.push_str(" AND (f.folder_path = ? OR f.folder_path LIKE ?)");
+        params.push(source.to_string());
+        params.push(format!("{}/%", source));
+    }
+    clause.push_str(&ignored_files_clause(params));
+    clause
+}
+
+/// 排除配置中忽略的文件名（与播放列表一致）
+fn ignored_files_clause(params: &mut Vec<String>) -> String {
+    let ignored = crate::config_api::storage::load_config()
+        .map(|c| c.ignored_files)
+        .unwrap_or_default();
+    if ignored.is_empty() {
+        return String::new();
+    }
+    let placeholders = ignored.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+    params.extend(ignored);
+    format!(" AND f.file_name NOT IN ({})", placeholders)
+}
+
+/// 艺术家列表（按名称排序，没有艺术家标签的曲目不计入）
+pub fn list_artists(source_folder: Option<&str>) -> Result<Vec<MusicArtist>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let mut params = Vec::new();
+    let scope = scope_clause(source_folder, &mut params);
+    let query = format!(
+        "SELECT {key} AS name, COUNT(DISTINCT t.album), COUNT(*)
+         FROM audio_tags t INNER JOIN file_index f ON f.uuid = t.file_uuid
+         WHERE {scope} AND {key} IS NOT NULL
+         GROUP BY name ORDER BY name COLLATE NOCASE",
+        key = ARTIST_KEY, scope = scope
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+        Ok(MusicArtist {
+            name: row.get(0)?,
+            album_count: row.get(1)?,
+            track_count: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// 专辑列表（可按艺术家筛选），封面取碟号 / 音轨号最小的曲目
+pub fn list_albums(source_folder: Option<&str>, artist: Option<&str>) -> Result<Vec<MusicAlbum>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let mut params = Vec::new();
+    let mut scope = scope_clause(source_folder, &mut params);
+    if let Some(artist) = artist {
+        scope.push_str(&format!(" AND {} = ?", ARTIST_KEY));
+        params.push(artist.to_string());
+    }
+    // 封面用窗口函数按曲目顺序编号取第一首；聚合里的裸列在有多个 MIN / MAX 时取自哪一行是未定义的
+    let query = format!(
+        "WITH scoped AS (
+             SELECT t.album, {key} AS album_artist, t.year, f.uuid,
+                    ROW_NUMBER() OVER (PARTITION BY t.album, {key} ORDER BY {order}) AS track_rank
+             FROM audio_tags t INNER JOIN file_index f ON f.uuid = t.file_uuid
+             WHERE {scope} AND t.album IS NOT NULL
+         )
+         SELECT album, album_artist, MAX(year), COUNT(*), MAX(CASE WHEN track_rank = 1 THEN uuid END)
+         FROM scoped
+         GROUP BY album, album_artist
+         ORDER BY album_artist COLLATE NOCASE, MAX(year), album COLLATE NOCASE",
+        key = ARTIST_KEY, order = TRACK_ORDER, scope = scope
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+        Ok(MusicAlbum {
+            name: row.get(0)?,
+            artist: row.get(1)?,
+            year: row.get(2)?,
+            track_count: row.get(3)?,
+            cover_uuid: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// 曲目列表（按艺术家 / 专辑筛选），按艺术家、专辑、碟号、音轨号排序
+pub fn list_tracks(
+    source_folder: Option<&str>,
+    artist: Option<&str>,
+    album: Option<&str>,
+    offset: i64,
+    limit: i64,
+) -> Result<(Vec<MusicTrack>, i64), rusqlite::Error> {
+    let conn = get_connection()?;
+    let mut params = Vec::new();
+    let mut scope = scope_clause(source_folder, &mut params);
+    if let Some(artist) = artist {
+        scope.push_str(&format!(" AND {} = ?", ARTIST_KEY));
+        params.push(artist.to_string());
+    }
+    if let Some(album) = album {
+        scope.push_str(" AND t.album = ?");
+        params.push(album.to_string());
+    }
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM audio_tags t INNER JOIN file_index f ON f.uuid = t.file_uuid WHERE {}", scope),
+        rusqlite::params_from_iter(params.iter()),
+        |row| row.get(0),
+    )?;
+
+    let query = format!(
+        "SELECT {}, {} FROM audio_tags t INNER JOIN file_index f ON f.uuid = t.file_uuid
+         WHERE {} ORDER BY {} COLLATE NOCASE, t.album COLLATE NOCASE, {} LIMIT {} OFFSET {}",
+        FILE_COLUMNS, TAG_COLUMNS, scope, ARTIST_KEY, TRACK_ORDER, limit, offset
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+        Ok(MusicTrack {
+            file: map_file_row(row)?,
+            tags: map_tags_row(row, 15)?,
+        })
+    })?;
+    Ok((rows.collect::<Result<_, _>>()?, total))
+}
+
+/// 单个文件的标签
+pub fn get_tags(file_uuid: &str) -> Result<Option<AudioTags>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM audio_tags t WHERE t.file_uuid = ?1", TAG_COLUMNS))?;
+    let mut rows = stmt.query(params![file_uuid])?;
+    rows.next()?.map(|row| map_tags_row(row, 0)).transpose()
+}
+
+/// 与指定曲目同专辑（专辑名 + 艺术家归并键相同）的全部曲目，按碟号 / 音轨号排序
+pub fn get_album_files(file_uuid: &str) -> Result<Option<Vec<crate::indexer::models::IndexedFile>>, rusqlite::Error> {
+    let Some(tags) = get_tags(file_uuid)? else { return Ok(None) };
+    let Some(album) = tags.album else { return Ok(None) };
+    let artist = tags.album_artist.or(tags.artist);
+
+    let conn = get_connection()?;
+    let mut ignored = Vec::new();
+    let ignore_clause = ignored_files_clause(&mut ignored);
+    let query = format!(
+        "SELECT {} FROM audio_tags t INNER JOIN file_index f ON f.uuid = t.file_uuid
+         WHERE f.current_path IS NOT NULL AND t.album = ?1 AND {} IS ?2{}
+         ORDER BY {}",
+        FILE_COLUMNS, ARTIST_KEY, ignore_clause, TRACK_ORDER
+    );
+    let mut values: Vec<&dyn rusqlite::ToSql> = vec![&album, &artist];
+    values.extend(ignored.iter().map(|name| name as &dyn rusqlite::ToSql));
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(values.as_slice(), map_file_row)?;
+    Ok(Some(rows.collect::<Result<_, _>>()?))
+}
@@ -0,0 +1,624 @@
+// 音频标签读取：ID3v2 / ID3v1 (MP3 等)、Vorbis Comment (FLAC / Ogg Vorbis / Opus)、
+// iTunes 元数据 (M4A / MP4 的 moov/udta/meta/ilst)
+// 只读标题、艺术家、专辑、专辑艺术家、音轨号 / 碟号、年份和流派，其余帧跳过
+use encoding_rs::{Encoding, WINDOWS_1252};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::models::AudioTags;
+
+/// 标签块读取上限：内嵌封面可能有几 MB，再大基本是损坏的文件
+const TAG_LIMIT: u64 = 32 * 1024 * 1024;
+
+/// ID3v1 流派表（0–79，Winamp 扩展部分不常见，不收录）
+const ID3V1_GENRES: &[&str] = &[
+    "Blues", "Classic Rock", "Country", "Dance", "Disco", "Funk", "Grunge", "Hip-Hop", "Jazz", "Metal",
+    "New Age", "Oldies", "Other", "Pop", "R&B", "Rap", "Reggae", "Rock", "Techno", "Industrial",
+    "Alternative", "Ska", "Death Metal", "Pranks", "Soundtrack", "Euro-Techno", "Ambient", "Trip-Hop", "Vocal", "Jazz+Funk",
+    "Fusion", "Trance", "Classical", "Instrumental", "Acid", "House", "Game", "Sound Clip", "Gospel", "Noise",
+    "AlternRock", "Bass", "Soul", "Punk", "Space", "Meditative", "Instrumental Pop", "Instrumental Rock", "Ethnic", "Gothic",
+    "Darkwave", "Techno-Industrial", "Electronic", "Pop-Folk", "Eurodance", "Dream", "Southern Rock", "Comedy", "Cult", "Gangsta",
+    "Top 40", "Christian Rap", "Pop/Funk", "Jungle", "Native American", "Cabaret", "New Wave", "Psychadelic", "Rave", "Showtunes",
+    "Trailer", "Lo-Fi", "Tribal", "Acid Punk", "Acid Jazz", "Polka", "Retro", "Musical", "Rock & Roll", "Hard Rock",
+];
+
+/// 读取音频文件的标签；没有标签或格式不支持时返回全空
+pub fn read_tags(path: &Path) -> AudioTags {
+    let Ok(mut f) = File::open(path) else { return AudioTags::default() };
+    let mut head = [0u8; 12];
+    let n = f.read(&mut head).unwrap_or(0);
+    let head = &head[..n];
+
+    let mut tags = AudioTags::default();
+    if head.starts_with(b"ID3") {
+        if let Some(tag) = read_id3v2_block(&mut f) {
+            parse_id3v2(&tag, &mut tags);
+        }
+        // 少数 FLAC 文件前面带 ID3v2，继续读后面的 Vorbis Comment 补缺
+        if let Some(block) = flac_after_id3(&mut f) {
+            parse_vorbis_comments(&block, &mut tags);
+        }
+    } else if head.starts_with(b"fLaC") {
+        if let Some(block) = read_flac_comment_block(&mut f, 4) {
+            parse_vorbis_comments(&block, &mut tags);
+        }
+    } else if head.starts_with(b"OggS") {
+        if let Some(packet) = read_ogg_comment_packet(&mut f) {
+            let body = packet.strip_prefix(b"\x03vorbis").or_else(|| packet.strip_prefix(b"OpusTags"));
+            if let Some(body) = body {
+                parse_vorbis_comments(body, &mut tags);
+            }
+        }
+    } else if head.len() >= 8 && &head[4..8] == b"ftyp" {
+        if let Some(ilst) = read_mp4_ilst(&mut f) {
+            parse_ilst(&ilst, &mut tags);
+        }
+    }
+
+    // MP3 / WAV 等没有 ID3v2 时，退到文件末尾的 ID3v1
+    if tags.is_empty() {
+        if let Some(v1) = read_id3v1(&mut f) {
+            tags = v1;
+        }
+    }
+    tags
+}
+
+// ============================================================================
+// 文本工具
+// ============================================================================
+
+/// 去掉首尾空白和结尾的 NUL，空字符串视为没有
+fn clean(s: &str) -> Option<String> {
+    let s = s.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+/// 标称 Latin-1 的文本：国内常见的旧 MP3 实际写的是 GBK 等本地编码
+/// 对整个标签里这类文本拼在一起做一次字符集检测（与文档预览相同），纯 ASCII 时不必检测
+fn legacy_encoding<'a>(samples: impl IntoIterator<Item = &'a [u8]>) -> &'static Encoding {
+    let mut sample = Vec::new();
+    for s in samples {
+        let end = s.iter().position(|&b| b == 0).unwrap_or(s.len());
+        sample.extend_from_slice(&s[..end]);
+        sample.push(b' ');
+    }
+    if sample.is_ascii() {
+        return WINDOWS_1252;
+    }
+    crate::charset::detect_encoding(&sample, true).0
+}
+
+fn decode_legacy(bytes: &[u8], encoding: &'static Encoding) -> String {
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+fn utf16(bytes: &[u8], big_endian: bool) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| if big_endian { u16::from_be_bytes([c[0], c[1]]) } else { u16::from_le_bytes([c[0], c[1]]) })
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// "3" / "3/12" / " 03 " → 3
+fn parse_number(s: &str) -> Option<u32> {
+    let digits: String = s.trim().chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok().filter(|n| *n > 0)
+}
+
+/// "2004" / "2004-05-01" / "2004-05-01T12:00" → 2004
+fn parse_year(s: &str) -> Option<i32> {
+    let s = s.trim();
+    let year = s.get(..4)?;
+    if !year.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    year.parse().ok().filter(|y| *y > 0)
+}
+
+/// ID3 流派："(13)" / "13" → 表中名称；"(13)Pop" / "Pop" → 去掉引用后的文本
+fn normalize_genre(s: &str) -> Option<String> {
+    let s = s.trim();
+    let (reference, rest) = match s.strip_prefix('(').and_then(|r| r.split_once(')')) {
+        Some((num, rest)) => (Some(num), rest),
+        None => (None, s),
+    };
+    if let Some(text) = clean(rest) {
+        if text.chars().all(|c| c.is_ascii_digit()) {
+            return text.parse::<usize>().ok().and_then(|i| ID3V1_GENRES.get(i)).map(|g| g.to_string());
+        }
+        return Some(text);
+    }
+    reference
+        .and_then(|n| n.parse::<usize>().ok())
+        .and_then(|i| ID3V1_GENRES.get(i))
+        .map(|g| g.to_string())
+}
+
+/// 按字段名写入标签（Vorbis Comment 的键名，已转大写）；已有值的字段不覆盖
+fn set_field(tags: &mut AudioTags, key: &str, value: &str) {
+    fn fill(slot: &mut Option<String>, value: &str) {
+        if slot.is_none() {
+            *slot = clean(value);
+        }
+    }
+    match key {
+        "TITLE" => fill(&mut tags.title, value),
+        "ARTIST" => fill(&mut tags.artist, value),
+        "ALBUM" => fill(&mut tags.album, value),
+        "ALBUMARTIST" | "ALBUM ARTIST" | "ALBUM_ARTIST" => fill(&mut tags.album_artist, value),
+        "TRACKNUMBER" | "TRACK" if tags.track_number.is_none() => tags.track_number = parse_number(value),
+        "DISCNUMBER" | "DISC" if tags.disc_number.is_none() => tags.disc_number = parse_number(value),
+        "DATE" | "YEAR" | "ORIGINALDATE" if tags.year.is_none() => tags.year = parse_year(value),
+        "GENRE" if tags.genre.is_none() => tags.genre = normalize_genre(value),
+        _ => {}
+    }
+}
+
+// ============================================================================
+// ID3v2 / ID3v1
+// ============================================================================
+
+/// 读取整个 ID3v2 标签（含 10 字节头），读完后文件位置在标签之后
+fn read_id3v2_block(f: &mut File) -> Option<Vec<u8>> {
+    f.seek(SeekFrom::Start(0)).ok()?;
+    let mut header = [0u8; 10];
+    f.read_exact(&mut header).ok()?;
+    let size = syncsafe(&header[6..10]) as u64;
+    if size > TAG_LIMIT {
+        return None;
+    }
+    let mut tag = header.to_vec();
+    f.take(size).read_to_end(&mut tag).ok()?;
+    // 有 footer 时跳过
+    if header[5] & 0x10 != 0 {
+        f.seek(SeekFrom::Current(10)).ok()?;
+    }
+    Some(tag)
+}
+
+fn syncsafe(b: &[u8]) -> u32 {
+    b.iter().fold(0u32, |acc, &x| (acc << 7) | (x & 0x7F) as u32)
+}
+
+/// 去掉反同步插入的 0x00（FF 00 → FF）
+fn remove_unsync(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut prev_ff = false;
+    for &b in data {
+        if prev_ff && b == 0 {
+            prev_ff = false;
+            continue;
+        }
+        out.push(b);
+        prev_ff = b == 0xFF;
+    }
+    out
+}
+
+/// 解析 ID3v2.2 / 2.3 / 2.4 标签（data 含 10 字节头）
+fn parse_id3v2(data: &[u8], tags: &mut AudioTags) {
+    if data.len() < 10 || !data.starts_with(b"ID3") {
+        return;
+    }
+    let version = data[3];
+    let flags = data[5];
+    let mut body = data[10..].to_vec();
+    // v2.2 / v2.3 的反同步作用于整个标签，v2.4 按帧标记
+    if flags & 0x80 != 0 && version < 4 {
+        body = remove_unsync(&body);
+    }
+
+    let mut pos = 0;
+    // 扩展头
+    if flags & 0x40 != 0 && version >= 3 && body.len() >= 4 {
+        pos = if version == 4 {
+            syncsafe(&body[..4]) as usize
+        } else {
+            4 + u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize
+        };
+    }
+
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    let mut frames: Vec<(&'static str, Vec<u8>)> = Vec::new();
+    while pos + header_len <= body.len() {
+        let id = &body[pos..pos + id_len];
+        // 到达填充区
+        if id[0] == 0 {
+            break;
+        }
+        let size = match version {
+            2 => u32::from_be_bytes([0, body[pos + 3], body[pos + 4], body[pos + 5]]) as usize,
+            3 => u32::from_be_bytes([body[pos + 4], body[pos + 5], body[pos + 6], body[pos + 7]]) as usize,
+            _ => syncsafe(&body[pos + 4..pos + 8]) as usize,
+        };
+        let format_flags = if version == 2 { 0 } else { body[pos + 9] };
+        let start = pos + header_len;
+        let Some(frame) = body.get(start..start + size) else { break };
+        pos = start + size;
+
+        // 压缩 / 加密的帧不处理
+        let skip = match version {
+            3 => format_flags & 0xC0 != 0,
+            4 => format_flags & 0x0C != 0,
+            _ => false,
+        };
+        let key = match id {
+            b"TIT2" | b"TT2" => "TITLE",
+            b"TPE1" | b"TP1" => "ARTIST",
+            b"TALB" | b"TAL" => "ALBUM",
+            b"TPE2" | b"TP2" => "ALBUMARTIST",
+            b"TRCK" | b"TRK" => "TRACKNUMBER",
+            b"TPOS" | b"TPA" => "DISCNUMBER",
+            b"TDRC" | b"TYER" | b"TYE" => "DATE",
+            b"TDOR" | b"TORY" => "ORIGINALDATE",
+            b"TCON" | b"TCO" => "GENRE",
+            _ => continue,
+        };
+        if skip {
+            continue;
+        }
+
+        let mut frame = frame.to_vec();
+        if version == 4 {
+            if format_flags & 0x02 != 0 {
+                frame = remove_unsync(&frame);
+            }
+            // 数据长度指示
+            if format_flags & 0x01 != 0 && frame.len() >= 4 {
+                frame.drain(..4);
+            }
+        }
+        frames.push((key, frame));
+    }
+
+    // 单个帧太短，字符集检测不可靠，拼起全部 Latin-1 帧一起检测
+    let legacy = legacy_encoding(
+        frames.iter().filter(|(_, f)| f.first() == Some(&0)).map(|(_, f)| &f[1..]),
+    );
+    for (key, frame) in &frames {
+        if let Some(text) = decode_text_frame(frame, legacy) {
+            set_field(tags, key, &text);
+        }
+    }
+}
+
+/// 文本帧：首字节为编码（0 Latin-1，按 legacy 检测结果解码 / 1 带 BOM 的 UTF-16 / 2 UTF-16BE / 3 UTF-8），
+/// v2.4 的多值以 NUL 分隔，只取第一个
+fn decode_text_frame(frame: &[u8], legacy: &'static Encoding) -> Option<String> {
+    let (&encoding, raw) = frame.split_first()?;
+    let text = match encoding {
+        0 => decode_legacy(raw, legacy),
+        1 | 2 => {
+            let (big_endian, raw) = match raw {
+                [0xFE, 0xFF, rest @ ..] => (true, rest),
+                [0xFF, 0xFE, rest @ ..] => (false, rest),
+                _ => (encoding == 2, raw),
+            };
+            utf16(raw, big_endian)
+        }
+        3 => String::from_utf8_lossy(raw).into_owned(),
+        _ => return None,
+    };
+    let first = text.split('\0').next().unwrap_or("");
+    clean(first)
+}
+
+/// 文件末尾 128 字节的 ID3v1 标签
+fn read_id3v1(f: &mut File) -> Option<AudioTags> {
+    let len = f.metadata().ok()?.len();
+    if len < 128 {
+        return None;
+    }
+    f.seek(SeekFrom::Start(len - 128)).ok()?;
+    let mut tag = [0u8; 128];
+    f.read_exact(&mut tag).ok()?;
+    parse_id3v1(&tag)
+}
+
+fn parse_id3v1(tag: &[u8; 128]) -> Option<AudioTags> {
+    if &tag[..3] != b"TAG" {
+        return None;
+    }
+    let legacy = legacy_encoding([&tag[3..33], &tag[33..63], &tag[63..93]]);
+    let field = |range: std::ops::Range<usize>| {
+        let raw = &tag[range];
+        let end = raw.iter().position(|&b| b == 0).unwrap_or(raw.len());
+        clean(&decode_legacy(&raw[..end], legacy))
+    };
+    // ID3v1.1：注释第 29 字节为 0 时，第 30 字节是音轨号
+    let track_number = (tag[125] == 0 && tag[126] != 0).then_some(tag[126] as u32);
+    Some(AudioTags {
+        title: field(3..33),
+        artist: field(33..63),
+        album: field(63..93),
+        year: field(93..97).and_then(|y| parse_year(&y)),
+        track_number,
+        genre: ID3V1_GENRES.get(tag[127] as usize).map(|g| g.to_string()),
+        ..Default::default()
+    })
+}
+
+// ============================================================================
+// FLAC / Ogg 的 Vorbis Comment
+// ============================================================================
+
+/// ID3v2 之后紧跟 "fLaC" 时读取其 Vorbis Comment
+fn flac_after_id3(f: &mut File) -> Option<Vec<u8>> {
+    let mut magic = [0u8; 4];
+    f.read_exact(&mut magic).ok()?;
+    if &magic != b"fLaC" {
+        return None;
+    }
+    let offset = f.stream_position().ok()?;
+    read_flac_comment_block(f, offset)
+}
+
+/// 从 offset（紧跟 "fLaC" 之后）开始遍历元数据块，返回 VORBIS_COMMENT（类型 4）块内容
+fn read_flac_comment_block(f: &mut File, offset: u64) -> Option<Vec<u8>> {
+    f.seek(SeekFrom::Start(offset)).ok()?;
+    loop {
+        let mut header = [0u8; 4];
+        f.read_exact(&mut header).ok()?;
+        let last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7F;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+        if block_type == 4 {
+            let mut block = Vec::new();
+            f.take(len).read_to_end(&mut block).ok()?;
+            return Some(block);
+        }
+        if last {
+            return None;
+        }
+        f.seek(SeekFrom::Current(len as i64)).ok()?;
+    }
+}
+
+/// Ogg 第二个包（Vorbis / Opus 的注释头），可能跨多个页
+fn read_ogg_comment_packet(f: &mut File) -> Option<Vec<u8>> {
+    f.seek(SeekFrom::Start(0)).ok()?;
+    let mut packet_index = 0;
+    let mut packet = Vec::new();
+    let mut read_total = 0u64;
+    loop {
+        let mut header = [0u8; 27];
+        f.read_exact(&mut header).ok()?;
+        if &header[..4] != b"OggS" {
+            return None;
+        }
+        let segment_count = header[26] as usize;
+        let mut lacing = vec![0u8; segment_count];
+        f.read_exact(&mut lacing).ok()?;
+        let page_len: u64 = lacing.iter().map(|&l| l as u64).sum();
+        let mut page = Vec::with_capacity(page_len as usize);
+        f.take(page_len).read_to_end(&mut page).ok()?;
+        read_total += page_len;
+        if read_total > TAG_LIMIT {
+            return None;
+        }
+
+        // 段长 < 255 表示包结束
+        let mut pos = 0;
+        for &l in &lacing {
+            let seg = page.get(pos..pos + l as usize)?;
+            pos += l as usize;
+            if packet_index == 1 {
+                packet.extend_from_slice(seg);
+            }
+            if l < 255 {
+                if packet_index == 1 {
+                    return Some(packet);
+                }
+                packet_index += 1;
+            }
+        }
+    }
+}
+
+/// Vorbis Comment：vendor 长度 + vendor + 条目数 + 若干 "KEY=value"（长度均为小端 u32）
+fn parse_vorbis_comments(data: &[u8], tags: &mut AudioTags) {
+    let read_u32 = |pos: usize| -> Option<usize> {
+        data.get(pos..pos + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    let Some(vendor_len) = read_u32(0) else { return };
+    let mut pos = 4 + vendor_len;
+    let Some(count) = read_u32(pos) else { return };
+    pos += 4;
+    for _ in 0..count {
+        let Some(len) = read_u32(pos) else { return };
+        pos += 4;
+        let Some(entry) = data.get(pos..pos + len) else { return };
+        pos += len;
+        let entry = String::from_utf8_lossy(entry);
+        if let Some((key, value)) = entry.split_once('=') {
+            set_field(tags, &key.to_ascii_uppercase(), value);
+        }
+    }
+}
+
+// ============================================================================
+// MP4 / M4A
+// ============================================================================
+
+/// 在 [start, end) 范围内查找直接子 atom，返回 (内容起点, 内容长度)
+fn find_atom(f: &mut File, start: u64, end: u64, name: &[u8; 4]) -> Option<(u64, u64)> {
+    let mut pos = start;
+    while pos + 8 <= end {
+        f.seek(SeekFrom::Start(pos)).ok()?;
+        let mut header = [0u8; 8];
+        f.read_exact(&mut header).ok()?;
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let mut header_len = 8;
+        if size == 1 {
+            let mut large = [0u8; 8];
+            f.read_exact(&mut large).ok()?;
+            size = u64::from_be_bytes(large);
+            header_len = 16;
+        } else if size == 0 {
+            size = end - pos;
+        }
+        if size < header_len {
+            return None;
+        }
+        if &header[4..8] == name {
+            return Some((pos + header_len, size - header_len));
+        }
+        pos += size;
+    }
+    None
+}
+
+/// moov/udta/meta/ilst 的内容
+fn read_mp4_ilst(f: &mut File) -> Option<Vec<u8>> {
+    let file_len = f.metadata().ok()?.len();
+    let (moov, moov_len) = find_atom(f, 0, file_len, b"moov")?;
+    let (udta, udta_len) = find_atom(f, moov, moov + moov_len, b"udta")?;
+    let (meta, meta_len) = find_atom(f, udta, udta + udta_len, b"meta")?;
+    // meta 通常是 full box（多 4 字节 version/flags），QuickTime 风格的没有
+    let mut probe = [0u8; 8];
+    f.seek(SeekFrom::Start(meta)).ok()?;
+    f.read_exact(&mut probe).ok()?;
+    let children = if &probe[4..8] == b"hdlr" { meta } else { meta + 4 };
+    let (ilst, ilst_len) = find_atom(f, children, meta + meta_len, b"ilst")?;
+    if ilst_len > TAG_LIMIT {
+        return None;
+    }
+    f.seek(SeekFrom::Start(ilst)).ok()?;
+    let mut data = Vec::with_capacity(ilst_len as usize);
+    f.take(ilst_len).read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+/// ilst 条目：每个条目内含 data atom（类型 4 字节 + locale 4 字节 + 值）
+fn parse_ilst(data: &[u8], tags: &mut AudioTags) {
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let size = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        if size < 8 || pos + size > data.len() {
+            break;
+        }
+        let name = &data[pos + 4..pos + 8];
+        let item = &data[pos + 8..pos + size];
+        pos += size;
+
+        // 条目下的第一个 data atom
+        if item.len() < 16 || &item[4..8] != b"data" {
+            continue;
+        }
+        let data_size = u32::from_be_bytes([item[0], item[1], item[2], item[3]]) as usize;
+        let Some(value) = item.get(16..data_size.min(item.len())) else { continue };
+
+        match name {
+            b"\xA9nam" => set_field(tags, "TITLE", &String::from_utf8_lossy(value)),
+            b"\xA9ART" => set_field(tags, "ARTIST", &String::from_utf8_lossy(value)),
+            b"\xA9alb" => set_field(tags, "ALBUM", &String::from_utf8_lossy(value)),
+            b"aART" => set_field(tags, "ALBUMARTIST", &String::from_utf8_lossy(value)),
+            b"\xA9day" => set_field(tags, "DATE", &String::from_utf8_lossy(value)),
+            b"\xA9gen" => set_field(tags, "GENRE", &String::from_utf8_lossy(value)),
+            // 预设流派：ID3v1 序号 + 1
+            b"gnre" if value.len() >= 2 => {
+                let index = u16::from_be_bytes([value[0], value[1]]) as usize;
+                if tags.genre.is_none() && index > 0 {
+                    tags.genre = ID3V1_GENRES.get(index - 1).map(|g| g.to_string());
+                }
+            }
+            // 2 字节填充 + 序号 u16 + 总数 u16
+            b"trkn" | b"disk" if value.len() >= 4 => {
+                let n = u16::from_be_bytes([value[2], value[3]]) as u32;
+                let slot = if name == b"trkn" { &mut tags.track_number } else { &mut tags.disc_number };
+                if slot.is_none() && n > 0 {
+                    *slot = Some(n);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id3_frame(id: &[u8; 4], text: &[u8]) -> Vec<u8> {
+        let mut frame = id.to_vec();
+        frame.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(text);
+        frame
+    }
+
+    #[test]
+    fn test_parse_id3v2() {
+        let mut frames = Vec::new();
+        frames.extend(id3_frame(b"TIT2", b"\x03\xE6\x99\xB4\xE5\xA4\xA9\0"));
+        // UTF-16 带 BOM
+        frames.extend(id3_frame(b"TPE1", b"\x01\xFF\xFEA\0b\0"));
+        frames.extend(id3_frame(b"TALB", b"\x00Album"));
+        frames.extend(id3_frame(b"TRCK", b"\x003/12"));
+        frames.extend(id3_frame(b"TYER", b"\x001999"));
+        frames.extend(id3_frame(b"TCON", b"\x00(17)"));
+        frames.extend_from_slice(&[0; 16]); // 填充
+        let mut tag = b"ID3\x03\x00\x00".to_vec();
+        let size = frames.len() as u32;
+        tag.extend([(size >> 21) as u8 & 0x7F, (size >> 14) as u8 & 0x7F, (size >> 7) as u8 & 0x7F, size as u8 & 0x7F]);
+        tag.extend(frames);
+
+        let mut tags = AudioTags::default();
+        parse_id3v2(&tag, &mut tags);
+        assert_eq!(tags.title.as_deref(), Some("晴天"));
+        assert_eq!(tags.artist.as_deref(), Some("Ab"));
+        assert_eq!(tags.album.as_deref(), Some("Album"));
+        assert_eq!(tags.track_number, Some(3));
+        assert_eq!(tags.year, Some(1999));
+        assert_eq!(tags.genre.as_deref(), Some("Rock"));
+    }
+
+    #[test]
+    fn test_parse_id3_legacy_charset() {
+        // 标称 Latin-1 实为 GBK：晴天 / 周杰伦
+        let mut frames = Vec::new();
+        frames.extend(id3_frame(b"TIT2", b"\x00\xC7\xE7\xCC\xEC"));
+        frames.extend(id3_frame(b"TPE1", b"\x00\xD6\xDC\xBD\xDC\xC2\xD7\0"));
+        let mut tag = b"ID3\x03\x00\x00".to_vec();
+        tag.extend([0, 0, 0, frames.len() as u8]);
+        tag.extend(frames);
+        let mut tags = AudioTags::default();
+        parse_id3v2(&tag, &mut tags);
+        assert_eq!(tags.title.as_deref(), Some("晴天"));
+        assert_eq!(tags.artist.as_deref(), Some("周杰伦"));
+
+        // 真正的 Latin-1 不受影响
+        let mut v1 = [0u8; 128];
+        v1[..3].copy_from_slice(b"TAG");
+        v1[3..9].copy_from_slice(b"M\xF6tley");
+        v1[33..37].copy_from_slice(b"Caf\xE9");
+        let tags = parse_id3v1(&v1).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Mötley"));
+        assert_eq!(tags.artist.as_deref(), Some("Café"));
+    }
+
+    #[test]
+    fn test_parse_vorbis_comments() {
+        let mut block = Vec::new();
+        block.extend(&6u32.to_le_bytes());
+        block.extend(b"vendor");
+        let entries: [&[u8]; 5] = [b"title=Song", b"ALBUMARTIST=Various", b"TRACKNUMBER=07", b"DATE=2004-05-01", b"ARTIST=First"];
+        block.extend(&(entries.len() as u32 + 1).to_le_bytes());
+        for e in entries.iter().chain([b"ARTIST=Second".as_slice()].iter()) {
+            block.extend(&(e.len() as u32).to_le_bytes());
+            block.extend(*e);
+        }
+
+        let mut tags = AudioTags::default();
+        parse_vorbis_comments(&block, &mut tags);
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+        assert_eq!(tags.album_artist.as_deref(), Some("Various"));
+        assert_eq!(tags.track_number, Some(7));
+        assert_eq!(tags.year, Some(2004));
+        // 重复的键取第一个
+        assert_eq!(tags.artist.as_deref(), Some("First"));
+    }
+}
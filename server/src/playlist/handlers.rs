@@ -1,6 +1,7 @@
 use actix_web::{web, HttpResponse, Result};
 use super::models::*;
 use super::storage;
+use super::shuffle;
 
 pub async fn playlist(query: web::Query<PlaylistQuery>) -> Result<HttpResponse> {
     let uuid = query.uuid.clone();
@@ -9,9 +10,19 @@ pub async fn playlist(query: web::Query<PlaylistQuery>) -> Result<HttpResponse>
     let sort = query.sort.clone();
     let file_type = query.file_type.clone();
     let current_queue_str = query.current_queue.clone();
+    let session_id = query.session_id.clone();
+    if mode == "session" && session_id.as_deref().is_none_or(str::is_empty) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "session_id is required for mode=session"
+        })));
+    }
 
     let result = tokio::task::spawn_blocking(move || {
-        match mode.as_str() {
+        let result = match mode.as_str() {
+            "session" => {
+                storage::get_playlist_session(session_id.as_deref().unwrap_or_default(), &uuid)
+            }
+            "album" => storage::get_playlist_album(&folder_path, &uuid),
             "shuffle" => {
                 let queue: Option<Vec<String>> = current_queue_str.map(|s| {
                     s.split(',').map(|u| u.trim().to_string()).filter(|u| !u.is_empty()).collect()
@@ -32,7 +43,11 @@ pub async fn playlist(query: web::Query<PlaylistQuery>) -> Result<HttpResponse>
                     sort.as_deref(),
                 )
             }
-        }
+        };
+        result.map(|(mut items, current_index)| {
+            crate::indexer::storage::attach_loudness(&mut items);
+            (items, current_index)
+        })
     })
     .await
     .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
@@ -54,3 +69,130 @@ pub async fn playlist(query: web::Query<PlaylistQuery>) -> Result<HttpResponse>
         }
     }
 }
+
+fn error_response(e: rusqlite::Error, not_found: &str) -> HttpResponse {
+    if matches!(e, rusqlite::Error::QueryReturnedNoRows) {
+        HttpResponse::NotFound().json(serde_json::json!({ "error": not_found }))
+    } else {
+        HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("Playlist error: {}", e)
+        }))
+    }
+}
+
+/// POST /api/playlist/session — create a server-side shuffle session
+pub async fn create_session(body: web::Json<CreateSessionRequest>) -> Result<HttpResponse> {
+    if !storage::SESSION_MODES.contains(&body.mode.as_str()) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Unknown mode: {} (expected one of {:?})", body.mode, storage::SESSION_MODES)
+        })));
+    }
+    let weight_by = if body.mode == "weighted" {
+        let w = body.weight_by.clone().unwrap_or_else(|| "play_count".to_string());
+        if !storage::WEIGHT_BY.contains(&w.as_str()) {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Unknown weight_by: {} (expected one of {:?})", w, storage::WEIGHT_BY)
+            })));
+        }
+        Some(w)
+    } else {
+        None
+    };
+    let seed = body.seed.map(|s| s & shuffle::MAX_SEED).unwrap_or_else(shuffle::random_seed);
+    let folder_path = body.folder_path.clone();
+    let file_type = body.file_type.clone();
+    let mode = body.mode.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        storage::create_session(&folder_path, file_type.as_deref(), &mode, weight_by.as_deref(), seed)
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+
+    match result {
+        Ok(session) => Ok(HttpResponse::Ok().json(session)),
+        Err(e) => Ok(error_response(e, "Session not found")),
+    }
+}
+
+/// GET /api/playlist/session/{id}
+pub async fn get_session(path: web::Path<String>) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let result = tokio::task::spawn_blocking(move || storage::get_session(&id))
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+
+    match result {
+        Ok(Some(session)) => Ok(HttpResponse::Ok().json(session)),
+        Ok(None) => Ok(error_response(rusqlite::Error::QueryReturnedNoRows, "Session not found")),
+        Err(e) => Ok(error_response(e, "Session not found")),
+    }
+}
+
+/// DELETE /api/playlist/session/{id}
+pub async fn delete_session(path: web::Path<String>) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let result = tokio::task::spawn_blocking(move || storage::delete_session(&id))
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+
+    match result {
+        Ok(true) => Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true }))),
+        Ok(false) => Ok(error_response(rusqlite::Error::QueryReturnedNoRows, "Session not found")),
+        Err(e) => Ok(error_response(e, "Session not found")),
+    }
+}
+
+/// POST /api/playlist/session/{id}/next — advance the session by one file
+/// (for unattended displays that just want "the next picture")
+pub async fn session_next(path: web::Path<String>) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let result = tokio::task::spawn_blocking(move || {
+        let mut next = storage::next_in_session(&id)?;
+        if let Some((item, _)) = next.as_mut() {
+            crate::indexer::storage::attach_loudness(std::slice::from_mut(item));
+        }
+        Ok(next)
+    })
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+
+    match result {
+        Ok(Some((item, session))) => Ok(HttpResponse::Ok().json(SessionNextResponse { item, session })),
+        Ok(None) => Ok(error_response(rusqlite::Error::QueryReturnedNoRows, "Session not found")),
+        Err(e) => Ok(error_response(e, "No playable files in session scope")),
+    }
+}
+
+/// POST /api/playlist/played — record a play (feeds play_count and recency weighting)
+/// The only place plays are counted; session `next` does not count them
+pub async fn played(body: web::Json<PlayedRequest>) -> Result<HttpResponse> {
+    let uuid = body.uuid.clone();
+    let result = tokio::task::spawn_blocking(move || storage::record_play(&uuid))
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+
+    match result {
+        Ok(()) => Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true }))),
+        Err(e) => Ok(error_response(e, "File not found")),
+    }
+}
+
+/// POST /api/playlist/rating — set a 0-5 rating (feeds rating weighting)
+pub async fn rating(body: web::Json<RatingRequest>) -> Result<HttpResponse> {
+    if !(0..=5).contains(&body.rating) {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "rating must be between 0 and 5"
+        })));
+    }
+    let uuid = body.uuid.clone();
+    let rating = body.rating;
+    let result = tokio::task::spawn_blocking(move || storage::set_rating(&uuid, rating))
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+
+    match result {
+        Ok(()) => Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true }))),
+        Err(e) => Ok(error_response(e, "File not found")),
+    }
+}
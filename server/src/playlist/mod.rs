@@ -1,9 +1,17 @@
 pub mod models;
 mod storage;
 mod handlers;
+mod shuffle;
 
 use actix_web::web;
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(web::resource("").route(web::get().to(handlers::playlist)));
+    cfg.service(web::resource("").route(web::get().to(handlers::playlist)))
+       .service(web::resource("/session").route(web::post().to(handlers::create_session)))
+       .service(web::resource("/session/{id}")
+           .route(web::get().to(handlers::get_session))
+           .route(web::delete().to(handlers::delete_session)))
+       .service(web::resource("/session/{id}/next").route(web::post().to(handlers::session_next)))
+       .service(web::resource("/played").route(web::post().to(handlers::played)))
+       .service(web::resource("/rating").route(web::post().to(handlers::rating)));
 }
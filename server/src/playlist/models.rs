@@ -4,11 +4,13 @@ use crate::indexer::models::IndexedFile;
 #[derive(Debug, Deserialize)]
 pub struct PlaylistQuery {
     pub uuid: String,
+    #[serde(default)]
     pub folder_path: String,
-    pub mode: String,              // "sequential" or "shuffle"
+    pub mode: String,              // "sequential", "shuffle", "session" or "album" (whole album in track order)
     pub sort: Option<String>,      // name_asc, name_desc, size_asc, etc.
-    pub file_type: Option<String>, // filter by type (e.g. "video")
+    pub file_type: Option<String>, // filter by type (e.g. "video"), or "animated" for animated images of any format
     pub current_queue: Option<String>, // comma-separated UUIDs of current 7-item queue (shuffle mode)
+    pub session_id: Option<String>, // server-side shuffle session (session mode)
 }
 
 #[derive(Debug, Serialize)]
@@ -16,3 +18,48 @@ pub struct PlaylistResponse {
     pub items: Vec<IndexedFile>,
     pub current_index: usize,
 }
+
+/// Server-side shuffle session: a fixed order over the whole scope,
+/// regenerated (with a derived seed) each time a full cycle has been played
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistSession {
+    pub id: String,
+    pub folder_path: String,
+    pub file_type: Option<String>,
+    pub mode: String,              // "no_repeat" or "weighted"
+    pub weight_by: Option<String>, // "rating", "recency" or "play_count" (weighted mode)
+    pub seed: u64,
+    pub cycle: u64,
+    pub cursor: usize,
+    pub total: usize,
+    #[serde(skip)]
+    pub order: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSessionRequest {
+    pub folder_path: String,
+    pub file_type: Option<String>,
+    pub mode: String,
+    pub weight_by: Option<String>,
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionNextResponse {
+    pub item: IndexedFile,
+    pub session: PlaylistSession,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlayedRequest {
+    pub uuid: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RatingRequest {
+    pub uuid: String,
+    pub rating: i64, // 0 (unrated) - 5
+}
@@ -0,0 +1,101 @@
+// Deterministic shuffle primitives for server-side playlist sessions.
+// Same seed + same candidate list (sorted by uuid) => same order on every device.
+
+/// SplitMix64: tiny, fast, good-enough PRNG. No external crate needed and
+/// the output is stable across platforms and releases.
+pub struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform float in (0, 1]
+    pub fn next_f64(&mut self) -> f64 {
+        ((self.next_u64() >> 11) as f64 + 1.0) / (1u64 << 53) as f64
+    }
+}
+
+/// Largest seed that survives a round trip through a JavaScript number
+pub const MAX_SEED: u64 = (1u64 << 53) - 1;
+
+/// Fresh random seed (used when the client does not pin one)
+pub fn random_seed() -> u64 {
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    u64::from_le_bytes(bytes[..8].try_into().unwrap()) & MAX_SEED
+}
+
+/// Derive the seed of a given cycle, so every pass over the scope gets a new
+/// (but still reproducible) order
+pub fn cycle_seed(seed: u64, cycle: u64) -> u64 {
+    if cycle == 0 {
+        return seed;
+    }
+    SplitMix64::new(seed ^ cycle.wrapping_mul(0x9E37_79B9_7F4A_7C15)).next_u64()
+}
+
+/// Fisher-Yates shuffle
+pub fn permutation<T>(mut items: Vec<T>, seed: u64) -> Vec<T> {
+    let mut rng = SplitMix64::new(seed);
+    for i in (1..items.len()).rev() {
+        let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+    items
+}
+
+/// Weighted sampling without replacement (Efraimidis-Spirakis).
+/// Each item draws key = ln(u) / w; sorting keys descending yields an order
+/// where heavier items tend to come first, and every item appears exactly once.
+pub fn weighted_order<T>(items: Vec<(T, f64)>, seed: u64) -> Vec<T> {
+    let mut rng = SplitMix64::new(seed);
+    let mut keyed: Vec<(f64, T)> = items
+        .into_iter()
+        .map(|(item, w)| {
+            let w = if w.is_finite() && w > 0.0 { w } else { 1e-6 };
+            (rng.next_f64().ln() / w, item)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    keyed.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutation_is_deterministic() {
+        let items: Vec<u32> = (0..50).collect();
+        let a = permutation(items.clone(), 42);
+        let b = permutation(items.clone(), 42);
+        let c = permutation(items.clone(), 43);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        let mut sorted = a.clone();
+        sorted.sort();
+        assert_eq!(sorted, items);
+    }
+
+    #[test]
+    fn test_weighted_order_prefers_heavy_items() {
+        let mut heavy_first = 0;
+        for seed in 0..200 {
+            let order = weighted_order(vec![("light", 1.0), ("heavy", 20.0)], seed);
+            assert_eq!(order.len(), 2);
+            if order[0] == "heavy" {
+                heavy_first += 1;
+            }
+        }
+        assert!(heavy_first > 150);
+    }
+}
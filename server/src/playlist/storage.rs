@@ -1,13 +1,16 @@
+use chrono::{DateTime, Utc};
+use rusqlite::params;
 use crate::database::get_connection;
 use crate::indexer::models::IndexedFile;
 use crate::indexer::storage::{map_file_row, get_file_by_uuid};
+use super::models::PlaylistSession;
 
-const FILE_COLUMNS: &str = "uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url";
+const FILE_COLUMNS: &str = "uuid, fingerprint, current_path, folder_path, file_name, file_type, extension, file_size, created_at, modified_at, indexed_at, source_url, blurhash, animated, frame_count";
 const CONTEXT_SIZE: i64 = 3;
 
 fn fetch_window(
     folder_path: &str,
-    file_type: Option<&str>,
+    type_param: Option<&str>,
     ignored: &[String],
     ignore_clause: &str,
     type_clause: &str,
@@ -21,7 +24,7 @@ fn fetch_window(
         FILE_COLUMNS, type_clause, ignore_clause, order_by
     );
     let mut p: Vec<String> = vec![folder_path.to_string()];
-    if let Some(ft) = file_type { p.push(ft.to_string()); }
+    if let Some(ft) = type_param { p.push(ft.to_string()); }
     p.extend(ignored.iter().cloned());
     p.push(lim.to_string());
     p.push(off.to_string());
@@ -44,6 +47,17 @@ fn sort_clause(sort: Option<&str>) -> (&str, &str) {
     }
 }
 
+/// Type filter clause and its bound parameter. "animated" is a pseudo type that
+/// matches animated images of any format (GIF / WebP / APNG / AVIF, detected from
+/// the file header at index time) instead of the `file_type` column
+fn type_filter(file_type: Option<&str>, column_prefix: &str) -> (String, Option<String>) {
+    match file_type {
+        None => (String::new(), None),
+        Some("animated") => (format!(" AND {}animated = 1", column_prefix), None),
+        Some(ft) => (format!(" AND {}file_type = ?", column_prefix), Some(ft.to_string())),
+    }
+}
+
 fn ignored_files_clause() -> (String, Vec<String>) {
     let ignored = crate::config_api::storage::load_config()
         .map(|c| c.ignored_files)
@@ -69,7 +83,7 @@ pub fn get_playlist_sequential(
 
     let (col, dir) = sort_clause(sort);
     let (ignore_clause, ignored) = ignored_files_clause();
-    let type_clause = if file_type.is_some() { " AND file_type = ?" } else { "" };
+    let (type_clause, type_param) = type_filter(file_type, "");
 
     // Count files that come before this one in the sorted order
     // For ASC: count where (col < value) OR (col = value AND uuid < target_uuid)
@@ -89,7 +103,7 @@ pub fn get_playlist_sequential(
     );
 
     let mut pos_params: Vec<String> = vec![folder_path.to_string()];
-    if let Some(ft) = file_type { pos_params.push(ft.to_string()); }
+    pos_params.extend(type_param.clone());
     pos_params.extend(ignored.iter().cloned());
     pos_params.push(sort_value.clone());
     pos_params.push(sort_value.clone());
@@ -107,7 +121,7 @@ pub fn get_playlist_sequential(
         type_clause, ignore_clause
     );
     let mut total_params: Vec<String> = vec![folder_path.to_string()];
-    if let Some(ft) = file_type { total_params.push(ft.to_string()); }
+    total_params.extend(type_param.clone());
     total_params.extend(ignored.iter().cloned());
 
     let total: i64 = {
@@ -125,7 +139,7 @@ pub fn get_playlist_sequential(
     // Build window with wrapping
     let ctx = CONTEXT_SIZE.min(total - 1);
 
-    let fw = |off: i64, lim: i64| fetch_window(folder_path, file_type, &ignored, &ignore_clause, &type_clause, &order_by, off, lim);
+    let fw = |off: i64, lim: i64| fetch_window(folder_path, type_param.as_deref(), &ignored, &ignore_clause, &type_clause, &order_by, off, lim);
 
     // Items before (wrapping)
     let before = if position >= ctx {
@@ -154,6 +168,22 @@ pub fn get_playlist_sequential(
     Ok((items, current_index))
 }
 
+/// Album playlist: every track of the file's album (same album tag and album artist)
+/// in disc / track order, so a whole album plays straight through. Files without an
+/// album tag fall back to the folder's audio files sorted by name
+pub fn get_playlist_album(folder_path: &str, uuid: &str) -> Result<(Vec<IndexedFile>, usize), rusqlite::Error> {
+    match crate::music::storage::get_album_files(uuid)? {
+        Some(items) if !items.is_empty() => {
+            let current_index = items
+                .iter()
+                .position(|f| f.uuid == uuid)
+                .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+            Ok((items, current_index))
+        }
+        _ => get_playlist_sequential(folder_path, uuid, Some("audio"), Some("name_asc")),
+    }
+}
+
 /// Shuffle playlist: current file + 6 random files from the folder
 ///
 /// `current_queue`: the client's current 7-item queue (UUIDs in order).
@@ -171,7 +201,7 @@ pub fn get_playlist_shuffle(
         .ok_or_else(|| rusqlite::Error::QueryReturnedNoRows)?;
 
     let (ignore_clause, ignored) = ignored_files_clause();
-    let type_clause = if file_type.is_some() { " AND file_type = ?" } else { "" };
+    let (type_clause, type_param) = type_filter(file_type, "");
 
     if let Some(queue) = current_queue {
         // Find target's position in old queue
@@ -230,7 +260,7 @@ pub fn get_playlist_shuffle(
 
         let mut rand_params: Vec<String> = vec![folder_path.to_string()];
         rand_params.extend(exclude);
-        if let Some(ft) = file_type { rand_params.push(ft.to_string()); }
+        rand_params.extend(type_param.clone());
         rand_params.extend(ignored.iter().cloned());
         rand_params.push(need_total.to_string());
 
@@ -261,7 +291,7 @@ pub fn get_playlist_shuffle(
         );
 
         let mut rand_params: Vec<String> = vec![folder_path.to_string(), uuid.to_string()];
-        if let Some(ft) = file_type { rand_params.push(ft.to_string()); }
+        rand_params.extend(type_param.clone());
         rand_params.extend(ignored.iter().cloned());
 
         let mut stmt = conn.prepare(&rand_query)?;
@@ -278,3 +308,347 @@ pub fn get_playlist_shuffle(
         Ok((items, current_index))
     }
 }
+
+// ============================================================================
+// Server-side shuffle sessions
+// ============================================================================
+
+pub const SESSION_MODES: &[&str] = &["no_repeat", "weighted"];
+pub const WEIGHT_BY: &[&str] = &["rating", "recency", "play_count"];
+
+/// Sessions untouched for this long are dropped when a new one is created
+const SESSION_TTL_DAYS: i64 = 30;
+
+struct Candidate {
+    uuid: String,
+    last_played_at: Option<String>,
+    play_count: i64,
+    rating: i64,
+}
+
+impl Candidate {
+    fn last_played(&self) -> Option<DateTime<Utc>> {
+        let time = self.last_played_at.as_deref()?;
+        DateTime::parse_from_rfc3339(time).ok().map(|t| t.with_timezone(&Utc))
+    }
+}
+
+/// Everything playable in scope, sorted by uuid so that the shuffle input
+/// (and therefore the output for a given seed) is identical on every device
+fn scope_candidates(folder_path: &str, file_type: Option<&str>) -> Result<Vec<Candidate>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let (ignore_clause, ignored) = ignored_files_clause();
+    let (type_clause, type_param) = type_filter(file_type, "f.");
+    let query = format!(
+        "SELECT f.uuid, s.last_played_at, COALESCE(s.play_count, 0), COALESCE(s.rating, 0)
+         FROM file_index f LEFT JOIN file_stats s ON s.file_uuid = f.uuid
+         WHERE f.folder_path = ? AND f.current_path IS NOT NULL{}{} ORDER BY f.uuid",
+        type_clause, ignore_clause
+    );
+    let mut p: Vec<String> = vec![folder_path.to_string()];
+    p.extend(type_param);
+    p.extend(ignored);
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(p.iter()), |row| {
+        Ok(Candidate {
+            uuid: row.get(0)?,
+            last_played_at: row.get(1)?,
+            play_count: row.get(2)?,
+            rating: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Weight of a candidate for weighted mode.
+/// - rating: unrated = 1, each star adds 1 (5 stars is 6x as likely)
+/// - recency: favours files that have gone longest without a play. Never played
+///   (including newly added) files get the full weight 1; a played file starts near 0
+///   and recovers half of the remaining weight every 7 days since its last play.
+///   Ages are measured from the latest play in scope, not "now", so the order stays reproducible
+/// - play_count: 1 / (1 + plays), rarely played files surface first
+fn candidate_weight(c: &Candidate, weight_by: &str, latest_play: Option<DateTime<Utc>>) -> f64 {
+    match weight_by {
+        "rating" => 1.0 + c.rating.clamp(0, 5) as f64,
+        "recency" => match (latest_play, c.last_played()) {
+            (Some(latest), Some(played)) => {
+                let idle_days = (latest - played).num_seconds().max(0) as f64 / 86400.0;
+                (1.0 - 0.5f64.powf(idle_days / 7.0)).max(1e-3)
+            }
+            _ => 1.0,
+        },
+        _ => 1.0 / (1.0 + c.play_count.max(0) as f64),
+    }
+}
+
+/// Build the play order of one cycle
+fn build_order(
+    folder_path: &str,
+    file_type: Option<&str>,
+    mode: &str,
+    weight_by: Option<&str>,
+    seed: u64,
+    cycle: u64,
+    previous_last: Option<&str>,
+) -> Result<Vec<String>, rusqlite::Error> {
+    let candidates = scope_candidates(folder_path, file_type)?;
+    let cycle_seed = super::shuffle::cycle_seed(seed, cycle);
+
+    let mut order: Vec<String> = if mode == "weighted" {
+        let weight_by = weight_by.unwrap_or("play_count");
+        let latest_play = candidates.iter().filter_map(Candidate::last_played).max();
+        let weighted = candidates.iter()
+            .map(|c| (c.uuid.clone(), candidate_weight(c, weight_by, latest_play)))
+            .collect();
+        super::shuffle::weighted_order(weighted, cycle_seed)
+    } else {
+        super::shuffle::permutation(candidates.into_iter().map(|c| c.uuid).collect(), cycle_seed)
+    };
+
+    // Don't let the cycle boundary play the same file twice in a row
+    if order.len() > 1 && previous_last == Some(order[0].as_str()) {
+        order.swap(0, 1);
+    }
+    Ok(order)
+}
+
+fn map_session_row(row: &rusqlite::Row) -> Result<PlaylistSession, rusqlite::Error> {
+    let order_json: String = row.get(8)?;
+    let order: Vec<String> = serde_json::from_str(&order_json).unwrap_or_default();
+    Ok(PlaylistSession {
+        id: row.get(0)?,
+        folder_path: row.get(1)?,
+        file_type: row.get(2)?,
+        mode: row.get(3)?,
+        weight_by: row.get(4)?,
+        seed: row.get::<_, i64>(5)? as u64,
+        cycle: row.get::<_, i64>(6)? as u64,
+        cursor: row.get::<_, i64>(7)? as usize,
+        total: order.len(),
+        order,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+fn save_session_state(conn: &rusqlite::Connection, session: &PlaylistSession) -> Result<(), rusqlite::Error> {
+    let order_json = serde_json::to_string(&session.order).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "UPDATE playlist_sessions SET cycle = ?1, cursor = ?2, order_list = ?3, updated_at = ?4 WHERE id = ?5",
+        params![session.cycle as i64, session.cursor as i64, order_json, session.updated_at, session.id],
+    )?;
+    Ok(())
+}
+
+/// Create a session and generate the order of its first cycle
+pub fn create_session(
+    folder_path: &str,
+    file_type: Option<&str>,
+    mode: &str,
+    weight_by: Option<&str>,
+    seed: u64,
+) -> Result<PlaylistSession, rusqlite::Error> {
+    let conn = get_connection()?;
+    let now = Utc::now().to_rfc3339();
+
+    let cutoff = (Utc::now() - chrono::Duration::days(SESSION_TTL_DAYS)).to_rfc3339();
+    conn.execute("DELETE FROM playlist_sessions WHERE updated_at < ?1", params![cutoff])?;
+
+    let order = build_order(folder_path, file_type, mode, weight_by, seed, 0, None)?;
+    let session = PlaylistSession {
+        id: uuid::Uuid::new_v4().to_string(),
+        folder_path: folder_path.to_string(),
+        file_type: file_type.map(|s| s.to_string()),
+        mode: mode.to_string(),
+        weight_by: weight_by.map(|s| s.to_string()),
+        seed,
+        cycle: 0,
+        cursor: 0,
+        total: order.len(),
+        order,
+        created_at: now.clone(),
+        updated_at: now,
+    };
+
+    let order_json = serde_json::to_string(&session.order).unwrap_or_else(|_| "[]".to_string());
+    conn.execute(
+        "INSERT INTO playlist_sessions (id, folder_path, file_type, mode, weight_by, seed, cycle, cursor, order_list, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, 0, ?7, ?8, ?8)",
+        params![session.id, session.folder_path, session.file_type, session.mode, session.weight_by, seed as i64, order_json, session.created_at],
+    )?;
+    Ok(session)
+}
+
+pub fn get_session(id: &str) -> Result<Option<PlaylistSession>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, folder_path, file_type, mode, weight_by, seed, cycle, cursor, order_list, created_at, updated_at
+         FROM playlist_sessions WHERE id = ?1"
+    )?;
+    let mut rows = stmt.query_map(params![id], map_session_row)?;
+    rows.next().transpose()
+}
+
+pub fn delete_session(id: &str) -> Result<bool, rusqlite::Error> {
+    let conn = get_connection()?;
+    let affected = conn.execute("DELETE FROM playlist_sessions WHERE id = ?1", params![id])?;
+    Ok(affected > 0)
+}
+
+/// Advance the session by one file. Starts a new cycle (new order, derived
+/// seed, fresh scope so newly added files join) once every file was played.
+/// Files deleted since the order was built are skipped.
+/// Plays are not counted here: clients report them once via POST /played,
+/// the same as for any other playback.
+pub fn next_in_session(id: &str) -> Result<Option<(IndexedFile, PlaylistSession)>, rusqlite::Error> {
+    let Some(mut session) = get_session(id)? else { return Ok(None) };
+    let conn = get_connection()?;
+
+    // At most one regeneration per call: an empty scope must not loop forever
+    let mut regenerated = false;
+    loop {
+        if session.cursor >= session.order.len() {
+            if regenerated {
+                return Err(rusqlite::Error::QueryReturnedNoRows);
+            }
+            let previous_last = session.order.last().cloned();
+            session.cycle += 1;
+            session.order = build_order(
+                &session.folder_path,
+                session.file_type.as_deref(),
+                &session.mode,
+                session.weight_by.as_deref(),
+                session.seed,
+                session.cycle,
+                previous_last.as_deref(),
+            )?;
+            session.total = session.order.len();
+            session.cursor = 0;
+            regenerated = true;
+            continue;
+        }
+
+        let uuid = session.order[session.cursor].clone();
+        session.cursor += 1;
+        if let Some(file) = get_file_by_uuid(&uuid)? {
+            if file.current_path.is_some() {
+                session.updated_at = Utc::now().to_rfc3339();
+                save_session_state(&conn, &session)?;
+                return Ok(Some((file, session)));
+            }
+        }
+    }
+}
+
+/// Session playlist window: 3 before + current + 3 after in session order.
+/// Moves the session cursor past `uuid`, so `next` continues from there.
+/// A uuid outside the session order is returned alone, followed by the upcoming files.
+pub fn get_playlist_session(session_id: &str, uuid: &str) -> Result<(Vec<IndexedFile>, usize), rusqlite::Error> {
+    let mut session = get_session(session_id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    let target = get_file_by_uuid(uuid)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    let total = session.order.len();
+    let ctx = (CONTEXT_SIZE as usize).min(total.saturating_sub(1));
+
+    let load = |uuids: &[String]| -> Result<Vec<IndexedFile>, rusqlite::Error> {
+        let mut files = Vec::new();
+        for u in uuids {
+            if let Some(f) = get_file_by_uuid(u)? {
+                if f.current_path.is_some() { files.push(f); }
+            }
+        }
+        Ok(files)
+    };
+
+    let (before, after) = match session.order.iter().position(|u| u == uuid) {
+        Some(pos) => {
+            let before: Vec<String> = (1..=ctx).rev()
+                .map(|d| session.order[(pos + total - d) % total].clone())
+                .collect();
+            let after: Vec<String> = (1..=ctx)
+                .map(|d| session.order[(pos + d) % total].clone())
+                .collect();
+            session.cursor = pos + 1;
+            (before, after)
+        }
+        None => {
+            let after: Vec<String> = session.order.iter()
+                .skip(session.cursor)
+                .chain(session.order.iter().take(session.cursor))
+                .take(CONTEXT_SIZE as usize * 2)
+                .cloned()
+                .collect();
+            (Vec::new(), after)
+        }
+    };
+
+    session.updated_at = Utc::now().to_rfc3339();
+    save_session_state(&get_connection()?, &session)?;
+
+    let mut items = load(&before)?;
+    let current_index = items.len();
+    items.push(target);
+    items.extend(load(&after)?);
+    Ok((items, current_index))
+}
+
+// ============================================================================
+// Play statistics
+// ============================================================================
+
+/// Unknown uuids are rejected (QueryReturnedNoRows) instead of creating orphan stats rows
+pub fn record_play(uuid: &str) -> Result<(), rusqlite::Error> {
+    get_file_by_uuid(uuid)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT INTO file_stats (file_uuid, play_count, last_played_at) VALUES (?1, 1, ?2)
+         ON CONFLICT(file_uuid) DO UPDATE SET play_count = play_count + 1, last_played_at = excluded.last_played_at",
+        params![uuid, Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+pub fn set_rating(uuid: &str, rating: i64) -> Result<(), rusqlite::Error> {
+    get_file_by_uuid(uuid)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT INTO file_stats (file_uuid, rating) VALUES (?1, ?2)
+         ON CONFLICT(file_uuid) DO UPDATE SET rating = excluded.rating",
+        params![uuid, rating],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(last_played_at: Option<&str>) -> Candidate {
+        Candidate {
+            uuid: String::new(),
+            last_played_at: last_played_at.map(str::to_string),
+            play_count: 0,
+            rating: 0,
+        }
+    }
+
+    #[test]
+    fn test_recency_weight_favours_long_unplayed() {
+        let just_played = candidate(Some("2026-03-01T00:00:00Z"));
+        let week_ago = candidate(Some("2026-02-22T00:00:00Z"));
+        let two_months_ago = candidate(Some("2025-12-31T00:00:00Z"));
+        let never = candidate(None);
+        let latest = [&just_played, &week_ago, &two_months_ago, &never]
+            .iter()
+            .filter_map(|c| c.last_played())
+            .max();
+
+        let weight = |c: &Candidate| candidate_weight(c, "recency", latest);
+        assert_eq!(weight(&just_played), 1e-3);
+        assert!((weight(&week_ago) - 0.5).abs() < 1e-9);
+        assert!(weight(&two_months_ago) > 0.99);
+        // Never played (e.g. newly added) files get the full weight
+        assert_eq!(weight(&never), 1.0);
+        // Nothing played yet: uniform
+        assert_eq!(candidate_weight(&just_played, "recency", None), 1.0);
+    }
+}
@@ -0,0 +1,318 @@
+// 动图检测：按文件头判断 GIF / WebP / APNG / AVIF 是否为动画并统计帧数
+// 扩展名不可靠：.gif 可能只有一帧，.webp / .png / .avif 也可能是动画
+use image::{AnimationDecoder, DynamicImage};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// 可能包含动画的格式
+pub const ANIMATABLE_EXTENSIONS: &[&str] = &["gif", "webp", "png", "avif"];
+
+/// 统计帧数。不可能是动画的格式返回 None；可能是动画但解析失败时按静图（1 帧）处理
+pub fn probe_frame_count(path: &Path) -> Option<u32> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !ANIMATABLE_EXTENSIONS.contains(&extension.as_str()) {
+        return None;
+    }
+    let count = File::open(path)
+        .ok()
+        .and_then(|f| count_frames(&mut BufReader::new(f)))
+        .unwrap_or(1);
+    Some(count.max(1))
+}
+
+/// 按魔数分派（不看扩展名，改错扩展名的文件也能识别）
+fn count_frames<R: Read + Seek>(r: &mut R) -> Option<u32> {
+    let mut magic = [0u8; 12];
+    r.read_exact(&mut magic).ok()?;
+    r.seek(SeekFrom::Start(0)).ok()?;
+    if magic.starts_with(b"GIF8") {
+        gif_frames(r)
+    } else if magic.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_frames(r)
+    } else if magic.starts_with(b"RIFF") && &magic[8..12] == b"WEBP" {
+        webp_frames(r)
+    } else if &magic[4..8] == b"ftyp" {
+        avif_frames(r)
+    } else {
+        Some(1)
+    }
+}
+
+fn read_byte<R: Read>(r: &mut R) -> Option<u8> {
+    let mut b = [0u8; 1];
+    r.read_exact(&mut b).ok()?;
+    Some(b[0])
+}
+
+fn skip<R: Seek>(r: &mut R, n: u64) -> Option<()> {
+    r.seek(SeekFrom::Current(n as i64)).ok().map(|_| ())
+}
+
+/// GIF 没有帧数字段，只能逐块走到文件尾（数据子块按长度跳过，不解码）
+fn gif_frames<R: Read + Seek>(r: &mut R) -> Option<u32> {
+    let mut header = [0u8; 13];
+    r.read_exact(&mut header).ok()?;
+    // 全局颜色表
+    if header[10] & 0x80 != 0 {
+        skip(r, 3 << ((header[10] & 0x07) + 1))?;
+    }
+    let skip_sub_blocks = |r: &mut R| -> Option<()> {
+        loop {
+            match read_byte(r)? {
+                0 => return Some(()),
+                n => skip(r, n as u64)?,
+            }
+        }
+    };
+
+    let mut frames = 0;
+    loop {
+        match read_byte(r) {
+            // 图像描述符：位置和尺寸 8 字节 + 标志位，之后是局部颜色表和 LZW 数据
+            Some(0x2C) => {
+                let mut desc = [0u8; 9];
+                if r.read_exact(&mut desc).is_err() {
+                    break;
+                }
+                if desc[8] & 0x80 != 0 {
+                    skip(r, 3 << ((desc[8] & 0x07) + 1))?;
+                }
+                frames += 1;
+                if read_byte(r).is_none() || skip_sub_blocks(r).is_none() {
+                    break;
+                }
+            }
+            Some(0x21) => {
+                if read_byte(r).is_none() || skip_sub_blocks(r).is_none() {
+                    break;
+                }
+            }
+            // 0x3B 为结束符；截断的文件按已读到的帧数计算
+            _ => break,
+        }
+    }
+    Some(frames)
+}
+
+/// APNG 在第一个 IDAT 之前有 acTL 块，其中记录帧数
+fn png_frames<R: Read + Seek>(r: &mut R) -> Option<u32> {
+    skip(r, 8)?;
+    loop {
+        let mut head = [0u8; 8];
+        r.read_exact(&mut head).ok()?;
+        let len = u32::from_be_bytes(head[0..4].try_into().ok()?) as u64;
+        match &head[4..8] {
+            b"acTL" => {
+                let mut num_frames = [0u8; 4];
+                r.read_exact(&mut num_frames).ok()?;
+                return Some(u32::from_be_bytes(num_frames));
+            }
+            b"IDAT" | b"IEND" => return Some(1),
+            _ => skip(r, len + 4)?,
+        }
+    }
+}
+
+/// 动画 WebP：VP8X 的动画标志位，帧数为 ANMF 块的个数
+fn webp_frames<R: Read + Seek>(r: &mut R) -> Option<u32> {
+    skip(r, 12)?;
+    let mut frames = 0;
+    let mut animated = false;
+    loop {
+        let mut head = [0u8; 8];
+        if r.read_exact(&mut head).is_err() {
+            break;
+        }
+        let len = u32::from_le_bytes(head[4..8].try_into().ok()?) as u64;
+        let padded = len + (len & 1);
+        match &head[0..4] {
+            b"VP8X" => {
+                let flags = read_byte(r)?;
+                animated = flags & 0x02 != 0;
+                if !animated {
+                    return Some(1);
+                }
+                skip(r, padded - 1)?;
+            }
+            b"ANMF" => {
+                frames += 1;
+                skip(r, padded)?;
+            }
+            // 简单格式（VP8 / VP8L）只有一帧
+            b"VP8 " | b"VP8L" if !animated => return Some(1),
+            _ => skip(r, padded)?,
+        }
+    }
+    Some(frames)
+}
+
+/// AVIF 图像序列：moov/trak/mdia/minf/stbl/stts 中各段 sample_count 之和为帧数；
+/// 没有 moov（只有 meta）的是静态 AVIF
+fn avif_frames<R: Read + Seek>(r: &mut R) -> Option<u32> {
+    fn read_box_header<R: Read>(r: &mut R) -> Option<(u64, [u8; 4], u64)> {
+        let mut head = [0u8; 8];
+        r.read_exact(&mut head).ok()?;
+        let size = u32::from_be_bytes(head[0..4].try_into().ok()?) as u64;
+        let kind: [u8; 4] = head[4..8].try_into().ok()?;
+        if size == 1 {
+            let mut large = [0u8; 8];
+            r.read_exact(&mut large).ok()?;
+            Some((u64::from_be_bytes(large), kind, 16))
+        } else {
+            Some((size, kind, 8))
+        }
+    }
+
+    // 在 [start, end) 范围内逐层进入容器 box，返回各条轨道 stts 帧数的最大值（alpha 轨与主轨帧数相同）
+    fn walk<R: Read + Seek>(r: &mut R, end: u64, depth: usize) -> Option<u32> {
+        let mut best: Option<u32> = None;
+        loop {
+            let pos = r.stream_position().ok()?;
+            if pos + 8 > end {
+                break;
+            }
+            let Some((size, kind, header_len)) = read_box_header(r) else { break };
+            let box_end = if size == 0 { end } else { pos + size };
+            if box_end < pos + header_len || box_end > end {
+                break;
+            }
+            match &kind {
+                b"moov" | b"trak" | b"mdia" | b"minf" | b"stbl" if depth < 5 => {
+                    if let Some(n) = walk(r, box_end, depth + 1) {
+                        best = Some(best.map_or(n, |b| b.max(n)));
+                    }
+                }
+                b"stts" => {
+                    // version/flags(4) + entry_count(4) + [sample_count(4) + sample_delta(4)]...
+                    let mut head = [0u8; 8];
+                    r.read_exact(&mut head).ok()?;
+                    let entries = u32::from_be_bytes(head[4..8].try_into().ok()?);
+                    let mut total: u32 = 0;
+                    for _ in 0..entries.min(1 << 20) {
+                        let mut entry = [0u8; 8];
+                        r.read_exact(&mut entry).ok()?;
+                        total = total.saturating_add(u32::from_be_bytes(entry[0..4].try_into().ok()?));
+                    }
+                    best = Some(best.map_or(total, |b| b.max(total)));
+                }
+                _ => {}
+            }
+            r.seek(SeekFrom::Start(box_end)).ok()?;
+        }
+        best
+    }
+
+    let end = r.seek(SeekFrom::End(0)).ok()?;
+    r.seek(SeekFrom::Start(0)).ok()?;
+    Some(walk(r, end, 0).unwrap_or(1))
+}
+
+/// 代表帧最多取到第几帧：只能从头逐帧解码，上千帧的长动图取中间一帧代价太高
+const MAX_REPRESENTATIVE_FRAME: u32 = 30;
+
+/// 代表帧序号：中间一帧，但不超过 MAX_REPRESENTATIVE_FRAME
+fn representative_index(frame_count: u32) -> usize {
+    (frame_count / 2).min(MAX_REPRESENTATIVE_FRAME) as usize
+}
+
+/// 动图的代表帧：取中间一帧（很多动图第一帧是黑场或淡入，不适合做缩略图），长动图取靠前的一帧
+/// 不支持逐帧解码的格式（AVIF）返回 None，由调用方按静图处理
+pub fn representative_frame(path: &Path, frame_count: u32) -> Option<DynamicImage> {
+    let target = representative_index(frame_count);
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut magic = [0u8; 12];
+    reader.read_exact(&mut magic).ok()?;
+    reader.seek(SeekFrom::Start(0)).ok()?;
+
+    let frames = if magic.starts_with(b"GIF8") {
+        image::codecs::gif::GifDecoder::new(reader).ok()?.into_frames()
+    } else if magic.starts_with(b"\x89PNG\r\n\x1a\n") {
+        image::codecs::png::PngDecoder::new(reader).ok()?.apng().into_frames()
+    } else if magic.starts_with(b"RIFF") && &magic[8..12] == b"WEBP" {
+        image::codecs::webp::WebPDecoder::new(reader).ok()?.into_frames()
+    } else {
+        return None;
+    };
+    // 解码失败的帧之前若已有成功的帧，退而取最后一帧成功的
+    let mut last = None;
+    for (i, frame) in frames.enumerate() {
+        match frame {
+            Ok(f) => last = Some(f),
+            Err(_) => break,
+        }
+        if i >= target {
+            break;
+        }
+    }
+    last.map(|f| DynamicImage::ImageRgba8(f.into_buffer()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn gif(frames: usize) -> Vec<u8> {
+        // 1x1，带 2 色全局颜色表
+        let mut g = b"GIF89a\x01\x00\x01\x00\x80\x00\x00".to_vec();
+        g.extend_from_slice(&[0, 0, 0, 255, 255, 255]);
+        // NETSCAPE 循环扩展
+        g.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+        for _ in 0..frames {
+            g.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00, 0x0A, 0x00, 0x00, 0x00]);
+            g.extend_from_slice(&[0x2C, 0, 0, 0, 0, 1, 0, 1, 0, 0x00]);
+            g.extend_from_slice(&[0x02, 0x02, 0x44, 0x01, 0x00]);
+        }
+        g.push(0x3B);
+        g
+    }
+
+    #[test]
+    fn test_count_frames() {
+        assert_eq!(count_frames(&mut Cursor::new(gif(1))), Some(1));
+        assert_eq!(count_frames(&mut Cursor::new(gif(3))), Some(3));
+
+        // APNG：IHDR 之后的 acTL 记录 5 帧
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend_from_slice(&[0, 0, 0, 13]);
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&[0; 13 + 4]);
+        png.extend_from_slice(&[0, 0, 0, 8]);
+        png.extend_from_slice(b"acTL");
+        png.extend_from_slice(&[0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(count_frames(&mut Cursor::new(png)), Some(5));
+
+        // 动画 WebP：VP8X（动画标志）+ ANIM + 2 个 ANMF
+        // 奇数长度的块补 1 字节
+        let chunk = |kind: &[u8], body: &[u8]| [kind, &(body.len() as u32).to_le_bytes(), body, &vec![0; body.len() & 1]].concat();
+        let body = [
+            chunk(b"VP8X", &[0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            chunk(b"ANIM", &[0; 6]),
+            chunk(b"ANMF", &[0; 17]),
+            chunk(b"ANMF", &[0; 16]),
+        ].concat();
+        let webp = [b"RIFF".as_slice(), &((body.len() + 4) as u32).to_le_bytes(), b"WEBP", &body].concat();
+        assert_eq!(count_frames(&mut Cursor::new(webp)), Some(2));
+
+        // AVIF 图像序列：moov/trak/mdia/minf/stbl/stts，两段共 24 帧
+        let bx = |kind: &[u8], body: &[u8]| [&((body.len() + 8) as u32).to_be_bytes(), kind, body].concat();
+        let stts = bx(b"stts", &[[0u8; 4].as_slice(), &2u32.to_be_bytes(), &20u32.to_be_bytes(), &1u32.to_be_bytes(), &4u32.to_be_bytes(), &1u32.to_be_bytes()].concat());
+        let moov = bx(b"moov", &bx(b"trak", &bx(b"mdia", &bx(b"minf", &bx(b"stbl", &stts)))));
+        let avif = [bx(b"ftyp", b"avis\0\0\0\0avifavis"), bx(b"meta", &[0; 4]), moov].concat();
+        assert_eq!(count_frames(&mut Cursor::new(avif)), Some(24));
+        let still = [bx(b"ftyp", b"avif\0\0\0\0avifmif1"), bx(b"meta", &[0; 4])].concat();
+        assert_eq!(count_frames(&mut Cursor::new(still)), Some(1));
+    }
+
+    #[test]
+    fn test_representative_index() {
+        assert_eq!(representative_index(2), 1);
+        assert_eq!(representative_index(24), 12);
+        assert_eq!(representative_index(5000), MAX_REPRESENTATIVE_FRAME as usize);
+    }
+}
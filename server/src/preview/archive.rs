@@ -0,0 +1,300 @@
+// 压缩包浏览：列出 ZIP / CBZ 内的条目，按条目流式返回内容或缩略图
+// 漫画 CBZ、Pixiv 动图 ZIP 可以逐页浏览，不必先解压到磁盘
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use futures_util::stream;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use super::content::resolve_source;
+use super::disk_cache;
+use super::exif;
+use super::image_proc::{cache_path, image_response, process_permits, CACHE};
+use super::models::{ArchiveEntry, ArchiveListResponse, ARCHIVE_EXTENSIONS};
+use super::thumb_cache;
+use super::thumbnail::{encode_thumbnail, negotiate_format};
+use crate::indexer::scanner::classify_extension;
+
+/// 生成缩略图时读入内存的条目大小上限
+const MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// 流式返回时每块的大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// 包内内容和 API 同源返回，HTML / SVG 里的脚本能读到 localStorage 里的 API Key
+/// 用 CSP sandbox 把文档放进不透明源并禁止脚本
+const SANDBOX_CSP: &str = "sandbox; default-src 'self' data:; script-src 'none'";
+
+/// 解析 path / uuid 并确认是支持的压缩包
+fn resolve_archive(query: &HashMap<String, String>) -> Result<PathBuf> {
+    let source_path = resolve_source(query)?;
+    if !source_path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let extension = source_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !ARCHIVE_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest("仅支持 ZIP / CBZ 文件"));
+    }
+    Ok(source_path)
+}
+
+pub(super) fn open_archive(path: &Path) -> std::result::Result<zip::ZipArchive<std::fs::File>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("打开 ZIP 文件失败: {}", e))?;
+    zip::ZipArchive::new(file).map_err(|e| format!("读取 ZIP 文件失败: {}", e))
+}
+
+/// macOS 打包时附带的资源分支和系统文件，对浏览没有意义
+fn is_junk(name: &str) -> bool {
+    name.starts_with("__MACOSX/")
+        || name.rsplit('/').next().map(|n| n == ".DS_Store" || n == "Thumbs.db").unwrap_or(false)
+}
+
+/// 自然排序：数字段按数值比较（page2 < page10），其余按不区分大小写的字符比较
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut ai, mut bi) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (ai.peek().copied(), bi.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |it: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = it.peek().copied().filter(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                        it.next();
+                    }
+                    digits
+                };
+                let (na, nb) = (take_number(&mut ai), take_number(&mut bi));
+                let (ta, tb) = (na.trim_start_matches('0'), nb.trim_start_matches('0'));
+                let ord = ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ord = x.to_lowercase().cmp(y.to_lowercase());
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                ai.next();
+                bi.next();
+            }
+        }
+    }
+}
+
+fn entry_file_type(name: &str) -> String {
+    let ext = Path::new(name).extension().and_then(|e| e.to_str()).unwrap_or("");
+    classify_extension(ext)
+}
+
+/// GET /api/preview/archive?uuid=<uuid> — 列出压缩包内的条目（按自然顺序，跳过 __MACOSX 等系统文件）
+pub async fn list_archive(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_archive(&query)?;
+
+    let response = tokio::task::spawn_blocking(move || {
+        let mut archive = open_archive(&source_path)?;
+        let mut entries = Vec::with_capacity(archive.len());
+        for i in 0..archive.len() {
+            let entry = archive.by_index_raw(i).map_err(|e| format!("读取 ZIP 文件条目失败: {}", e))?;
+            let name = entry.name().to_string();
+            if is_junk(&name) {
+                continue;
+            }
+            entries.push(ArchiveEntry {
+                file_type: if entry.is_dir() { "folder".to_string() } else { entry_file_type(&name) },
+                size: entry.size(),
+                compressed_size: entry.compressed_size(),
+                is_dir: entry.is_dir(),
+                name,
+            });
+        }
+        entries.sort_by(|a, b| natural_cmp(&a.name, &b.name));
+        let total_size = entries.iter().map(|e| e.size).sum();
+        Ok::<_, String>(ArchiveListResponse { entries, total_size })
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// GET /api/preview/archive/entry?uuid=<uuid>&entry=<包内路径>
+/// GET /api/preview/archive/entry?uuid=<uuid>&entry=<包内路径>&thumbnail=true&size=<size>&format=<jpeg|webp|avif>
+/// 流式返回单个条目的原始内容（Content-Type 按扩展名推断）；thumbnail=true 时返回图片条目的缩略图
+pub async fn get_archive_entry(req: HttpRequest, query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_archive(&query)?;
+    let entry_name = query.get("entry")
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("缺少 entry 参数"))?
+        .clone();
+
+    let size = lookup_entry(&source_path, &entry_name).await?;
+
+    if query.get("thumbnail").map(|s| s == "true" || s == "1").unwrap_or(false) {
+        return entry_thumbnail(&req, &query, source_path, entry_name, size).await;
+    }
+
+    Ok(sandboxed(stream_entry(source_path, entry_name, size)))
+}
+
+/// 给包内内容的响应加上 CSP sandbox 和 nosniff，防止压缩包里的 HTML / SVG 在 API 源下执行脚本
+fn sandboxed(response: HttpResponse) -> HttpResponse {
+    with_content_policy(response, SANDBOX_CSP)
+}
+
+/// 加上指定的 CSP 和 nosniff（禁止浏览器把条目嗅探成 HTML）
+pub(super) fn with_content_policy(mut response: HttpResponse, csp: &'static str) -> HttpResponse {
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_SECURITY_POLICY, HeaderValue::from_static(csp));
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    response
+}
+
+/// 确认条目存在且不是文件夹，返回解压后大小
+pub(super) async fn lookup_entry(source_path: &Path, entry_name: &str) -> Result<u64> {
+    let (path, name) = (source_path.to_path_buf(), entry_name.to_string());
+    let lookup = tokio::task::spawn_blocking(move || {
+        let mut archive = open_archive(&path)?;
+        let entry = match archive.by_name(&name) {
+            Ok(entry) => entry,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(format!("读取 ZIP 文件条目失败: {}", e)),
+        };
+        Ok::<_, String>(Some((entry.size(), entry.is_dir())))
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let (size, is_dir) = lookup.ok_or_else(|| actix_web::error::ErrorNotFound("压缩包内没有该条目"))?;
+    if is_dir {
+        return Err(actix_web::error::ErrorBadRequest("条目是文件夹"));
+    }
+    Ok(size)
+}
+
+/// 流式返回条目内容（Content-Type 按扩展名推断）
+/// 解压在阻塞线程里进行，通过 channel 逐块送给响应体；客户端断开后 send 失败，线程随之退出
+/// 头部里的解压后大小由文件决定、不可信：不用它当 Content-Length（分块传输），
+/// 实际字节数与之不符时中断响应，客户端能看出内容不完整
+pub(super) fn stream_entry(source_path: PathBuf, entry_name: String, size: u64) -> HttpResponse {
+    let (tx, rx) = tokio::sync::mpsc::channel::<std::io::Result<web::Bytes>>(4);
+    let name = entry_name.clone();
+    tokio::task::spawn_blocking(move || {
+        let copy = || -> std::result::Result<(), String> {
+            let mut archive = open_archive(&source_path)?;
+            let mut entry = archive.by_name(&name).map_err(|e| format!("读取 ZIP 文件条目失败: {}", e))?;
+            let mut buf = vec![0u8; CHUNK_SIZE];
+            let mut sent = 0u64;
+            loop {
+                let n = entry.read(&mut buf).map_err(|e| format!("解压失败: {}", e))?;
+                sent += n as u64;
+                if sent > size || (n == 0 && sent != size) {
+                    return Err(format!("条目大小与头部不符 (头部 {} 字节)", size));
+                }
+                if n == 0 || tx.blocking_send(Ok(web::Bytes::copy_from_slice(&buf[..n]))).is_err() {
+                    return Ok(());
+                }
+            }
+        };
+        if let Err(e) = copy() {
+            eprintln!("[archive] {} 失败: {}", name, e);
+            let _ = tx.blocking_send(Err(std::io::Error::other(e)));
+        }
+    });
+    let body_stream = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+
+    let extension = Path::new(&entry_name).extension().and_then(|e| e.to_str()).unwrap_or("");
+    HttpResponse::Ok()
+        .content_type(actix_files::file_extension_to_mime(extension))
+        .streaming(body_stream)
+}
+
+/// 条目缩略图：按 (压缩包路径, mtime, 条目名, 档位) 缓存到图片处理缓存
+async fn entry_thumbnail(
+    req: &HttpRequest,
+    query: &HashMap<String, String>,
+    source_path: PathBuf,
+    entry_name: String,
+    size: u64,
+) -> Result<HttpResponse> {
+    if entry_file_type(&entry_name) != "image" && entry_file_type(&entry_name) != "gif" {
+        return Err(actix_web::error::ErrorBadRequest("仅支持图片条目的缩略图"));
+    }
+    if size > MAX_DECODE_BYTES {
+        return Err(actix_web::error::ErrorPayloadTooLarge("条目过大，无法生成缩略图"));
+    }
+    let thumb_size = thumb_cache::bucket_size(
+        query.get("size").and_then(|s| s.parse().ok()).unwrap_or(300),
+    );
+    let format = negotiate_format(req, query);
+
+    let params = format!("zip_{}_t{}", disk_cache::hash_name(&entry_name), thumb_size);
+    let cache_file = cache_path(&source_path, &params, format.ext());
+    if let Some(data) = cache_file.as_deref().and_then(|p| CACHE.read(p)) {
+        return Ok(image_response(data));
+    }
+
+    let _permit = process_permits().acquire().await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+    if let Some(data) = cache_file.as_deref().and_then(|p| CACHE.read(p)) {
+        return Ok(image_response(data));
+    }
+
+    let data = tokio::task::spawn_blocking(move || {
+        let mut archive = open_archive(&source_path)?;
+        let mut entry = archive.by_name(&entry_name).map_err(|e| format!("读取 ZIP 文件条目失败: {}", e))?;
+        // size 来自压缩包头，不可信：不按它预分配，并且最多只读 MAX_DECODE_BYTES
+        let mut bytes = Vec::new();
+        entry.by_ref().take(MAX_DECODE_BYTES).read_to_end(&mut bytes).map_err(|e| format!("解压失败: {}", e))?;
+
+        let img = image::load_from_memory(&bytes).map_err(|e| format!("无法解码图片: {}", e))?;
+        let img = exif::apply_orientation(img, exif::orientation_from_bytes(&bytes));
+        let data = encode_thumbnail(&img.thumbnail(thumb_size, thumb_size), format).map_err(|e| e.to_string())?;
+        if let Some(ref cache_file) = cache_file {
+            CACHE.store(cache_file, &data);
+        }
+        Ok::<_, String>(data)
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(image_response(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut names = vec!["p10.jpg", "p2.jpg", "P1.jpg", "p02.png", "cover.jpg", "p1/a.jpg"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["cover.jpg", "P1.jpg", "p1/a.jpg", "p2.jpg", "p02.png", "p10.jpg"]);
+        assert!(is_junk("__MACOSX/._p1.jpg"));
+        assert!(is_junk("dir/.DS_Store"));
+        assert!(!is_junk("dir/p1.jpg"));
+    }
+
+    #[test]
+    fn test_sandboxed_headers() {
+        let response = sandboxed(HttpResponse::Ok().content_type("text/html").body("<script></script>"));
+        let csp = response.headers().get(header::CONTENT_SECURITY_POLICY).unwrap().to_str().unwrap();
+        assert!(csp.starts_with("sandbox;"));
+        assert!(csp.contains("script-src 'none'"));
+        assert_eq!(response.headers().get(header::X_CONTENT_TYPE_OPTIONS).unwrap(), "nosniff");
+    }
+}
@@ -0,0 +1,120 @@
+// BlurHash 编码（https://blurha.sh）
+// 客户端在缩略图加载前用这串 ~30 字符的字符串绘制模糊占位图
+use image::{DynamicImage, GenericImageView};
+
+const BASE83: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// 计算前先缩到这个尺寸以内，占位图只需要极低频信息
+const SAMPLE_SIZE: u32 = 32;
+
+/// 从图像计算 BlurHash；横图 4x3 分量，竖图 3x4
+/// 透明像素按白底合成，与 JPEG 缩略图的表现一致
+pub fn encode(img: &DynamicImage) -> String {
+    let (w, h) = img.dimensions();
+    let (cx, cy) = if w >= h { (4, 3) } else { (3, 4) };
+    let small = img.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE).into_rgba8();
+    let (w, h) = small.dimensions();
+
+    // 预先转到线性空间
+    let pixels: Vec<[f64; 3]> = small
+        .pixels()
+        .map(|p| {
+            let a = p[3] as f64 / 255.0;
+            let blend = |c: u8| c as f64 * a + 255.0 * (1.0 - a);
+            [srgb_to_linear(blend(p[0])), srgb_to_linear(blend(p[1])), srgb_to_linear(blend(p[2]))]
+        })
+        .collect();
+
+    let mut factors: Vec<[f64; 3]> = Vec::with_capacity(cx * cy);
+    for j in 0..cy {
+        for i in 0..cx {
+            let norm = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            let mut sum = [0.0f64; 3];
+            for y in 0..h {
+                let by = (std::f64::consts::PI * j as f64 * y as f64 / h as f64).cos();
+                for x in 0..w {
+                    let basis = by * (std::f64::consts::PI * i as f64 * x as f64 / w as f64).cos();
+                    let px = pixels[(y * w + x) as usize];
+                    for c in 0..3 {
+                        sum[c] += basis * px[c];
+                    }
+                }
+            }
+            let scale = norm / (w * h) as f64;
+            factors.push([sum[0] * scale, sum[1] * scale, sum[2] * scale]);
+        }
+    }
+
+    let mut hash = String::with_capacity(4 + 2 * factors.len());
+    push_base83(&mut hash, ((cx - 1) + (cy - 1) * 9) as u32, 1);
+
+    let ac = &factors[1..];
+    let max_value = if ac.is_empty() {
+        push_base83(&mut hash, 0, 1);
+        1.0
+    } else {
+        let actual_max = ac.iter().flat_map(|f| f.iter()).fold(0.0f64, |m, v| m.max(v.abs()));
+        let quantised = (actual_max * 166.0 - 0.5).floor().clamp(0.0, 82.0) as u32;
+        push_base83(&mut hash, quantised, 1);
+        (quantised + 1) as f64 / 166.0
+    };
+
+    let dc = factors[0];
+    let dc_value = (linear_to_srgb(dc[0]) << 16) + (linear_to_srgb(dc[1]) << 8) + linear_to_srgb(dc[2]);
+    push_base83(&mut hash, dc_value, 4);
+
+    for f in ac {
+        let q = |v: f64| (sign_pow(v / max_value, 0.5) * 9.0 + 9.5).floor().clamp(0.0, 18.0) as u32;
+        push_base83(&mut hash, q(f[0]) * 19 * 19 + q(f[1]) * 19 + q(f[2]), 2);
+    }
+    hash
+}
+
+fn push_base83(out: &mut String, value: u32, length: u32) {
+    for i in 1..=length {
+        let digit = (value / 83u32.pow(length - i)) % 83;
+        out.push(BASE83[digit as usize] as char);
+    }
+}
+
+fn srgb_to_linear(c: f64) -> f64 {
+    let v = c / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f64) -> u32 {
+    let v = v.clamp(0.0, 1.0);
+    if v <= 0.003_130_8 {
+        (v * 12.92 * 255.0 + 0.5) as u32
+    } else {
+        ((1.055 * v.powf(1.0 / 2.4) - 0.055) * 255.0 + 0.5) as u32
+    }
+}
+
+fn sign_pow(v: f64, exp: f64) -> f64 {
+    v.abs().powf(exp).copysign(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solid_color_hash() {
+        let img = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(40, 30, image::Rgb([255, 0, 0])));
+        let hash = encode(&img);
+        // 1 (尺寸) + 1 (AC 最大值) + 4 (DC) + 11 * 2 (AC)
+        assert_eq!(hash.len(), 28);
+        // 4x3 分量 → 尺寸标志 3 + 2 * 9 = 21 → 'L'
+        assert!(hash.starts_with('L'));
+        assert!(hash.bytes().all(|b| BASE83.contains(&b)));
+        // DC = 0xFF0000
+        let mut dc = String::new();
+        push_base83(&mut dc, 0xFF0000, 4);
+        assert_eq!(&hash[2..6], dc);
+    }
+}
@@ -11,12 +11,17 @@ use std::sync::{Mutex, OnceLock};
 use std::time::SystemTime;
 use tokio::io::AsyncReadExt;
 
-use super::models::CLIP_EXTENSION;
-use super::utils::{extract_clip_thumbnail, get_ffmpeg_path};
+use super::models::{AudioTrackInfo, MediaInfoResponse, DESIGN_EXTENSIONS, RAW_EXTENSIONS};
+use super::fixup;
+use super::hls_cache;
+use super::subtitles;
+use super::design::extract_design_image;
+use super::raw::raw_preview_jpeg;
+use super::utils::get_ffmpeg_path;
 
 /// 不被浏览器/AVPlayer 原生支持、需要转码播放的视频格式.
-/// 这些格式会被改造成 "on-demand HLS": 服务端不写磁盘,
-/// 每个 .ts 片段都是请求到达时临时 spawn ffmpeg 流式产出.
+/// 这些格式会被改造成 "on-demand HLS": 每个 .ts 片段都是请求到达时临时
+/// spawn ffmpeg 流式产出, 同时写入有容量上限的切片缓存 (见 hls_cache).
 const HLS_TRANSCODE_EXTENSIONS: &[&str] = &["wmv", "flv", "avi", "mkv", "webm"];
 
 /// 需要检查 faststart 的容器格式
@@ -29,19 +34,60 @@ const HEVC_CHECK_EXTENSIONS: &[&str] = &["mp4", "mov", "m4v"];
 /// 越小首帧越快、索引越长;6 秒是 Apple 推荐值的折中点.
 const HLS_SEGMENT_SECONDS: f64 = 6.0;
 
+/// ABR 码率阶梯中的一档.
+/// 按短边定义 (横屏即高度), 竖屏视频也能正确缩放; 码率用 `-maxrate` 封顶.
+struct HlsRendition {
+    name: &'static str,
+    short_side: u32,
+    max_video_kbps: u32,
+}
+
+/// 码率阶梯 (从低到高). 只输出不高于源分辨率的档位, 不做放大.
+const HLS_LADDER: &[HlsRendition] = &[
+    HlsRendition { name: "360p", short_side: 360, max_video_kbps: 800 },
+    HlsRendition { name: "720p", short_side: 720, max_video_kbps: 2800 },
+    HlsRendition { name: "1080p", short_side: 1080, max_video_kbps: 5000 },
+];
+
+/// 各档统一的音频码率 (AAC stereo)
+const HLS_AUDIO_KBPS: u32 = 128;
+
 // ============================================================================
-// HLS: duration 缓存
+// HLS: 探测缓存 (时长 + 分辨率)
 // ============================================================================
 
-/// 按 (path, mtime) 缓存 ffmpeg 探测到的视频时长,避免每次拉 m3u8 都 probe 一次.
+/// ffmpeg 探测到的视频基本信息. 分辨率探测不到时为 0.
+#[derive(Debug, Clone)]
+pub(super) struct VideoProbe {
+    pub duration: f64,
+    pub width: u32,
+    pub height: u32,
+    pub audio: Vec<MediaStream>,
+    pub subtitles: Vec<MediaStream>,
+}
+
+/// ffmpeg 探测到的一条音频 / 字幕流
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct MediaStream {
+    /// 流序号 (`-map 0:N` 中的 N)
+    pub index: u32,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub codec: String,
+    pub default: bool,
+    /// 声道布局 (`stereo` / `5.1(side)` ...), 仅音频流
+    pub channels: Option<String>,
+}
+
+/// 按 (path, mtime) 缓存 ffmpeg 探测结果,避免每次拉 m3u8 都 probe 一次.
 /// mtime 变化自动作废缓存.
-fn duration_cache() -> &'static Mutex<HashMap<PathBuf, (SystemTime, f64)>> {
-    static CACHE: OnceLock<Mutex<HashMap<PathBuf, (SystemTime, f64)>>> = OnceLock::new();
+fn probe_cache() -> &'static Mutex<HashMap<PathBuf, (SystemTime, VideoProbe)>> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, (SystemTime, VideoProbe)>>> = OnceLock::new();
     CACHE.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
-/// 用 `ffmpeg -i` 的 stderr 解析 `Duration: HH:MM:SS.cs` 拿到时长.
-fn probe_duration(file_path: &str, ffmpeg: &Path) -> Option<f64> {
+/// 用 `ffmpeg -i` 的 stderr 解析 `Duration: HH:MM:SS.cs`、首个视频流的 `WxH` 以及音频 / 字幕流.
+pub(super) fn probe_video(file_path: &str, ffmpeg: &Path) -> Option<VideoProbe> {
     let output = std::process::Command::new(ffmpeg)
         .args(["-hide_banner", "-i", file_path])
         .output()
@@ -53,29 +99,98 @@ fn probe_duration(file_path: &str, ffmpeg: &Path) -> Option<f64> {
     let m: f64 = caps[2].parse().ok()?;
     let s: f64 = caps[3].parse().ok()?;
     let cs: f64 = caps[4].parse().ok()?;
-    Some(h * 3600.0 + m * 60.0 + s + cs / 100.0)
+
+    // `Stream #0:0: Video: h264 (High), yuv420p(progressive), 1920x1080 [SAR 1:1 DAR 16:9], ...`
+    let size_re = regex::Regex::new(r"Video:[^\n]*?[ ,](\d{2,5})x(\d{2,5})[ ,\[\n]").ok()?;
+    let (width, height) = size_re
+        .captures(&stderr)
+        .and_then(|c| Some((c[1].parse().ok()?, c[2].parse().ok()?)))
+        .unwrap_or((0, 0));
+
+    Some(VideoProbe {
+        duration: h * 3600.0 + m * 60.0 + s + cs / 100.0,
+        width,
+        height,
+        audio: parse_streams(&stderr, "Audio"),
+        subtitles: parse_streams(&stderr, "Subtitle"),
+    })
 }
 
-/// 取时长,命中内存缓存就立刻返回,未命中才调 probe_duration.
-fn get_duration_cached(file_path: &Path) -> Option<f64> {
+/// 解析 `ffmpeg -i` stderr 中指定类型 (`Audio` / `Subtitle`) 的流及其 title 元数据:
+///
+/// ```text
+///   Stream #0:1(jpn): Audio: aac (LC), 48000 Hz, stereo, fltp (default)
+///     Metadata:
+///       title           : 日本語
+/// ```
+pub(super) fn parse_streams(stderr: &str, kind: &str) -> Vec<MediaStream> {
+    let pattern = format!(
+        r"Stream #0:(\d+)(?:\[0x[0-9a-fA-F]+\])?(?:\((\w+)\))?: {}: (\w+)(.*)",
+        kind
+    );
+    let (Ok(re), Ok(channels_re)) = (regex::Regex::new(&pattern), regex::Regex::new(r"Hz, ([^,]+)")) else {
+        return Vec::new();
+    };
+    let mut streams: Vec<MediaStream> = Vec::new();
+    // 当前行是否属于最近一条目标类型流的 Metadata 块
+    let mut in_stream = false;
+    for line in stderr.lines() {
+        if let Some(caps) = re.captures(line) {
+            streams.push(MediaStream {
+                index: caps[1].parse().unwrap_or(0),
+                language: caps.get(2).map(|m| m.as_str().to_string()).filter(|l| l != "und"),
+                title: None,
+                codec: caps[3].to_string(),
+                default: caps[4].contains("(default)"),
+                channels: channels_re.captures(&caps[4]).map(|c| c[1].trim().to_string()),
+            });
+            in_stream = true;
+            continue;
+        }
+        // 其他流开始, 之后的 Metadata 不属于目标流
+        if line.trim_start().starts_with("Stream #") {
+            in_stream = false;
+            continue;
+        }
+        if !in_stream {
+            continue;
+        }
+        if let Some((k, v)) = line.split_once(':') {
+            if k.trim() == "title" && !v.trim().is_empty() {
+                if let Some(s) = streams.last_mut() {
+                    s.title = Some(v.trim().to_string());
+                }
+            }
+        }
+    }
+    streams
+}
+
+/// 取探测结果,命中内存缓存就立刻返回,未命中才调 probe_video.
+pub(super) fn get_probe_cached(file_path: &Path) -> Option<VideoProbe> {
     let mtime = std::fs::metadata(file_path)
         .and_then(|m| m.modified())
         .ok()?;
     {
-        let cache = duration_cache().lock().unwrap();
-        if let Some((cached_mtime, d)) = cache.get(file_path) {
+        let cache = probe_cache().lock().unwrap();
+        if let Some((cached_mtime, p)) = cache.get(file_path) {
             if *cached_mtime == mtime {
-                return Some(*d);
+                return Some(p.clone());
             }
         }
     }
     let ffmpeg = get_ffmpeg_path();
-    let d = probe_duration(&file_path.to_string_lossy(), &ffmpeg)?;
-    duration_cache()
+    let p = probe_video(&file_path.to_string_lossy(), &ffmpeg)?;
+    probe_cache()
         .lock()
         .unwrap()
-        .insert(file_path.to_path_buf(), (mtime, d));
-    Some(d)
+        .insert(file_path.to_path_buf(), (mtime, p.clone()));
+    Some(p)
+}
+
+/// 只要时长时的便捷封装.
+pub(super) fn get_duration_cached(file_path: &Path) -> Option<f64> {
+    get_probe_cached(file_path).map(|p| p.duration)
 }
 
 /// 已知会触发探测失败的源文件黑名单,避免每次都去跑一次 ffmpeg 空跑.
@@ -90,19 +205,15 @@ fn _probe_failure_registry() -> &'static Mutex<HashSet<PathBuf>> {
 // HLS: m3u8 生成
 // ============================================================================
 
-/// 构造一个 VOD 类型的 m3u8 playlist.
+/// 转义 URL 里的 `key=` 值.
 ///
-/// 每一条 segment URL 指回 `/api/preview/content/<encoded-src>?hls_seg=N&key=...`,
-/// 这样播放器拉切片时:
-/// 1. 走的是同一个 actix 路由,复用鉴权中间件
-/// 2. URL 里带 `?key`,AVPlayer 不会因为丢 query string 而 401
-fn build_m3u8(source_path: &Path, duration: f64, key: Option<&str>) -> String {
-    use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
+/// 最小 query-value 转义集: 只转义真正会破坏 query string 结构的字符.
+/// 保留 `_` `-` `.` 等 token 友好字符不编码, 因为 auth 中间件
+/// (server/src/auth/middleware.rs) 对 `key=` 值是**不做 percent-decode**
+/// 的直接字符串比对,过度编码会往返失配导致 401.
+pub(super) fn encode_key(key: &str) -> String {
+    use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
 
-    // 最小 query-value 转义集: 只转义真正会破坏 query string 结构的字符.
-    // 保留 `_` `-` `.` 等 token 友好字符不编码, 因为 auth 中间件
-    // (server/src/auth/middleware.rs) 对 `key=` 值是**不做 percent-decode**
-    // 的直接字符串比对,过度编码会往返失配导致 401.
     const KEY_SET: &AsciiSet = &CONTROLS
         .add(b' ')
         .add(b'"')
@@ -113,16 +224,120 @@ fn build_m3u8(source_path: &Path, duration: f64, key: Option<&str>) -> String {
         .add(b'=')
         .add(b'?');
 
+    utf8_percent_encode(key, KEY_SET).to_string()
+}
+
+/// 按源分辨率挑出可用的档位, 返回 (档位, 输出宽, 输出高).
+/// 源比最低档还小时只保留最低档 (按源尺寸输出); 分辨率探测不到时按横屏假设全部输出.
+fn hls_ladder(probe: &VideoProbe) -> Vec<(&'static HlsRendition, u32, u32)> {
+    let (w, h) = (probe.width, probe.height);
+    if w == 0 || h == 0 {
+        return HLS_LADDER
+            .iter()
+            .map(|r| (r, even(r.short_side * 16 / 9), r.short_side))
+            .collect();
+    }
+    let short = w.min(h);
+    let mut ladder: Vec<_> = HLS_LADDER
+        .iter()
+        .filter(|r| r.short_side <= short)
+        .map(|r| {
+            let scale = r.short_side as f64 / short as f64;
+            (r, even((w as f64 * scale).round() as u32), even((h as f64 * scale).round() as u32))
+        })
+        .collect();
+    if ladder.is_empty() {
+        ladder.push((&HLS_LADDER[0], even(w), even(h)));
+    }
+    ladder
+}
+
+/// libx264 + yuv420p 要求宽高为偶数
+fn even(v: u32) -> u32 {
+    (v & !1).max(2)
+}
+
+fn find_rendition(probe: &VideoProbe, name: &str) -> Option<(&'static HlsRendition, u32, u32)> {
+    hls_ladder(probe).into_iter().find(|(r, _, _)| r.name == name)
+}
+
+fn audio_query(audio: Option<u32>) -> String {
+    audio.map(|a| format!("&hls_audio={}", a)).unwrap_or_default()
+}
+
+/// 解析 `?hls_audio=<流序号>` 并校验确实是该视频的音频流; 不带参数时用 ffmpeg 默认音轨
+fn selected_audio(query: &HashMap<String, String>, probe: &VideoProbe) -> Result<Option<u32>> {
+    let Some(raw) = query.get("hls_audio") else { return Ok(None) };
+    let index: u32 = raw
+        .parse()
+        .map_err(|_| actix_web::error::ErrorBadRequest("hls_audio 必须是非负整数"))?;
+    if !probe.audio.iter().any(|a| a.index == index) {
+        return Err(actix_web::error::ErrorNotFound("音轨不存在"));
+    }
+    Ok(Some(index))
+}
+
+/// 构造 master playlist: 每一档指向 `/api/preview/content/<encoded-src>.m3u8?hls_variant=<档位>`.
+/// `.m3u8` 伪后缀与切片的 `.ts` 同理, serve_file 会脱掉再按源文件处理.
+/// 有字幕轨时以 `EXT-X-MEDIA` 字幕组 `subs` 引用, 每轨指向 `...m3u8?hls_subs=<轨道 ID>`.
+/// 指定了音轨 (`hls_audio`) 时透传到各档位的 playlist URL, 切片随之换用该音轨.
+fn build_master_m3u8(source_path: &Path, probe: &VideoProbe, audio: Option<u32>, key: Option<&str>) -> String {
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
     let encoded_src =
         utf8_percent_encode(&source_path.to_string_lossy(), NON_ALPHANUMERIC).to_string();
-    let key_query = key
-        .map(|k| format!("&key={}", utf8_percent_encode(k, KEY_SET)))
-        .unwrap_or_default();
+    let key_query = key.map(|k| format!("&key={}", encode_key(k))).unwrap_or_default();
+
+    let mut m3u8 = String::from("#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-INDEPENDENT-SEGMENTS\n");
+    let subs = subtitles::hls_subtitles(source_path);
+    for sub in &subs {
+        let language = sub
+            .language
+            .as_ref()
+            .map(|l| format!(",LANGUAGE=\"{}\"", l))
+            .unwrap_or_default();
+        m3u8.push_str(&format!(
+            "#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subs\",NAME=\"{}\"{},DEFAULT={},AUTOSELECT=YES,URI=\"/api/preview/content/{}.m3u8?hls_subs={}{}\"\n",
+            sub.name.replace('"', "'"),
+            language,
+            if sub.default { "YES" } else { "NO" },
+            encoded_src,
+            sub.id,
+            key_query
+        ));
+    }
+    let subs_attr = if subs.is_empty() { "" } else { ",SUBTITLES=\"subs\"" };
+    for (r, w, h) in hls_ladder(probe) {
+        let bandwidth = (r.max_video_kbps + HLS_AUDIO_KBPS) * 1000;
+        m3u8.push_str(&format!(
+            "#EXT-X-STREAM-INF:BANDWIDTH={},RESOLUTION={}x{},NAME=\"{}\"{}\n",
+            bandwidth, w, h, r.name, subs_attr
+        ));
+        m3u8.push_str(&format!(
+            "/api/preview/content/{}.m3u8?hls_variant={}{}{}\n",
+            encoded_src, r.name, audio_query(audio), key_query
+        ));
+    }
+    m3u8
+}
+
+/// 构造一个 VOD 类型的 m3u8 media playlist.
+///
+/// 每一条 segment URL 指回 `/api/preview/content/<encoded-src>?hls_seg=N&hls_variant=...&hls_audio=...&key=...`,
+/// 这样播放器拉切片时:
+/// 1. 走的是同一个 actix 路由,复用鉴权中间件
+/// 2. URL 里带 `?key`,AVPlayer 不会因为丢 query string 而 401
+fn build_m3u8(source_path: &Path, duration: f64, variant: &str, audio: Option<u32>, key: Option<&str>) -> String {
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+    let encoded_src =
+        utf8_percent_encode(&source_path.to_string_lossy(), NON_ALPHANUMERIC).to_string();
+    let key_query = key.map(|k| format!("&key={}", encode_key(k))).unwrap_or_default();
 
     let num_segs = (duration / HLS_SEGMENT_SECONDS).ceil() as usize;
     let target_dur = HLS_SEGMENT_SECONDS.ceil() as u32;
 
-    let mut m3u8 = String::with_capacity(256 + num_segs * 96);
+    let mut m3u8 = String::with_capacity(256 + num_segs * 112);
     m3u8.push_str("#EXTM3U\n");
     m3u8.push_str("#EXT-X-VERSION:3\n");
     m3u8.push_str(&format!("#EXT-X-TARGETDURATION:{}\n", target_dur));
@@ -138,8 +353,8 @@ fn build_m3u8(source_path: &Path, duration: f64, key: Option<&str>) -> String {
         // 看到 `.wmv` 会拒绝. 拼 `.ts` 后所有客户端都能正确识别这是切片.
         // serve_file 开头会把 `.ts` 脱掉再按源文件处理.
         m3u8.push_str(&format!(
-            "/api/preview/content/{}.ts?hls_seg={}{}\n",
-            encoded_src, i, key_query
+            "/api/preview/content/{}.ts?hls_seg={}&hls_variant={}{}{}\n",
+            encoded_src, i, variant, audio_query(audio), key_query
         ));
     }
     m3u8.push_str("#EXT-X-ENDLIST\n");
@@ -147,23 +362,180 @@ fn build_m3u8(source_path: &Path, duration: f64, key: Option<&str>) -> String {
 }
 
 // ============================================================================
-// HLS: on-demand segment 流式产出
+// HLS: on-demand segment 流式产出 + 磁盘缓存 + 预读
 // ============================================================================
 
-/// 给单个切片临时 spawn 一次 ffmpeg,把 stdout 包装成 chunked HTTP body 返回.
+/// 单个切片的 ffmpeg 参数, `output` 为 `pipe:1` (流式返回) 或缓存临时文件路径.
 ///
 /// 关键点:
 /// - `-ss BEFORE -i` 快速定位(demuxer seek),自 ffmpeg 2.1 起也是帧准确的
 /// - `-force_key_frames expr:gte(t,0)` 强制每段从 IDR 开头,段间可独立解码
+/// - `-output_ts_offset` 让各段时间戳接续在整片时间轴上, WebVTT 字幕才能按 X-TIMESTAMP-MAP 对齐
 /// - `-f mpegts` → 产出原始 .ts 字节流,HLS 标准切片格式
-/// - `kill_on_drop(true)` → 客户端断开时顺带把 ffmpeg SIGKILL,避免僵尸
-async fn serve_hls_segment(source_path: &Path, seg_idx: usize) -> Result<HttpResponse> {
+/// - `rendition` 指定档位时缩放到该档分辨率并用 `-maxrate` 封顶码率;
+///   不带档位 (旧版单一 playlist 的切片 URL) 时按源分辨率输出
+/// - `audio` 指定音频流序号时显式 `-map` 首个视频流 + 该音频流, 否则用 ffmpeg 默认选流
+fn segment_ffmpeg_args(
+    source_str: &str,
+    start: f64,
+    seg_dur: f64,
+    rendition: Option<(&HlsRendition, u32, u32)>,
+    audio: Option<u32>,
+    output: &str,
+) -> Vec<String> {
+    // 封顶档位用 crf 23: 码率受 maxrate 约束, crf 只影响简单画面下省多少
+    let crf = if rendition.is_some() { "23" } else { "20" };
+    let mut args: Vec<String> = [
+        "-hide_banner",
+        "-loglevel", "warning",
+        // fast seek 到段起点 (在 -i 之前, demuxer seek, 很快)
+        "-ss", &format!("{:.3}", start),
+        "-i", source_str,
+        "-t", &format!("{:.3}", seg_dur),
+        "-output_ts_offset", &format!("{:.3}", start),
+        // 视频: H.264 veryfast + crf 20 (ABR 档位为 crf 23 + maxrate 封顶).
+        // ultrafast preset 会禁用 B 帧 / psy 优化 / in-loop deblocking, 画质肉眼可见地软;
+        // veryfast 开回这些编码工具,同等视觉质量下码率反而更低,且 640x480 上仍有 >30x 实时速度.
+        "-c:v", "libx264",
+        "-preset", "veryfast",
+        "-crf", crf,
+        "-pix_fmt", "yuv420p",
+        // 强制段首为 IDR, 保证段间可独立解码
+        "-force_key_frames", "expr:gte(t,0)",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    if let Some(index) = audio {
+        args.extend(["-map".into(), "0:v:0".into(), "-map".into(), format!("0:{}", index)]);
+    }
+    // 档位参数: 缩放 + 码率封顶 (bufsize 取 2 倍 maxrate)
+    if let Some((r, w, h)) = rendition {
+        args.extend([
+            "-vf".into(), format!("scale={}:{}", w, h),
+            "-maxrate".into(), format!("{}k", r.max_video_kbps),
+            "-bufsize".into(), format!("{}k", r.max_video_kbps * 2),
+        ]);
+    }
+    args.extend(
+        [
+            // 音频: AAC 128k stereo, 满足 Apple HLS 规范
+            "-c:a", "aac",
+            "-b:a", &format!("{}k", HLS_AUDIO_KBPS),
+            "-ac", "2",
+            "-ar", "44100",
+            // 输出: MPEG-TS
+            "-f", "mpegts",
+            "-muxdelay", "0",
+            "-muxpreload", "0",
+            "-y",
+            output,
+        ]
+        .iter()
+        .map(|s| s.to_string()),
+    );
+    args
+}
+
+/// 预读: 在后台把 seg_idx 之后的若干切片转码进缓存, 播放到那里时直接命中.
+/// 已缓存 / 正在转码 / 已排队的切片跳过, 不重复 spawn; 并发受 hls_cache::lookahead_permits 限制.
+fn schedule_lookahead(
+    source_path: &Path,
+    probe: &VideoProbe,
+    variant: Option<&str>,
+    audio: Option<u32>,
+    seg_idx: usize,
+) {
+    let num_segs = (probe.duration / HLS_SEGMENT_SECONDS).ceil() as usize;
+    let rendition = variant.and_then(|name| find_rendition(probe, name));
+    let end = (seg_idx + 1 + hls_cache::lookahead_count()).min(num_segs);
+
+    for idx in seg_idx + 1..end {
+        let Some(target) = hls_cache::segment_path(source_path, variant, audio, idx) else { return };
+        if target.exists() {
+            continue;
+        }
+        let Some(scheduled) = hls_cache::schedule(&target) else { continue };
+        let source = source_path.to_path_buf();
+        let duration = probe.duration;
+        tokio::spawn(async move {
+            let _scheduled = scheduled;
+            let Ok(_permit) = hls_cache::lookahead_permits().acquire().await else { return };
+            // 排队期间可能已被前台请求转完 / 认领
+            if target.exists() {
+                return;
+            }
+            let Ok(guard) = hls_cache::begin(&target) else { return };
+            let Some(pending) = hls_cache::PendingSegment::create(&target, guard).await else { return };
+            let Some(source_str) = source.to_str() else { return };
+
+            let start = idx as f64 * HLS_SEGMENT_SECONDS;
+            let seg_dur = (duration - start).min(HLS_SEGMENT_SECONDS);
+            let tmp = pending.tmp_path().to_string_lossy().to_string();
+            let output = tokio::process::Command::new(get_ffmpeg_path())
+                .args(segment_ffmpeg_args(source_str, start, seg_dur, rendition, audio, &tmp))
+                .stdin(std::process::Stdio::null())
+                .kill_on_drop(true)
+                .output()
+                .await;
+            match output {
+                Ok(o) if o.status.success() => pending.finish().await,
+                Ok(o) => eprintln!(
+                    "[hls-lookahead {} idx={}] ffmpeg: {}",
+                    source.display(), idx, String::from_utf8_lossy(&o.stderr)
+                ),
+                Err(e) => eprintln!("[hls-lookahead] ffmpeg spawn 失败: {}", e),
+            }
+        });
+    }
+}
+
+/// 从缓存返回切片 (NamedFile: Range / ETag)
+async fn cached_segment_response(cache_file: &Path, req: &HttpRequest) -> Result<HttpResponse> {
+    let f = NamedFile::open_async(cache_file)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("打开文件失败: {}", e)))?
+        .set_content_type("video/mp2t".parse().unwrap());
+    let mut resp = f.into_response(req);
+    resp.headers_mut().insert(
+        actix_web::http::header::CACHE_CONTROL,
+        actix_web::http::header::HeaderValue::from_static("no-cache"),
+    );
+    Ok(resp)
+}
+
+/// 返回单个切片.
+///
+/// - 缓存命中 → 直接返回文件
+/// - 其他请求 / 预读正在转同一切片 → 等它写完再读缓存
+/// - 否则临时 spawn 一次 ffmpeg, 把 stdout 包装成 chunked HTTP body 返回, 同时写入缓存临时文件,
+///   ffmpeg 成功退出后移入缓存; `kill_on_drop(true)` → 客户端断开时顺带把 ffmpeg SIGKILL,
+///   临时文件随 PendingSegment 一起删除
+///
+/// 每次请求都会顺带调度后续切片的预读.
+async fn serve_hls_segment(
+    source_path: &Path,
+    seg_idx: usize,
+    variant: Option<&str>,
+    query: &HashMap<String, String>,
+    req: &HttpRequest,
+) -> Result<HttpResponse> {
     if !source_path.exists() {
         return Err(actix_web::error::ErrorNotFound("源文件不存在"));
     }
 
-    let duration = get_duration_cached(source_path)
+    let probe = get_probe_cached(source_path)
         .ok_or_else(|| actix_web::error::ErrorInternalServerError("无法探测视频时长"))?;
+    let duration = probe.duration;
+    let audio = selected_audio(query, &probe)?;
+
+    let rendition = match variant {
+        Some(name) => Some(
+            find_rendition(&probe, name)
+                .ok_or_else(|| actix_web::error::ErrorNotFound("未知的 hls_variant"))?,
+        ),
+        None => None,
+    };
 
     let start = seg_idx as f64 * HLS_SEGMENT_SECONDS;
     if start >= duration {
@@ -171,39 +543,33 @@ async fn serve_hls_segment(source_path: &Path, seg_idx: usize) -> Result<HttpRes
     }
     let seg_dur = (duration - start).min(HLS_SEGMENT_SECONDS);
 
+    schedule_lookahead(source_path, &probe, variant, audio, seg_idx);
+
+    let cache_file = hls_cache::segment_path(source_path, variant, audio, seg_idx);
+    let mut pending = None;
+    if let Some(ref cache_file) = cache_file {
+        if hls_cache::touch(cache_file) {
+            return cached_segment_response(cache_file, req).await;
+        }
+        match hls_cache::begin(cache_file) {
+            Ok(guard) => pending = hls_cache::PendingSegment::create(cache_file, guard).await,
+            Err(rx) => {
+                hls_cache::wait(rx).await;
+                if hls_cache::touch(cache_file) {
+                    return cached_segment_response(cache_file, req).await;
+                }
+                // 对方转码失败: 下面直接实时转码, 不写缓存
+            }
+        }
+    }
+
     let ffmpeg = get_ffmpeg_path();
     let source_str = source_path
         .to_str()
         .ok_or_else(|| actix_web::error::ErrorInternalServerError("源路径含非 UTF-8"))?;
 
     let mut child = tokio::process::Command::new(&ffmpeg)
-        .args([
-            "-hide_banner",
-            "-loglevel", "warning",
-            // fast seek 到段起点 (在 -i 之前, demuxer seek, 很快)
-            "-ss", &format!("{:.3}", start),
-            "-i", source_str,
-            "-t", &format!("{:.3}", seg_dur),
-            // 视频: H.264 veryfast + crf 20.
-            // ultrafast preset 会禁用 B 帧 / psy 优化 / in-loop deblocking, 画质肉眼可见地软;
-            // veryfast 开回这些编码工具,同等视觉质量下码率反而更低,且 640x480 上仍有 >30x 实时速度.
-            "-c:v", "libx264",
-            "-preset", "veryfast",
-            "-crf", "20",
-            "-pix_fmt", "yuv420p",
-            // 强制段首为 IDR, 保证段间可独立解码
-            "-force_key_frames", "expr:gte(t,0)",
-            // 音频: AAC 128k stereo, 满足 Apple HLS 规范
-            "-c:a", "aac",
-            "-b:a", "128k",
-            "-ac", "2",
-            "-ar", "44100",
-            // 输出: MPEG-TS 流到 stdout
-            "-f", "mpegts",
-            "-muxdelay", "0",
-            "-muxpreload", "0",
-            "pipe:1",
-        ])
+        .args(segment_ffmpeg_args(source_str, start, seg_dur, rendition, audio, "pipe:1"))
         .stdin(std::process::Stdio::null())
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
@@ -236,19 +602,26 @@ async fn serve_hls_segment(source_path: &Path, seg_idx: usize) -> Result<HttpRes
 
     // 把 ffmpeg stdout 包装成 Stream<Bytes> 喂给 actix 的 streaming body.
     // unfold state 持有 child, 流结束/drop 时 child 跟着被 drop → kill_on_drop 触发.
-    let state = (stdout, child, vec![0u8; 64 * 1024]);
-    let body_stream = stream::unfold(state, |(mut stdout, mut child, mut buf)| async move {
+    // pending 同理: 没走到成功 EOF 就被 drop 时删除临时文件.
+    let state = (stdout, child, vec![0u8; 64 * 1024], pending);
+    let body_stream = stream::unfold(state, |(mut stdout, mut child, mut buf, mut pending)| async move {
         match stdout.read(&mut buf).await {
             Ok(0) => {
-                // EOF: 等 child 退出,避免僵尸进程
-                let _ = child.wait().await;
+                // EOF: 等 child 退出,避免僵尸进程; 成功退出才写入缓存
+                let ok = child.wait().await.map(|s| s.success()).unwrap_or(false);
+                if let (true, Some(p)) = (ok, pending.take()) {
+                    p.finish().await;
+                }
                 None
             }
             Ok(n) => {
+                if let Some(p) = pending.as_mut() {
+                    p.write(&buf[..n]).await;
+                }
                 let chunk = actix_web::web::Bytes::copy_from_slice(&buf[..n]);
                 Some((
                     Ok::<_, std::io::Error>(chunk),
-                    (stdout, child, buf),
+                    (stdout, child, buf, pending),
                 ))
             }
             Err(e) => {
@@ -260,17 +633,317 @@ async fn serve_hls_segment(source_path: &Path, seg_idx: usize) -> Result<HttpRes
 
     Ok(HttpResponse::Ok()
         .content_type("video/mp2t")
-        .insert_header(("Cache-Control", "no-store"))
+        .insert_header(("Cache-Control", "no-cache"))
         .streaming(body_stream))
 }
 
+// ============================================================================
+// 拖动预览: sprite sheet + WebVTT 缩略图轨
+// ============================================================================
+
+/// sprite 每行的帧数
+const SPRITE_COLUMNS: u32 = 10;
+
+/// 默认帧数 / 上限 (短视频再按 1 帧/秒 封顶, 见 sprite_params)
+const SPRITE_DEFAULT_FRAMES: u32 = 100;
+const SPRITE_MAX_FRAMES: u32 = 400;
+
+/// 单帧宽度默认值与可选范围, 高度按视频宽高比
+const SPRITE_DEFAULT_WIDTH: u32 = 160;
+const SPRITE_WIDTH_RANGE: (u32, u32) = (80, 320);
+
+/// 同时跑的 ffmpeg seek 进程数.
+/// 每帧都是 `-ss` 快速定位后只解一帧, 4K 长视频也不用整段解码.
+const SPRITE_PARALLELISM: usize = 4;
+
+/// 从 `?uuid=` 或 `?path=` 解析源文件路径
+pub(super) fn resolve_source(query: &HashMap<String, String>) -> Result<PathBuf> {
+    if let Some(uuid) = query.get("uuid") {
+        let file = crate::indexer::storage::get_file_by_uuid(uuid)
+            .map_err(|e| actix_web::error::ErrorInternalServerError(format!("数据库错误: {}", e)))?
+            .ok_or_else(|| actix_web::error::ErrorNotFound("UUID 对应的文件未找到"))?;
+        let path = file.current_path
+            .ok_or_else(|| actix_web::error::ErrorNotFound("文件已被删除或移动"))?;
+        Ok(PathBuf::from(path))
+    } else {
+        let path = query.get("path")
+            .ok_or_else(|| actix_web::error::ErrorBadRequest("缺少 path 或 uuid 参数"))?;
+        Ok(PathBuf::from(path))
+    }
+}
+
+/// 请求参数 → (帧数, 单帧宽度), 帧数按时长封顶
+fn sprite_params(query: &HashMap<String, String>, duration: f64) -> (u32, u32) {
+    let frames = query.get("frames")
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(SPRITE_DEFAULT_FRAMES)
+        .clamp(1, SPRITE_MAX_FRAMES);
+    let frames = frames.min((duration.ceil() as u32).max(1));
+    let width = query.get("width")
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(SPRITE_DEFAULT_WIDTH)
+        .clamp(SPRITE_WIDTH_RANGE.0, SPRITE_WIDTH_RANGE.1);
+    (frames, width)
+}
+
+/// 缓存文件比源文件新才算有效
+pub(super) fn is_cache_fresh(source_path: &Path, cache_path: &Path) -> bool {
+    let src_modified = std::fs::metadata(source_path).and_then(|m| m.modified()).ok();
+    let cache_modified = std::fs::metadata(cache_path).and_then(|m| m.modified()).ok();
+    matches!((src_modified, cache_modified), (Some(src_t), Some(cache_t)) if cache_t >= src_t)
+}
+
+/// 确保 sprite 已生成, 返回缓存路径.
+/// 与图片处理共用有容量上限的磁盘缓存 (按源路径 + mtime 命名, 源文件变了自然失效)
+fn ensure_sprite(source_path: &Path, duration: f64, frames: u32, width: u32) -> std::result::Result<PathBuf, String> {
+    let cache_path = super::image_proc::cache_path(source_path, &format!("sprite_{}x{}", frames, width), "jpg")
+        .ok_or("无法确定缓存路径")?;
+    if super::image_proc::CACHE.touch(&cache_path) {
+        return Ok(cache_path);
+    }
+    let cache_dir = cache_path.parent().ok_or("无法确定缓存路径")?;
+    std::fs::create_dir_all(cache_dir).map_err(|e| format!("无法创建缓存目录: {}", e))?;
+
+    // 每帧取所在区间的中点, 与 VTT 的 cue 区间一一对应
+    let interval = duration / frames as f64;
+    let times: Vec<f64> = (0..frames).map(|i| (i as f64 + 0.5) * interval).collect();
+    let ffmpeg = get_ffmpeg_path();
+    let source_str = source_path.to_str().ok_or("源路径含非 UTF-8")?;
+
+    let mut tiles: Vec<Option<image::DynamicImage>> = vec![None; frames as usize];
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..SPRITE_PARALLELISM)
+            .map(|worker| {
+                let times = &times;
+                let ffmpeg = &ffmpeg;
+                scope.spawn(move || {
+                    (worker..times.len())
+                        .step_by(SPRITE_PARALLELISM)
+                        .map(|i| (i, extract_frame_at(ffmpeg, source_str, times[i], width)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for handle in handles {
+            for (i, tile) in handle.join().unwrap_or_default() {
+                tiles[i] = tile;
+            }
+        }
+    });
+
+    let tile_h = tiles
+        .iter()
+        .flatten()
+        .next()
+        .map(|t| t.height())
+        .ok_or("ffmpeg 无法提取任何视频帧")?;
+    let rows = frames.div_ceil(SPRITE_COLUMNS);
+    let cols = frames.min(SPRITE_COLUMNS);
+    let mut sheet = image::RgbImage::new(cols * width, rows * tile_h);
+    for (i, tile) in tiles.into_iter().enumerate() {
+        // 个别帧提取失败时留黑块, 不影响整体
+        let Some(tile) = tile else { continue };
+        let tile = if tile.width() != width || tile.height() != tile_h {
+            tile.resize_exact(width, tile_h, image::imageops::FilterType::Triangle)
+        } else {
+            tile
+        };
+        let x = (i as u32 % SPRITE_COLUMNS) * width;
+        let y = (i as u32 / SPRITE_COLUMNS) * tile_h;
+        image::imageops::replace(&mut sheet, &tile.to_rgb8(), x as i64, y as i64);
+    }
+
+    // 先写临时文件再 rename, 并发请求不会读到半个文件
+    let tmp = super::image_proc::CACHE.temp_path(&cache_path);
+    if let Err(e) = sheet.save_with_format(&tmp, image::ImageFormat::Jpeg) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(format!("无法保存 sprite: {}", e));
+    }
+    if !super::image_proc::CACHE.commit(&tmp, &cache_path) {
+        return Err("无法保存 sprite".to_string());
+    }
+    Ok(cache_path)
+}
+
+/// `-ss` 快速定位后只解码一帧, 缩放到目标宽度, 以 PNG 从 stdout 读回
+fn extract_frame_at(ffmpeg: &Path, source: &str, time: f64, width: u32) -> Option<image::DynamicImage> {
+    let output = std::process::Command::new(ffmpeg)
+        .args([
+            "-hide_banner",
+            "-loglevel", "error",
+            "-ss", &format!("{:.3}", time),
+            "-i", source,
+            "-an", "-sn",
+            "-frames:v", "1",
+            "-vf", &format!("scale={}:-2", width),
+            "-f", "image2pipe",
+            "-c:v", "png",
+            "pipe:1",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return None;
+    }
+    image::load_from_memory(&output.stdout).ok()
+}
+
+/// 秒 → WebVTT 时间戳 `HH:MM:SS.mmm`
+fn format_vtt_time(seconds: f64) -> String {
+    let total_ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        total_ms / 3_600_000,
+        total_ms / 60_000 % 60,
+        total_ms / 1000 % 60,
+        total_ms % 1000
+    )
+}
+
+/// 构造 WebVTT 缩略图轨: 每个 cue 对应一个时间区间, 文本为 `<sprite URL>#xywh=x,y,w,h`
+fn build_sprite_vtt(duration: f64, frames: u32, tile_w: u32, tile_h: u32, sprite_url: &str) -> String {
+    let interval = duration / frames as f64;
+    let mut vtt = String::with_capacity(16 + frames as usize * (sprite_url.len() + 64));
+    vtt.push_str("WEBVTT\n");
+    for i in 0..frames {
+        let start = i as f64 * interval;
+        let end = if i + 1 == frames { duration } else { start + interval };
+        let x = (i % SPRITE_COLUMNS) * tile_w;
+        let y = (i / SPRITE_COLUMNS) * tile_h;
+        vtt.push_str(&format!(
+            "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+            format_vtt_time(start),
+            format_vtt_time(end),
+            sprite_url,
+            x, y, tile_w, tile_h
+        ));
+    }
+    vtt
+}
+
+/// 解析源文件 + 时长 + 生成 (或命中) sprite, 供两个端点共用
+async fn prepare_sprite(query: &HashMap<String, String>) -> Result<(PathBuf, f64, u32, u32)> {
+    let source_path = resolve_source(query)?;
+    if !source_path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let extension = source_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !super::models::VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest("仅支持视频文件"));
+    }
+
+    let query = query.clone();
+    tokio::task::spawn_blocking(move || {
+        let duration = get_duration_cached(&source_path).ok_or("无法探测视频时长")?;
+        let (frames, width) = sprite_params(&query, duration);
+        let sprite = ensure_sprite(&source_path, duration, frames, width)?;
+        Ok::<_, String>((sprite, duration, frames, width))
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+    .map_err(actix_web::error::ErrorInternalServerError)
+}
+
+/// GET /api/preview/sprite?uuid=<uuid>&frames=<n>&width=<px>
+/// GET /api/preview/sprite?path=<file_path>&frames=<n>&width=<px>
+///
+/// 返回 N 帧均匀分布的视频截图拼成的 sprite sheet (JPEG, 每行 10 帧).
+pub async fn serve_sprite(
+    query: web::Query<HashMap<String, String>>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let (sprite, _, _, _) = prepare_sprite(&query).await?;
+    named_file_response(&sprite.to_string_lossy(), &req).await
+}
+
+/// GET /api/preview/sprite.vtt?uuid=<uuid>&frames=<n>&width=<px>
+///
+/// 返回 WebVTT 缩略图轨, cue 文本指向 /api/preview/sprite 的对应区域 (`#xywh=`),
+/// 播放器拖动进度条时据此显示帧预览. 与 m3u8 一样把 `?key` 透传到 sprite URL.
+pub async fn serve_sprite_vtt(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+    let (sprite, duration, frames, width) = prepare_sprite(&query).await?;
+    let (_, sheet_h) = image::image_dimensions(&sprite)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法读取 sprite: {}", e)))?;
+    let tile_h = sheet_h / frames.div_ceil(SPRITE_COLUMNS);
+
+    let source_param = match query.get("uuid") {
+        Some(uuid) => format!("uuid={}", utf8_percent_encode(uuid, NON_ALPHANUMERIC)),
+        None => format!("path={}", utf8_percent_encode(query.get("path").map(|s| s.as_str()).unwrap_or(""), NON_ALPHANUMERIC)),
+    };
+    let key_query = query.get("key").map(|k| format!("&key={}", encode_key(k))).unwrap_or_default();
+    let sprite_url = format!(
+        "/api/preview/sprite?{}&frames={}&width={}{}",
+        source_param, frames, width, key_query
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/vtt; charset=utf-8")
+        .insert_header(("Cache-Control", "no-store"))
+        .body(build_sprite_vtt(duration, frames, width, tile_h, &sprite_url)))
+}
+
+// ============================================================================
+// 媒体信息: 音轨列表
+// ============================================================================
+
+/// GET /api/preview/media-info?uuid=<uuid>
+/// GET /api/preview/media-info?path=<file_path>
+///
+/// 返回视频时长、分辨率和音轨列表. 客户端切换音轨时在 content URL 上带 `?hls_audio=<index>`
+/// 重新加载 (会以 HLS 转码播放).
+pub async fn get_media_info(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_source(&query)?;
+    if !source_path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let extension = source_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !super::models::VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest("仅支持视频文件"));
+    }
+
+    let probe = web::block(move || get_probe_cached(&source_path))
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("ffmpeg 无法探测视频信息"))?;
+
+    let audio_tracks = probe
+        .audio
+        .iter()
+        .map(|a| AudioTrackInfo {
+            index: a.index,
+            language: a.language.clone(),
+            title: a.title.clone(),
+            codec: a.codec.clone(),
+            channels: a.channels.clone(),
+            default: a.default,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(MediaInfoResponse {
+        duration: probe.duration,
+        width: probe.width,
+        height: probe.height,
+        audio_tracks,
+    }))
+}
+
 // ============================================================================
 // 其他原有辅助: HEVC 探测 / faststart 检测
 // ============================================================================
 
 /// 检测视频文件是否为 HEVC hev1 编码。
 /// iOS AVPlayer 只支持 hvc1 tag 的 HEVC，hev1 tag（ffmpeg/非 Apple 编码器默认）会黑屏。
-fn is_hevc_hev1(file_path: &str, ffmpeg_path: &std::path::PathBuf) -> bool {
+pub(super) fn is_hevc_hev1(file_path: &str, ffmpeg_path: &std::path::PathBuf) -> bool {
     let output = std::process::Command::new(ffmpeg_path)
         .args(["-hide_banner", "-i", file_path])
         .output();
@@ -286,7 +959,7 @@ fn is_hevc_hev1(file_path: &str, ffmpeg_path: &std::path::PathBuf) -> bool {
 
 /// Check if an MP4 file has moov atom before mdat (faststart).
 /// Reads the file's top-level atoms sequentially. If mdat comes before moov, it's not faststart.
-fn is_faststart(file_path: &Path) -> bool {
+pub(super) fn is_faststart(file_path: &Path) -> bool {
     use std::io::{Read, Seek, SeekFrom};
     let Ok(mut f) = std::fs::File::open(file_path) else { return true };
     let Ok(file_size) = f.seek(SeekFrom::End(0)) else { return true };
@@ -329,10 +1002,13 @@ fn is_faststart(file_path: &Path) -> bool {
 ///
 /// 一个请求可能走以下几种路径之一:
 /// - `?uuid=<id>`           → 从 DB 查 current_path, 再走下面的文件逻辑
-/// - `?hls_seg=<n>`         → 从源文件实时转出第 n 段 .ts (mpegts 字节流)
+/// - `?hls_seg=<n>`         → 从源文件实时转出第 n 段 .ts (mpegts 字节流, `&hls_variant=` 选档位)
+/// - `?hls_variant=<档位>`  → 该档位的 media playlist
+/// - `?hls_subs=<轨道 ID>`  → 字幕轨的 media playlist
+/// - `?hls_audio=<流序号>`  → 以上 HLS 请求改用指定音轨; 非 HLS 转码格式带上它也会返回 master playlist
 /// - 扩展名 ∈ CLIP          → 提取内嵌 PNG 预览图 (缓存到 .transcoded/)
-/// - 扩展名 ∈ HLS_TRANSCODE → 返回 on-demand HLS playlist (m3u8 body, 不写磁盘)
-/// - 扩展名 ∈ FASTSTART     → 若 MP4 缺 faststart, 缓存一个 remux 版本
+/// - 扩展名 ∈ HLS_TRANSCODE → 返回 on-demand HLS master playlist (m3u8 body, 不写磁盘)
+/// - 扩展名 ∈ FASTSTART     → 有修复任务产出的 `_fixed.mp4` 则直接返回; 否则若缺 faststart, 缓存一个 remux 版本
 /// - 扩展名 ∈ HEVC          → 若是 hev1, 重封装成 hvc1 (iOS 兼容)
 /// - 其他                   → 原样走 NamedFile 静态文件服务 (Range / ETag / MIME 自动)
 pub async fn serve_file(
@@ -363,7 +1039,47 @@ pub async fn serve_file(
         let real_src = file_path
             .strip_suffix(".ts")
             .unwrap_or(&file_path);
-        return serve_hls_segment(Path::new(real_src), seg_idx).await;
+        let variant = query.get("hls_variant").map(|s| s.as_str());
+        return serve_hls_segment(Path::new(real_src), seg_idx, variant, &query, &req).await;
+    }
+
+    // 2b. ABR 档位的 media playlist: `/api/preview/content/<encoded-src>.m3u8?hls_variant=720p`
+    if let Some(variant) = query.get("hls_variant") {
+        let real_src = Path::new(file_path.strip_suffix(".m3u8").unwrap_or(&file_path));
+        if !real_src.exists() {
+            return Err(actix_web::error::ErrorNotFound("源文件不存在"));
+        }
+        let probe = get_probe_cached(real_src).ok_or_else(|| {
+            actix_web::error::ErrorInternalServerError("ffmpeg 无法探测视频时长")
+        })?;
+        if find_rendition(&probe, variant).is_none() {
+            return Err(actix_web::error::ErrorNotFound("未知的 hls_variant"));
+        }
+        let audio = selected_audio(&query, &probe)?;
+        let m3u8 = build_m3u8(real_src, probe.duration, variant, audio, query.get("key").map(|s| s.as_str()));
+        return Ok(HttpResponse::Ok()
+            .content_type("application/vnd.apple.mpegurl")
+            .insert_header(("Cache-Control", "no-store"))
+            .body(m3u8));
+    }
+
+    // 2c. 字幕轨的 media playlist: `/api/preview/content/<encoded-src>.m3u8?hls_subs=<轨道 ID>`
+    if let Some(track_id) = query.get("hls_subs") {
+        let real_src = Path::new(file_path.strip_suffix(".m3u8").unwrap_or(&file_path));
+        if !real_src.exists() {
+            return Err(actix_web::error::ErrorNotFound("源文件不存在"));
+        }
+        let probe = get_probe_cached(real_src).ok_or_else(|| {
+            actix_web::error::ErrorInternalServerError("ffmpeg 无法探测视频时长")
+        })?;
+        if !subtitles::has_track(real_src, track_id) {
+            return Err(actix_web::error::ErrorNotFound("字幕轨不存在"));
+        }
+        let m3u8 = subtitles::build_subtitle_m3u8(real_src, probe.duration, track_id, query.get("key").map(|s| s.as_str()));
+        return Ok(HttpResponse::Ok()
+            .content_type("application/vnd.apple.mpegurl")
+            .insert_header(("Cache-Control", "no-store"))
+            .body(m3u8));
     }
 
     let source_path = Path::new(&file_path);
@@ -375,8 +1091,10 @@ pub async fn serve_file(
         .unwrap_or("")
         .to_lowercase();
 
-    // 4. .clip 文件: 提取内嵌 PNG 预览图 (缓存到 .transcoded/)
-    if extension == CLIP_EXTENSION && source_path.exists() {
+    // 4. 设计源文件 (clip/psd/kra/ora/svg): 提取合成图或栅格化为 PNG;
+    //    相机 RAW (dng/cr2/nef/...): 取内嵌的最大 JPEG 预览. 都缓存到 .transcoded/
+    let is_raw = RAW_EXTENSIONS.contains(&extension.as_str());
+    if (DESIGN_EXTENSIONS.contains(&extension.as_str()) || is_raw) && source_path.exists() {
         let parent = source_path
             .parent()
             .ok_or_else(|| actix_web::error::ErrorInternalServerError("无法获取文件所在目录"))?;
@@ -385,7 +1103,8 @@ pub async fn serve_file(
             .file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("output");
-        let cache_path = transcode_dir.join(format!("{}.png", stem));
+        // 带上原扩展名，同名的 a.clip / a.psd 不会共用缓存
+        let cache_path = transcode_dir.join(format!("{}.{}.{}", stem, extension, if is_raw { "jpg" } else { "png" }));
 
         if cache_path.exists() {
             let src_modified = std::fs::metadata(source_path).and_then(|m| m.modified()).ok();
@@ -401,27 +1120,49 @@ pub async fn serve_file(
             actix_web::error::ErrorInternalServerError(format!("无法创建转码目录: {}", e))
         })?;
 
-        let img = extract_clip_thumbnail(source_path)?;
-        img.save_with_format(&cache_path, image::ImageFormat::Png)
-            .map_err(|e| {
-                actix_web::error::ErrorInternalServerError(format!("无法保存 CLIP 预览图: {}", e))
-            })?;
+        let src = source_path.to_path_buf();
+        let target = cache_path.clone();
+        tokio::task::spawn_blocking(move || {
+            if is_raw {
+                let jpeg = raw_preview_jpeg(&src)?;
+                return std::fs::write(&target, jpeg).map_err(|e| format!("无法保存预览图: {}", e));
+            }
+            let img = extract_design_image(&src).map_err(|e| e.to_string())?;
+            img.save_with_format(&target, image::ImageFormat::Png)
+                .map_err(|e| format!("无法保存预览图: {}", e))
+        })
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+        .map_err(actix_web::error::ErrorInternalServerError)?;
 
         return named_file_response(&cache_path.to_string_lossy(), &req).await;
     }
 
-    // 5. HLS 转码格式 (wmv/flv/avi/mkv/webm): 返回 on-demand m3u8 playlist
-    if HLS_TRANSCODE_EXTENSIONS.contains(&extension.as_str()) && source_path.exists() {
-        let duration = get_duration_cached(source_path).ok_or_else(|| {
+    // 5. HLS 转码格式 (wmv/flv/avi/mkv/webm): 返回 on-demand master playlist (多档位 ABR)
+    //    其他视频格式显式选择音轨 (`?hls_audio=`) 时也走 HLS, 原样直出无法切换音轨
+    let wants_audio_switch = query.contains_key("hls_audio")
+        && super::models::VIDEO_EXTENSIONS.contains(&extension.as_str());
+    if (HLS_TRANSCODE_EXTENSIONS.contains(&extension.as_str()) || wants_audio_switch)
+        && source_path.exists()
+    {
+        let probe = get_probe_cached(source_path).ok_or_else(|| {
             actix_web::error::ErrorInternalServerError("ffmpeg 无法探测视频时长")
         })?;
-        let m3u8 = build_m3u8(source_path, duration, query.get("key").map(|s| s.as_str()));
+        let audio = selected_audio(&query, &probe)?;
+        let m3u8 = build_master_m3u8(source_path, &probe, audio, query.get("key").map(|s| s.as_str()));
         return Ok(HttpResponse::Ok()
             .content_type("application/vnd.apple.mpegurl")
             .insert_header(("Cache-Control", "no-store"))
             .body(m3u8));
     }
 
+    // 5b. 修复任务 (fixup) 产出的 remux 版本: 已修好 faststart / hvc1, 直接返回, 跳过下面的逐次检测
+    if FASTSTART_CHECK_EXTENSIONS.contains(&extension.as_str()) {
+        if let Some(fixed) = fixup::fresh_fixed_path(source_path) {
+            return named_file_response(&fixed.to_string_lossy(), &req).await;
+        }
+    }
+
     // 6. MP4 faststart 检测: 若 moov 在 mdat 之后, 缓存一个 remux 过的版本
     if FASTSTART_CHECK_EXTENSIONS.contains(&extension.as_str())
         && source_path.exists()
@@ -556,3 +1297,76 @@ async fn named_file_response(path: &str, req: &HttpRequest) -> Result<HttpRespon
     });
     Ok(f.into_response(req))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(width: u32, height: u32) -> VideoProbe {
+        VideoProbe { duration: 60.0, width, height, audio: Vec::new(), subtitles: Vec::new() }
+    }
+
+    #[test]
+    fn test_hls_ladder_never_upscales() {
+        let names = |p: VideoProbe| hls_ladder(&p).iter().map(|(r, w, h)| (r.name, *w, *h)).collect::<Vec<_>>();
+
+        assert_eq!(
+            names(probe(3840, 2160)),
+            vec![("360p", 640, 360), ("720p", 1280, 720), ("1080p", 1920, 1080)]
+        );
+        // 竖屏按短边 (宽) 缩放
+        assert_eq!(names(probe(1080, 1920)), vec![("360p", 360, 640), ("720p", 720, 1280), ("1080p", 1080, 1920)]);
+        // 900p 源: 不输出 1080p
+        assert_eq!(names(probe(1600, 900)).len(), 2);
+        // 比最低档还小: 保留一档, 按源尺寸 (取偶数)
+        assert_eq!(names(probe(321, 241)), vec![("360p", 320, 240)]);
+    }
+
+    #[test]
+    fn test_parse_streams() {
+        let stderr = "\
+Input #0, matroska,webm, from 'a.mkv':
+  Duration: 00:24:00.02, start: 0.000000, bitrate: 2000 kb/s
+  Stream #0:0: Video: h264 (High), yuv420p(progressive), 1920x1080, 23.98 fps
+  Stream #0:1(jpn): Audio: aac (LC), 48000 Hz, stereo, fltp (default)
+  Stream #0:2(eng): Audio: ac3, 48000 Hz, 5.1(side), fltp, 448 kb/s
+    Metadata:
+      title           : English 5.1
+  Stream #0:3(chi): Subtitle: ass (default)
+    Metadata:
+      title           : 简体中文
+  Stream #0:4(und): Subtitle: subrip
+";
+        let audio = parse_streams(stderr, "Audio");
+        assert_eq!(audio.len(), 2);
+        assert_eq!((audio[0].index, audio[0].language.as_deref(), audio[0].title.as_deref()), (1, Some("jpn"), None));
+        assert!(audio[0].default);
+        assert_eq!(audio[0].channels.as_deref(), Some("stereo"));
+        assert_eq!((audio[1].codec.as_str(), audio[1].title.as_deref()), ("ac3", Some("English 5.1")));
+        assert_eq!(audio[1].channels.as_deref(), Some("5.1(side)"));
+
+        let subs = parse_streams(stderr, "Subtitle");
+        assert_eq!(subs.len(), 2);
+        assert_eq!((subs[0].index, subs[0].title.as_deref(), subs[0].default), (3, Some("简体中文"), true));
+        // und 视为未知语言
+        assert_eq!((subs[1].index, subs[1].language.as_deref(), subs[1].channels.as_deref()), (4, None, None));
+    }
+
+    #[test]
+    fn test_format_vtt_time() {
+        assert_eq!(format_vtt_time(0.0), "00:00:00.000");
+        assert_eq!(format_vtt_time(3723.4567), "01:02:03.457");
+        assert_eq!(format_vtt_time(-1.0), "00:00:00.000");
+    }
+
+    #[test]
+    fn test_build_sprite_vtt() {
+        // 12 帧: 第 11 帧换到第二行, 最后一个 cue 结束于视频时长
+        let vtt = build_sprite_vtt(25.0, 12, 160, 90, "/s");
+        assert!(vtt.starts_with("WEBVTT\n"));
+        assert_eq!(vtt.matches(" --> ").count(), 12);
+        assert!(vtt.contains("\n00:00:00.000 --> 00:00:02.083\n/s#xywh=0,0,160,90\n"));
+        assert!(vtt.contains("\n00:00:20.833 --> 00:00:22.917\n/s#xywh=0,90,160,90\n"));
+        assert!(vtt.ends_with("\n00:00:22.917 --> 00:00:25.000\n/s#xywh=160,90,160,90\n"));
+    }
+}
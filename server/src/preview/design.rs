@@ -0,0 +1,389 @@
+// 设计源文件预览：PSD / PSB 合成图、Krita / OpenRaster 内嵌合成图、SVG 栅格化
+// 浏览器无法直接显示这些格式，缩略图和 serve_file 的 .transcoded 缓存都从这里取位图
+use actix_web::Result;
+use image::{DynamicImage, GrayImage, RgbImage};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::models::CLIP_EXTENSION;
+use super::utils::{extract_clip_thumbnail, render_pdf_page};
+
+/// SVG 栅格化后的长边像素
+const SVG_RASTER_SIZE: f32 = 2048.0;
+
+/// PSD 合成图最大像素数，超过时改用内嵌的 JPEG 缩略图（通道平面加 RGB 输出，8 位 RGB 约 130 MB）
+const PSD_MAX_PIXELS: u64 = 32_000_000;
+
+/// PSB 规范允许的最大边长，超过视为损坏
+const PSD_MAX_DIMENSION: u32 = 300_000;
+
+/// Color Mode Data 段读取上限（索引色调色板只有 768 字节，双色调数据也很小）
+const PSD_MAX_COLOR_DATA: u64 = 64 * 1024;
+
+/// Image Resources 段读取上限（含 ICC、XMP 和 JPEG 缩略图），超过时跳过整段
+const PSD_MAX_RESOURCES: u64 = 64 * 1024 * 1024;
+
+/// Krita / OpenRaster 内合成图 PNG 的读取上限
+const MAX_MERGED_IMAGE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// 按扩展名提取设计源文件的预览位图
+pub fn extract_design_image(path: &Path) -> Result<DynamicImage> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    match extension.as_str() {
+        CLIP_EXTENSION => extract_clip_thumbnail(path),
+        "psd" | "psb" => extract_psd_composite(path),
+        "kra" | "ora" => extract_merged_image(path),
+        "svg" => rasterize_svg(path),
+        _ => Err(actix_web::error::ErrorBadRequest("不支持的设计文件格式")),
+    }
+}
+
+/// Krita (.kra) 和 OpenRaster (.ora) 都是 ZIP 包，根目录的 mergedimage.png 为全尺寸合成图；
+/// 旧版 Krita 没有合成图时退回到 preview.png / Thumbnails/thumbnail.png
+fn extract_merged_image(path: &Path) -> Result<DynamicImage> {
+    let mut archive = super::archive::open_archive(path)
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    for name in ["mergedimage.png", "preview.png", "Thumbnails/thumbnail.png"] {
+        let mut data = Vec::new();
+        let mut entry = match archive.by_name(name) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        if entry.size() > MAX_MERGED_IMAGE_BYTES {
+            return Err(actix_web::error::ErrorPayloadTooLarge(format!("{} 过大", name)));
+        }
+        entry
+            .by_ref()
+            .take(MAX_MERGED_IMAGE_BYTES)
+            .read_to_end(&mut data)
+            .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法读取 {}: {}", name, e)))?;
+        return image::load_from_memory_with_format(&data, image::ImageFormat::Png)
+            .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法解码合成图: {}", e)));
+    }
+    Err(actix_web::error::ErrorNotFound("文件中没有合成图"))
+}
+
+/// 用 MuPDF 把 SVG 渲染为位图，长边缩放到 SVG_RASTER_SIZE
+fn rasterize_svg(path: &Path) -> Result<DynamicImage> {
+    let path_str = path.to_str()
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("无效的文件路径"))?;
+
+    let doc = mupdf::Document::open(path_str)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法打开 SVG: {}", e)))?;
+    let page = doc.load_page(0)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法解析 SVG: {}", e)))?;
+    let bounds = page.bounds()
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法获取 SVG 尺寸: {}", e)))?;
+
+    let longest = (bounds.x1 - bounds.x0).max(bounds.y1 - bounds.y0);
+    let scale = if longest > 0.0 { SVG_RASTER_SIZE / longest } else { 1.0 };
+    render_pdf_page(&page, scale)
+}
+
+fn extract_psd_composite(path: &Path) -> Result<DynamicImage> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法读取 PSD 文件: {}", e)))?;
+    read_psd_composite(&mut BufReader::new(file))
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法解析 PSD: {}", e)))
+}
+
+fn read_u16<R: Read>(r: &mut R) -> std::io::Result<u16> {
+    let mut b = [0u8; 2];
+    r.read_exact(&mut b)?;
+    Ok(u16::from_be_bytes(b))
+}
+
+fn read_u32<R: Read>(r: &mut R) -> std::io::Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_be_bytes(b))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> std::io::Result<u64> {
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_be_bytes(b))
+}
+
+/// 读取长度由文件头给出的段：长度不可信，超过 max 时跳过整段并返回空数据，不按它预分配内存
+fn read_section<R: Read + Seek>(r: &mut R, len: u64, max: u64) -> std::io::Result<Vec<u8>> {
+    if len > max {
+        r.seek(SeekFrom::Current(len as i64))?;
+        return Ok(Vec::new());
+    }
+    let mut data = Vec::new();
+    r.by_ref().take(len).read_to_end(&mut data)?;
+    if (data.len() as u64) < len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(data)
+}
+
+/// PackBits 解压一行，输出恰好 len 字节
+fn unpack_bits(src: &[u8], len: usize) -> std::result::Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0;
+    while i < src.len() && out.len() < len {
+        let n = src[i] as i8;
+        i += 1;
+        if n >= 0 {
+            let count = n as usize + 1;
+            let end = (i + count).min(src.len());
+            out.extend_from_slice(&src[i..end]);
+            i = end;
+        } else if n != -128 {
+            let count = 1 - n as isize;
+            let value = *src.get(i).ok_or("RLE 数据截断")?;
+            i += 1;
+            out.extend(std::iter::repeat_n(value, count as usize));
+        }
+    }
+    if out.len() < len {
+        return Err("RLE 数据截断".to_string());
+    }
+    out.truncate(len);
+    Ok(out)
+}
+
+/// 读取 PSD / PSB 末尾的合成图（Image Data 段）
+///
+/// 文件结构：头部 26 字节 → Color Mode Data → Image Resources → Layer and Mask → Image Data；
+/// 合成图按通道平面存储，支持 8 / 16 位的灰度、双色调、索引、RGB、CMYK，额外的 alpha 通道忽略。
+/// 不支持的模式或超大画布退回到 Image Resources 里的 JPEG 缩略图（资源 1036）
+fn read_psd_composite<R: Read + Seek>(r: &mut R) -> std::result::Result<DynamicImage, String> {
+    let io = |e: std::io::Error| e.to_string();
+
+    let mut signature = [0u8; 4];
+    r.read_exact(&mut signature).map_err(io)?;
+    if &signature != b"8BPS" {
+        return Err("不是 PSD 文件".to_string());
+    }
+    let version = read_u16(r).map_err(io)?;
+    let is_psb = match version {
+        1 => false,
+        2 => true,
+        _ => return Err(format!("未知的 PSD 版本 {}", version)),
+    };
+    r.seek(SeekFrom::Current(6)).map_err(io)?;
+    let channels = read_u16(r).map_err(io)? as usize;
+    let height = read_u32(r).map_err(io)?;
+    let width = read_u32(r).map_err(io)?;
+    let depth = read_u16(r).map_err(io)?;
+    let color_mode = read_u16(r).map_err(io)?;
+
+    let color_data_len = read_u32(r).map_err(io)? as u64;
+    let color_data = read_section(r, color_data_len, PSD_MAX_COLOR_DATA).map_err(io)?;
+
+    let resources_len = read_u32(r).map_err(io)? as u64;
+    let resources = read_section(r, resources_len, PSD_MAX_RESOURCES).map_err(io)?;
+
+    let planes_needed = match color_mode {
+        1 | 8 => 1, // 灰度 / 双色调（按灰度显示）
+        2 => 1,     // 索引色
+        3 => 3,     // RGB
+        4 => 4,     // CMYK
+        _ => 0,
+    };
+    let pixels = width as u64 * height as u64;
+    let oversized = pixels > PSD_MAX_PIXELS || width > PSD_MAX_DIMENSION || height > PSD_MAX_DIMENSION;
+    if planes_needed == 0 || channels < planes_needed || !(depth == 8 || depth == 16) || pixels == 0 || oversized {
+        return psd_resource_thumbnail(&resources)
+            .ok_or_else(|| format!("不支持的 PSD 格式 (模式 {}, {} 位)", color_mode, depth));
+    }
+
+    // 跳过图层段
+    let layers_len = if is_psb { read_u64(r).map_err(io)? } else { read_u32(r).map_err(io)? as u64 };
+    r.seek(SeekFrom::Current(layers_len as i64)).map_err(io)?;
+
+    let compression = read_u16(r).map_err(io)?;
+    let (w, h) = (width as usize, height as usize);
+    let bytes_per_sample = depth as usize / 8;
+    let row_len = w * bytes_per_sample;
+
+    let mut planes: Vec<Vec<u8>> = Vec::with_capacity(planes_needed);
+    match compression {
+        0 => {
+            for _ in 0..planes_needed {
+                let mut raw = vec![0u8; row_len * h];
+                r.read_exact(&mut raw).map_err(io)?;
+                planes.push(raw);
+            }
+        }
+        1 => {
+            // 所有通道的每行压缩字节数（PSB 为 4 字节）；只读用到的通道，其余跳过
+            let mut row_sizes = Vec::with_capacity(planes_needed * h);
+            for _ in 0..planes_needed * h {
+                row_sizes.push(if is_psb { read_u32(r).map_err(io)? as usize } else { read_u16(r).map_err(io)? as usize });
+            }
+            let entry_len = if is_psb { 4 } else { 2 };
+            r.seek(SeekFrom::Current(((channels - planes_needed) * h * entry_len) as i64)).map_err(io)?;
+            // PackBits 最坏情况每 128 字节多 1 字节，更长的行必是损坏数据
+            let max_packed = row_len + row_len / 128 + 2;
+            for c in 0..planes_needed {
+                let mut plane = Vec::with_capacity(row_len * h);
+                let mut packed = Vec::new();
+                for &size in &row_sizes[c * h..(c + 1) * h] {
+                    if size > max_packed {
+                        return Err("RLE 行长度异常".to_string());
+                    }
+                    packed.resize(size, 0);
+                    r.read_exact(&mut packed).map_err(io)?;
+                    plane.extend(unpack_bits(&packed, row_len)?);
+                }
+                planes.push(plane);
+            }
+        }
+        _ => {
+            return psd_resource_thumbnail(&resources)
+                .ok_or_else(|| format!("不支持的 PSD 压缩方式 {}", compression));
+        }
+    }
+
+    // 16 位取高字节
+    if bytes_per_sample == 2 {
+        for plane in planes.iter_mut() {
+            *plane = plane.chunks_exact(2).map(|s| s[0]).collect();
+        }
+    }
+
+    let img = match color_mode {
+        1 | 8 => DynamicImage::ImageLuma8(
+            GrayImage::from_raw(width, height, planes.swap_remove(0)).ok_or("合成图尺寸不符")?,
+        ),
+        2 => {
+            // 调色板：256 个 R，再 256 个 G，再 256 个 B
+            if color_data.len() < 768 {
+                return Err("索引色 PSD 缺少调色板".to_string());
+            }
+            let mut rgb = Vec::with_capacity(w * h * 3);
+            for &i in &planes[0] {
+                let i = i as usize;
+                rgb.extend_from_slice(&[color_data[i], color_data[256 + i], color_data[512 + i]]);
+            }
+            DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, rgb).ok_or("合成图尺寸不符")?)
+        }
+        3 => {
+            let mut rgb = Vec::with_capacity(w * h * 3);
+            for ((&r, &g), &b) in planes[0].iter().zip(&planes[1]).zip(&planes[2]) {
+                rgb.extend_from_slice(&[r, g, b]);
+            }
+            DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, rgb).ok_or("合成图尺寸不符")?)
+        }
+        _ => {
+            // PSD 中 CMYK 按反相存储（255 表示无墨）
+            let mut rgb = Vec::with_capacity(w * h * 3);
+            for i in 0..w * h {
+                let k = planes[3][i] as u32;
+                for plane in &planes[..3] {
+                    rgb.push((plane[i] as u32 * k / 255) as u8);
+                }
+            }
+            DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, rgb).ok_or("合成图尺寸不符")?)
+        }
+    };
+    Ok(img)
+}
+
+/// 从 Image Resources 中取出资源 1036（JPEG 缩略图，最长边约 160px）
+fn psd_resource_thumbnail(resources: &[u8]) -> Option<DynamicImage> {
+    let mut pos = 0;
+    while pos + 12 <= resources.len() {
+        if &resources[pos..pos + 4] != b"8BIM" {
+            return None;
+        }
+        let id = u16::from_be_bytes([resources[pos + 4], resources[pos + 5]]);
+        // Pascal 字符串名称，连同长度字节补齐到偶数
+        let name_len = resources[pos + 6] as usize;
+        let mut cursor = pos + 6 + ((name_len + 2) & !1);
+        let size = u32::from_be_bytes(resources.get(cursor..cursor + 4)?.try_into().ok()?) as usize;
+        cursor += 4;
+        let data = resources.get(cursor..cursor + size)?;
+        // 28 字节的缩略图头之后是 JFIF 数据
+        if id == 1036 && data.len() > 28 {
+            return image::load_from_memory_with_format(&data[28..], image::ImageFormat::Jpeg).ok();
+        }
+        pos = cursor + ((size + 1) & !1);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+    use std::io::Cursor;
+
+    fn psd_header(channels: u16, width: u32, height: u32, mode: u16) -> Vec<u8> {
+        let mut data = b"8BPS".to_vec();
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&[0; 6]);
+        data.extend_from_slice(&channels.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&8u16.to_be_bytes());
+        data.extend_from_slice(&mode.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes()); // color mode data
+        data.extend_from_slice(&0u32.to_be_bytes()); // image resources
+        data.extend_from_slice(&4u32.to_be_bytes()); // layer and mask（内容跳过）
+        data.extend_from_slice(&[0xAA; 4]);
+        data
+    }
+
+    #[test]
+    fn test_unpack_bits() {
+        // 字面量 3 字节 + 重复 4 次 + 无操作
+        let packed = [2u8, 1, 2, 3, (-3i8) as u8, 9, 0x80];
+        assert_eq!(unpack_bits(&packed, 7).unwrap(), vec![1, 2, 3, 9, 9, 9, 9]);
+        assert!(unpack_bits(&packed, 8).is_err());
+    }
+
+    #[test]
+    fn test_read_psd_composite() {
+        // 2x1 RGB + 一个 alpha 通道，RLE 压缩
+        let mut data = psd_header(4, 2, 1, 3);
+        data.extend_from_slice(&1u16.to_be_bytes());
+        let rows: [&[u8]; 4] = [&[(-1i8) as u8, 255], &[1, 0, 128], &[(-1i8) as u8, 10], &[(-1i8) as u8, 0]];
+        for row in rows {
+            data.extend_from_slice(&(row.len() as u16).to_be_bytes());
+        }
+        for row in rows {
+            data.extend_from_slice(row);
+        }
+        let img = read_psd_composite(&mut Cursor::new(data)).unwrap();
+        assert_eq!(img.dimensions(), (2, 1));
+        let rgb = img.to_rgb8();
+        assert_eq!(rgb.get_pixel(0, 0).0, [255, 0, 10]);
+        assert_eq!(rgb.get_pixel(1, 0).0, [255, 128, 10]);
+
+        // CMYK 原始数据：C 反相 0 = 满墨 → 红色通道为 0
+        let mut data = psd_header(4, 1, 1, 4);
+        data.extend_from_slice(&0u16.to_be_bytes());
+        data.extend_from_slice(&[0, 255, 255, 255]);
+        let rgb = read_psd_composite(&mut Cursor::new(data)).unwrap().to_rgb8();
+        assert_eq!(rgb.get_pixel(0, 0).0, [0, 255, 255]);
+
+        assert!(read_psd_composite(&mut Cursor::new(b"GIF89a".to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_read_psd_untrusted_lengths() {
+        // 截断文件声称 3 GB 的 Color Mode Data：跳过后读不到后续段，报错而不是按长度分配内存
+        let mut data = psd_header(3, 1, 1, 3);
+        data.truncate(26);
+        data.extend_from_slice(&0xC000_0000u32.to_be_bytes());
+        assert!(read_psd_composite(&mut Cursor::new(data)).is_err());
+
+        // Image Resources 长度超出文件末尾
+        let mut data = psd_header(3, 1, 1, 3);
+        data.truncate(30);
+        data.extend_from_slice(&1024u32.to_be_bytes());
+        data.extend_from_slice(&[0; 16]);
+        assert!(read_psd_composite(&mut Cursor::new(data)).is_err());
+    }
+}
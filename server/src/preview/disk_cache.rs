@@ -0,0 +1,163 @@
+// 有容量上限的磁盘缓存（缩略图 / HLS 切片 / 图片处理共用）
+//
+// - 缓存目录: <app_dir>/cache/<name>/
+// - 命中时刷新缓存文件 mtime, 淘汰时按 mtime 升序删除 (近似 LRU)
+// - 容量从 app.json 读取 (单位 MB), 超出后清理到容量的 90%
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 淘汰时清理到容量的 90%，避免每写一次就触发一次全目录扫描
+const EVICT_TARGET_RATIO: f64 = 0.9;
+
+/// FNV-1a, 源路径等任意字符串 → 缓存子目录名（只需稳定, 不需要抗碰撞）
+pub fn hash_name(s: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in s.as_bytes() {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+pub struct DiskCache {
+    /// 子目录名, 同时用于日志前缀
+    name: &'static str,
+    /// app.json 中的容量配置项
+    budget_key: &'static str,
+    default_mb: u64,
+    /// 缓存总字节数（首次访问时扫描目录初始化，之后增量累加）
+    size: OnceLock<AtomicU64>,
+    evicting: AtomicBool,
+}
+
+impl DiskCache {
+    pub const fn new(name: &'static str, budget_key: &'static str, default_mb: u64) -> Self {
+        DiskCache {
+            name,
+            budget_key,
+            default_mb,
+            size: OnceLock::new(),
+            evicting: AtomicBool::new(false),
+        }
+    }
+
+    pub fn root(&self) -> PathBuf {
+        crate::static_files::app_dir().join("cache").join(self.name)
+    }
+
+    /// 容量（字节）
+    fn budget(&self) -> u64 {
+        let mb = crate::static_files::read_config_file("app.json")
+            .and_then(|data| serde_json::from_slice::<serde_json::Value>(&data).ok())
+            .and_then(|v| v.get(self.budget_key).and_then(|x| x.as_u64()))
+            .unwrap_or(self.default_mb);
+        mb.saturating_mul(1024 * 1024)
+    }
+
+    /// 读取缓存；命中时刷新 mtime 作为 LRU 访问时间
+    pub fn read(&self, path: &Path) -> Option<Vec<u8>> {
+        let data = std::fs::read(path).ok()?;
+        self.touch(path);
+        Some(data)
+    }
+
+    /// 刷新缓存文件的访问时间；文件不存在时返回 false
+    /// 用于直接以文件形式返回（需要 Range 支持）的缓存项
+    pub fn touch(&self, path: &Path) -> bool {
+        match std::fs::File::options().write(true).open(path) {
+            Ok(f) => {
+                let _ = f.set_modified(SystemTime::now());
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// 同目录下的临时文件路径（写完后用 commit 原子地 rename 到位）
+    pub fn temp_path(&self, path: &Path) -> PathBuf {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        dir.join(format!(".{}.tmp", uuid::Uuid::new_v4()))
+    }
+
+    /// 写入缓存（临时文件 + rename，保证并发读不会读到半个文件）
+    pub fn store(&self, path: &Path, data: &[u8]) {
+        let Some(dir) = path.parent() else { return };
+        if std::fs::create_dir_all(dir).is_err() {
+            return;
+        }
+        let tmp = self.temp_path(path);
+        if std::fs::write(&tmp, data).is_err() {
+            let _ = std::fs::remove_file(&tmp);
+            return;
+        }
+        self.commit(&tmp, path);
+    }
+
+    /// 把已写好的临时文件移入缓存并计入容量，超出时触发淘汰
+    pub fn commit(&self, tmp: &Path, path: &Path) -> bool {
+        let len = std::fs::metadata(tmp).map(|m| m.len()).unwrap_or(0);
+        if std::fs::rename(tmp, path).is_err() {
+            let _ = std::fs::remove_file(tmp);
+            return false;
+        }
+        let total = self.size().fetch_add(len, Ordering::Relaxed) + len;
+        let budget = self.budget();
+        if total > budget {
+            self.evict(budget);
+        }
+        true
+    }
+
+    fn size(&self) -> &AtomicU64 {
+        self.size.get_or_init(|| {
+            let total = walkdir::WalkDir::new(self.root())
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .filter_map(|e| e.metadata().ok())
+                .map(|m| m.len())
+                .sum();
+            AtomicU64::new(total)
+        })
+    }
+
+    /// 按访问时间从旧到新删除，直到总量降到容量的 90%
+    /// 临时文件（正在写入）不参与淘汰
+    fn evict(&self, budget: u64) {
+        if self.evicting.swap(true, Ordering::AcqRel) {
+            return; // 已有线程在淘汰
+        }
+
+        let mut entries: Vec<(PathBuf, u64, SystemTime)> = walkdir::WalkDir::new(self.root())
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|e| {
+                let m = e.metadata().ok()?;
+                Some((e.into_path(), m.len(), m.modified().unwrap_or(UNIX_EPOCH)))
+            })
+            .collect();
+        entries.sort_by_key(|(_, _, t)| *t);
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        let target = (budget as f64 * EVICT_TARGET_RATIO) as u64;
+        let mut removed = 0usize;
+        for (path, len, _) in entries {
+            if total <= target {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total = total.saturating_sub(len);
+                removed += 1;
+            }
+        }
+        self.size().store(total, Ordering::Relaxed);
+        if removed > 0 {
+            eprintln!("[{}-cache] evicted {} files, now {} MB", self.name, removed, total / 1024 / 1024);
+        }
+        self.evicting.store(false, Ordering::Release);
+    }
+}
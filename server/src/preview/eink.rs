@@ -0,0 +1,253 @@
+// 墨水屏渲染：按设备分辨率 / 方向输出灰阶抖动后的 PNG
+// E-ink 客户端 CPU 很弱，在设备上缩放 + 抖动既慢效果又差，交给服务端做
+use actix_web::{web, HttpResponse, Result};
+use image::{imageops::FilterType, DynamicImage, GenericImageView, GrayImage, ImageFormat, Luma};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use super::content::resolve_source;
+use super::image_proc::{cache_path, image_response, process_permits, CACHE};
+use super::models::{EBOOK_EXTENSIONS, PDF_EXTENSION};
+use super::thumbnail::render_thumbnail;
+use super::utils::render_pdf_page;
+
+/// 默认设备分辨率（6 寸 300ppi 竖屏）
+const DEFAULT_WIDTH: u32 = 1072;
+const DEFAULT_HEIGHT: u32 = 1448;
+
+const DIMENSION_RANGE: (u32, u32) = (64, 4096);
+
+/// 抖动算法
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dither {
+    None,
+    FloydSteinberg,
+    Atkinson,
+    Ordered,
+}
+
+impl Dither {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "none" => Some(Dither::None),
+            "floyd-steinberg" | "fs" => Some(Dither::FloydSteinberg),
+            "atkinson" => Some(Dither::Atkinson),
+            "ordered" | "bayer" => Some(Dither::Ordered),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Dither::None => "none",
+            Dither::FloydSteinberg => "fs",
+            Dither::Atkinson => "atkinson",
+            Dither::Ordered => "ordered",
+        }
+    }
+}
+
+/// 8x8 Bayer 矩阵（有序抖动阈值）
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// 把灰度图量化到 levels 级灰阶（2 / 4 / 16），输出值均匀分布在 0–255
+pub fn quantize(img: &GrayImage, levels: u8, dither: Dither) -> GrayImage {
+    let (w, h) = img.dimensions();
+    let (wu, hu) = (w as usize, h as usize);
+    let step = 255.0 / (levels.max(2) - 1) as f32;
+    let snap = |v: f32| (v / step).round().clamp(0.0, (levels.max(2) - 1) as f32) * step;
+
+    let mut buf: Vec<f32> = img.as_raw().iter().map(|&v| v as f32).collect();
+    // 误差扩散：(dx, dy, 权重)
+    let kernel: &[(isize, usize, f32)] = match dither {
+        Dither::FloydSteinberg => &[(1, 0, 7.0 / 16.0), (-1, 1, 3.0 / 16.0), (0, 1, 5.0 / 16.0), (1, 1, 1.0 / 16.0)],
+        // Atkinson 只扩散 6/8 的误差，高光和暗部更干净，对比更强
+        Dither::Atkinson => &[(1, 0, 0.125), (2, 0, 0.125), (-1, 1, 0.125), (0, 1, 0.125), (1, 1, 0.125), (0, 2, 0.125)],
+        _ => &[],
+    };
+
+    for y in 0..hu {
+        for x in 0..wu {
+            let i = y * wu + x;
+            let old = buf[i];
+            let new = if dither == Dither::Ordered {
+                let threshold = (BAYER_8X8[y % 8][x % 8] as f32 + 0.5) / 64.0 - 0.5;
+                snap(old + threshold * step)
+            } else {
+                snap(old)
+            };
+            buf[i] = new;
+            let err = old - new;
+            for &(dx, dy, weight) in kernel {
+                let nx = x as isize + dx;
+                let ny = y + dy;
+                if nx >= 0 && (nx as usize) < wu && ny < hu {
+                    buf[ny * wu + nx as usize] += err * weight;
+                }
+            }
+        }
+    }
+
+    GrayImage::from_fn(w, h, |x, y| Luma([buf[(y as usize) * wu + x as usize].round().clamp(0.0, 255.0) as u8]))
+}
+
+/// 以中灰为轴线性拉伸对比度
+fn adjust_contrast(img: &mut GrayImage, factor: f32) {
+    for p in img.pixels_mut() {
+        p[0] = ((p[0] as f32 - 128.0) * factor + 128.0).round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// 等比缩放（可放大）到屏幕内，居中贴到白底画布上
+fn fit_to_screen(img: &DynamicImage, width: u32, height: u32) -> GrayImage {
+    let (sw, sh) = img.dimensions();
+    let scale = (width as f64 / sw as f64).min(height as f64 / sh as f64);
+    let (tw, th) = (
+        ((sw as f64 * scale).round() as u32).clamp(1, width),
+        ((sh as f64 * scale).round() as u32).clamp(1, height),
+    );
+    // 透明部分按白底合成（墨水屏底色）
+    let rgba = img.resize_exact(tw, th, FilterType::CatmullRom).into_rgba8();
+    let mut canvas = GrayImage::from_pixel(width, height, Luma([255]));
+    let (ox, oy) = ((width - tw) / 2, (height - th) / 2);
+    for (x, y, p) in rgba.enumerate_pixels() {
+        let a = p[3] as f32 / 255.0;
+        let luma = 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32;
+        canvas.put_pixel(ox + x, oy + y, Luma([(luma * a + 255.0 * (1.0 - a)).round() as u8]));
+    }
+    canvas
+}
+
+/// 按屏幕尺寸取源图：PDF 首页按目标分辨率直接渲染、EPUB 取原始封面，
+/// 避免先渲染成缩略图再放大；其余类型走缩略图流程
+fn render_source(path: &std::path::Path, width: u32, height: u32) -> std::result::Result<DynamicImage, String> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    if extension == PDF_EXTENSION {
+        let path_str = path.to_str().ok_or("无效的文件路径")?;
+        let doc = mupdf::Document::open(path_str).map_err(|e| format!("无法打开 PDF: {}", e))?;
+        let page = doc.load_page(0).map_err(|e| format!("无法加载 PDF 页面: {}", e))?;
+        let bounds = page.bounds().map_err(|e| format!("无法获取页面尺寸: {}", e))?;
+        let (pw, ph) = (bounds.x1 - bounds.x0, bounds.y1 - bounds.y0);
+        if pw <= 0.0 || ph <= 0.0 {
+            return Err("页面尺寸无效".to_string());
+        }
+        let scale = (width as f32 / pw).min(height as f32 / ph);
+        render_pdf_page(&page, scale).map_err(|e| e.to_string())
+    } else if EBOOK_EXTENSIONS.contains(&extension.as_str()) {
+        super::epub::extract_epub_cover(path).map_err(|e| e.to_string())
+    } else {
+        render_thumbnail(path, width.max(height)).map_err(|e| e.to_string())
+    }
+}
+
+/// GET /api/preview/eink?uuid=<uuid>&width=<px>&height=<px>&orientation=<portrait|landscape>
+///     &levels=<2|4|16>&dither=<floyd-steinberg|atkinson|ordered|none>&contrast=<0.5-3.0>&sharpen=<true|false>
+/// 返回适配墨水屏的灰阶 PNG：等比缩放到设备分辨率、居中白底，量化到指定灰阶并抖动
+/// 源文件支持图片 / PDF（首页）/ 视频（首帧）等所有可生成缩略图的类型
+pub async fn get_eink(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_source(&query)?;
+    if !source_path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+
+    let dimension = |key: &str, default: u32| query.get(key)
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(default)
+        .clamp(DIMENSION_RANGE.0, DIMENSION_RANGE.1);
+    let (mut width, mut height) = (dimension("width", DEFAULT_WIDTH), dimension("height", DEFAULT_HEIGHT));
+    match query.get("orientation").map(|s| s.as_str()) {
+        Some("portrait") if width > height => std::mem::swap(&mut width, &mut height),
+        Some("landscape") if height > width => std::mem::swap(&mut width, &mut height),
+        Some("portrait") | Some("landscape") | None => {}
+        Some(_) => return Err(actix_web::error::ErrorBadRequest("orientation 只支持 portrait / landscape")),
+    }
+    let levels: u8 = match query.get("levels").map(|s| s.as_str()) {
+        None | Some("16") => 16,
+        Some("4") => 4,
+        Some("2") => 2,
+        Some(_) => return Err(actix_web::error::ErrorBadRequest("levels 只支持 2 / 4 / 16")),
+    };
+    let dither = match query.get("dither") {
+        None => Dither::FloydSteinberg,
+        Some(s) => Dither::parse(s).ok_or_else(|| actix_web::error::ErrorBadRequest("未知的 dither 算法"))?,
+    };
+    let contrast: f32 = query.get("contrast")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(1.0f32)
+        .clamp(0.5, 3.0);
+    let sharpen = query.get("sharpen").map(|s| s == "true" || s == "1").unwrap_or(false);
+
+    let params = format!(
+        "eink_{}x{}_l{}_{}_c{:.2}{}",
+        width, height, levels, dither.name(), contrast, if sharpen { "_s" } else { "" }
+    );
+    let cache_file = cache_path(&source_path, &params, "png");
+    if let Some(data) = cache_file.as_deref().and_then(|p| CACHE.read(p)) {
+        return Ok(image_response(data));
+    }
+
+    let _permit = process_permits().acquire().await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+    if let Some(data) = cache_file.as_deref().and_then(|p| CACHE.read(p)) {
+        return Ok(image_response(data));
+    }
+
+    let data = tokio::task::spawn_blocking(move || {
+        let img = render_source(&source_path, width, height)?;
+        let mut gray = fit_to_screen(&img, width, height);
+        if (contrast - 1.0).abs() > f32::EPSILON {
+            adjust_contrast(&mut gray, contrast);
+        }
+        if sharpen {
+            gray = image::imageops::unsharpen(&gray, 1.0, 2);
+        }
+        let out = quantize(&gray, levels, dither);
+
+        let mut buffer = Cursor::new(Vec::new());
+        DynamicImage::ImageLuma8(out)
+            .write_to(&mut buffer, ImageFormat::Png)
+            .map_err(|e| format!("无法编码图片: {}", e))?;
+        let data = buffer.into_inner();
+        if let Some(ref cache_file) = cache_file {
+            CACHE.store(cache_file, &data);
+        }
+        Ok::<_, String>(data)
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(image_response(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantize_levels_and_tone() {
+        // 水平渐变
+        let img = GrayImage::from_fn(64, 16, |x, _| Luma([(x * 4) as u8]));
+        let mean = |g: &GrayImage| g.as_raw().iter().map(|&v| v as f64).sum::<f64>() / g.len() as f64;
+        for dither in [Dither::None, Dither::FloydSteinberg, Dither::Atkinson, Dither::Ordered] {
+            for levels in [2u8, 4, 16] {
+                let out = quantize(&img, levels, dither);
+                let step = 255 / (levels as u32 - 1);
+                assert!(out.as_raw().iter().all(|&v| (v as u32).is_multiple_of(step)), "{:?} {}", dither, levels);
+                // 误差扩散 / 有序抖动保持整体灰度
+                if dither != Dither::None {
+                    assert!((mean(&out) - mean(&img)).abs() < 8.0, "{:?} {}", dither, levels);
+                }
+            }
+        }
+    }
+}
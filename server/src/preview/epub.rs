@@ -0,0 +1,479 @@
+// EPUB 电子书：封面提取、书脊 / 目录解析、按容器内路径返回章节 XHTML 和资源
+//
+// EPUB 就是 ZIP: META-INF/container.xml 指向 OPF, OPF 里有元数据、manifest (全部资源) 和 spine (阅读顺序)
+// 目录来自 EPUB3 的 nav 文档或 EPUB2 的 toc.ncx. 只需要其中几个标签, 用正则扫描即可, 不引入 XML 解析库
+//
+// 章节和资源走路径式 URL /api/preview/epub/{uuid}/{容器内路径}, 章节里的相对链接 (CSS / 图片 / 下一章)
+// 不用改写就能解析到同一路由下 (认证走 api_key cookie)
+// 资源保持同源 (sandbox 的不透明源下浏览器不会带 cookie, 样式和图片会加载失败), 只用 CSP 禁止脚本
+use actix_web::{web, HttpResponse, Result};
+use image::DynamicImage;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use super::archive::{lookup_entry, open_archive, stream_entry, with_content_policy};
+use super::content::resolve_source;
+use super::models::{EpubInfoResponse, EpubSpineItem, EpubTocItem, EBOOK_EXTENSIONS};
+
+type Archive = zip::ZipArchive<std::fs::File>;
+
+/// 容器内 XML 文档的读取上限
+const MAX_XML_BYTES: u64 = 16 * 1024 * 1024;
+
+/// 章节和资源的 CSP：禁止脚本和插件，带脚本的 EPUB 读不到 localStorage 里的 API Key；
+/// 章节普遍带内联样式，style-src 放开 'unsafe-inline'
+const RESOURCE_CSP: &str = "default-src 'self' data:; style-src 'self' 'unsafe-inline' data:; \
+    script-src 'none'; object-src 'none'; base-uri 'none'; form-action 'none'";
+
+/// 封面图片的读取上限
+const MAX_COVER_BYTES: u64 = 64 * 1024 * 1024;
+
+fn tag_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"<(/?)([A-Za-z_][\w:.-]*)([^>]*?)/?>").unwrap())
+}
+
+fn attr_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"([\w:.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap())
+}
+
+/// 去掉命名空间前缀（dc:title → title, opf:item → item）
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// 取标签属性值（按完整属性名匹配，如 epub:type）
+fn attr(attrs: &str, name: &str) -> Option<String> {
+    attr_regex()
+        .captures_iter(attrs)
+        .find(|c| &c[1] == name)
+        .and_then(|c| c.get(2).or(c.get(3)))
+        .map(|m| unescape(m.as_str()))
+}
+
+/// 解码常用 XML 实体
+fn unescape(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let Some(end) = rest.find(';').filter(|e| *e <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 折叠空白（目录标题里常有换行和缩进）
+fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 把文档内的相对 href 解析为容器内完整路径；保留 #锚点，外部链接返回 None
+fn resolve_href(base_file: &str, href: &str) -> Option<String> {
+    if href.contains("://") || href.starts_with("mailto:") {
+        return None;
+    }
+    let (path, fragment) = match href.split_once('#') {
+        Some((p, f)) => (p, Some(f)),
+        None => (href, None),
+    };
+    let path = percent_encoding::percent_decode_str(path).decode_utf8_lossy();
+
+    let mut parts: Vec<&str> = if path.is_empty() {
+        // 纯锚点：指向当前文档
+        base_file.split('/').collect()
+    } else if let Some(abs) = path.strip_prefix('/') {
+        abs.split('/').collect()
+    } else {
+        let mut parts: Vec<&str> = base_file.split('/').collect();
+        parts.pop();
+        parts.extend(path.split('/'));
+        parts
+    };
+    let mut normalized: Vec<&str> = Vec::with_capacity(parts.len());
+    for part in parts.drain(..) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                normalized.pop();
+            }
+            p => normalized.push(p),
+        }
+    }
+    let mut resolved = normalized.join("/");
+    if let Some(f) = fragment.filter(|f| !f.is_empty()) {
+        resolved.push('#');
+        resolved.push_str(f);
+    }
+    Some(resolved)
+}
+
+/// OPF 解析结果
+#[derive(Debug, Default)]
+struct Package {
+    title: Option<String>,
+    author: Option<String>,
+    language: Option<String>,
+    cover: Option<String>,
+    spine: Vec<EpubSpineItem>,
+    /// 目录文档路径，true 为 EPUB3 nav，false 为 NCX
+    toc: Option<(String, bool)>,
+}
+
+/// container.xml → OPF 路径
+fn parse_container(xml: &str) -> Option<String> {
+    tag_regex()
+        .captures_iter(xml)
+        .find(|c| c[1].is_empty() && local_name(&c[2]) == "rootfile")
+        .and_then(|c| attr(&c[3], "full-path"))
+}
+
+fn parse_opf(xml: &str, opf_path: &str) -> Package {
+    struct Item {
+        href: String,
+        media_type: String,
+        properties: String,
+    }
+    let mut package = Package::default();
+    let mut items: HashMap<String, Item> = HashMap::new();
+    let mut manifest_order: Vec<String> = Vec::new();
+    let mut cover_meta: Option<String> = None;
+    let mut spine_refs: Vec<(String, bool)> = Vec::new();
+    let mut ncx_id: Option<String> = None;
+
+    let mut last_end = 0;
+    let mut open_text: Option<String> = None;
+    for c in tag_regex().captures_iter(xml) {
+        let whole = c.get(0).unwrap();
+        let (closing, name, attrs) = (!c[1].is_empty(), local_name(&c[2]).to_string(), &c[3]);
+        let text = &xml[last_end..whole.start()];
+        last_end = whole.end();
+
+        if closing {
+            if open_text.as_deref() == Some(name.as_str()) {
+                let value = Some(collapse_whitespace(&unescape(text))).filter(|s| !s.is_empty());
+                match name.as_str() {
+                    "title" if package.title.is_none() => package.title = value,
+                    "creator" if package.author.is_none() => package.author = value,
+                    "language" if package.language.is_none() => package.language = value,
+                    _ => {}
+                }
+                open_text = None;
+            }
+            continue;
+        }
+        match name.as_str() {
+            "title" | "creator" | "language" => open_text = Some(name),
+            "meta" if attr(attrs, "name").as_deref() == Some("cover") => cover_meta = attr(attrs, "content"),
+            "item" => {
+                if let (Some(id), Some(href)) = (attr(attrs, "id"), attr(attrs, "href")) {
+                    let Some(href) = resolve_href(opf_path, &href) else { continue };
+                    manifest_order.push(id.clone());
+                    items.insert(id, Item {
+                        href,
+                        media_type: attr(attrs, "media-type").unwrap_or_default(),
+                        properties: attr(attrs, "properties").unwrap_or_default(),
+                    });
+                }
+            }
+            "spine" => ncx_id = attr(attrs, "toc"),
+            "itemref" => {
+                if let Some(idref) = attr(attrs, "idref") {
+                    spine_refs.push((idref, attr(attrs, "linear").as_deref() != Some("no")));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    package.spine = spine_refs
+        .into_iter()
+        .filter_map(|(id, linear)| {
+            let item = items.get(&id)?;
+            Some(EpubSpineItem { href: item.href.clone(), media_type: item.media_type.clone(), linear, id })
+        })
+        .collect();
+
+    let has_property = |item: &Item, p: &str| item.properties.split_whitespace().any(|x| x == p);
+    let is_image = |item: &Item| item.media_type.starts_with("image/");
+    let ordered = || manifest_order.iter().filter_map(|id| items.get(id).map(|item| (id, item)));
+
+    // 封面：EPUB3 cover-image 属性 > EPUB2 <meta name="cover"> > id / 文件名含 cover 的图片 > 第一张图片
+    package.cover = ordered()
+        .find(|(_, item)| has_property(item, "cover-image"))
+        .map(|(_, item)| item)
+        .or_else(|| cover_meta.as_ref().and_then(|id| items.get(id)).filter(|item| is_image(item)))
+        .or_else(|| {
+            ordered()
+                .find(|(id, item)| is_image(item) && (id.to_lowercase().contains("cover") || item.href.to_lowercase().contains("cover")))
+                .map(|(_, item)| item)
+        })
+        .or_else(|| ordered().find(|(_, item)| is_image(item)).map(|(_, item)| item))
+        .map(|item| item.href.clone());
+
+    package.toc = ordered()
+        .find(|(_, item)| has_property(item, "nav"))
+        .map(|(_, item)| (item.href.clone(), true))
+        .or_else(|| ncx_id.as_ref().and_then(|id| items.get(id)).map(|item| (item.href.clone(), false)))
+        .or_else(|| {
+            ordered()
+                .find(|(_, item)| item.media_type == "application/x-dtbncx+xml")
+                .map(|(_, item)| (item.href.clone(), false))
+        });
+
+    package
+}
+
+/// 解析目录：EPUB3 nav 文档（<nav epub:type="toc"> 下的 ol/li/a）或 EPUB2 NCX（navPoint/text/content）
+fn parse_toc(xml: &str, toc_path: &str, is_nav: bool) -> Vec<EpubTocItem> {
+    let (item_tag, title_tags, link_attr): (&str, &[&str], &str) = if is_nav {
+        ("li", &["a", "span"], "href")
+    } else {
+        ("navPoint", &["text"], "src")
+    };
+
+    let mut root: Vec<EpubTocItem> = Vec::new();
+    let mut stack: Vec<EpubTocItem> = Vec::new();
+    // nav 文档里可能还有 landmarks / page-list，只取 toc；没有标注 epub:type 时取第一个 nav
+    let mut in_toc = !is_nav;
+    let mut toc_done = false;
+    let mut title_depth = 0usize;
+    let mut title_text = String::new();
+    let mut last_end = 0;
+
+    for c in tag_regex().captures_iter(xml) {
+        let whole = c.get(0).unwrap();
+        let (closing, name, attrs) = (!c[1].is_empty(), local_name(&c[2]), &c[3]);
+        if title_depth > 0 {
+            title_text.push_str(&xml[last_end..whole.start()]);
+        }
+        last_end = whole.end();
+        let self_closing = whole.as_str().ends_with("/>");
+
+        if is_nav && name == "nav" {
+            if closing {
+                if in_toc {
+                    toc_done = true;
+                }
+                in_toc = false;
+            } else if !toc_done {
+                in_toc = attr(attrs, "epub:type").map(|t| t.split_whitespace().any(|x| x == "toc")).unwrap_or(true);
+            }
+            continue;
+        }
+        if !in_toc {
+            continue;
+        }
+
+        if name == item_tag {
+            if closing {
+                if let Some(item) = stack.pop() {
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(item),
+                        None => root.push(item),
+                    }
+                }
+            } else if !self_closing {
+                stack.push(EpubTocItem { title: String::new(), href: None, children: Vec::new() });
+            }
+        } else if title_tags.contains(&name) {
+            if closing {
+                title_depth = title_depth.saturating_sub(1);
+                if title_depth == 0 {
+                    if let Some(item) = stack.last_mut().filter(|i| i.title.is_empty()) {
+                        item.title = collapse_whitespace(&unescape(&title_text));
+                    }
+                    title_text.clear();
+                }
+            } else if !self_closing {
+                title_depth += 1;
+            }
+            if is_nav && !closing && name == "a" {
+                if let Some(item) = stack.last_mut().filter(|i| i.href.is_none()) {
+                    item.href = attr(attrs, link_attr).and_then(|h| resolve_href(toc_path, &h));
+                }
+            }
+        } else if !is_nav && !closing && name == "content" {
+            if let Some(item) = stack.last_mut().filter(|i| i.href.is_none()) {
+                item.href = attr(attrs, link_attr).and_then(|h| resolve_href(toc_path, &h));
+            }
+        }
+    }
+    root
+}
+
+fn read_entry(archive: &mut Archive, name: &str, limit: u64) -> std::result::Result<Vec<u8>, String> {
+    let entry = archive.by_name(name).map_err(|e| format!("EPUB 中缺少 {}: {}", name, e))?;
+    let mut data = Vec::new();
+    entry.take(limit).read_to_end(&mut data).map_err(|e| format!("解压 {} 失败: {}", name, e))?;
+    Ok(data)
+}
+
+fn read_xml(archive: &mut Archive, name: &str) -> std::result::Result<String, String> {
+    read_entry(archive, name, MAX_XML_BYTES).map(|d| String::from_utf8_lossy(&d).into_owned())
+}
+
+/// 打开 EPUB 并解析 OPF，返回 (容器, OPF 路径, 解析结果)
+fn open_package(path: &Path) -> std::result::Result<(Archive, Package), String> {
+    let mut archive = open_archive(path)?;
+    let container = read_xml(&mut archive, "META-INF/container.xml")?;
+    let opf_path = parse_container(&container).ok_or("container.xml 中没有 rootfile")?;
+    let opf = read_xml(&mut archive, &opf_path)?;
+    let package = parse_opf(&opf, &opf_path);
+    Ok((archive, package))
+}
+
+/// 提取 EPUB 封面（缩略图用）
+pub fn extract_epub_cover(path: &Path) -> Result<DynamicImage> {
+    let (mut archive, package) = open_package(path).map_err(actix_web::error::ErrorInternalServerError)?;
+    let cover = package.cover.ok_or_else(|| actix_web::error::ErrorNotFound("EPUB 没有封面"))?;
+    let data = read_entry(&mut archive, &cover, MAX_COVER_BYTES).map_err(actix_web::error::ErrorInternalServerError)?;
+    image::load_from_memory(&data)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法解码 EPUB 封面: {}", e)))
+}
+
+fn resolve_epub(query: &HashMap<String, String>) -> Result<PathBuf> {
+    let source_path = resolve_source(query)?;
+    if !source_path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let extension = source_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !EBOOK_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest("仅支持 EPUB 文件"));
+    }
+    Ok(source_path)
+}
+
+/// GET /api/preview/epub/info?uuid=<uuid> — 元数据、封面路径、书脊（阅读顺序）和目录
+pub async fn get_epub_info(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_epub(&query)?;
+
+    let info = tokio::task::spawn_blocking(move || {
+        let (mut archive, package) = open_package(&source_path)?;
+        let toc = match &package.toc {
+            Some((toc_path, is_nav)) => read_xml(&mut archive, toc_path)
+                .map(|xml| parse_toc(&xml, toc_path, *is_nav))
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        Ok::<_, String>(EpubInfoResponse {
+            title: package.title,
+            author: package.author,
+            language: package.language,
+            cover: package.cover,
+            spine: package.spine,
+            toc,
+        })
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(info))
+}
+
+/// GET /api/preview/epub/{uuid}/{path} — 返回 EPUB 容器内的章节 XHTML / 样式 / 图片等资源
+pub async fn serve_epub_resource(path: web::Path<(String, String)>) -> Result<HttpResponse> {
+    let (uuid, entry_name) = path.into_inner();
+    let query = HashMap::from([("uuid".to_string(), uuid)]);
+    let source_path = resolve_epub(&query)?;
+
+    let size = lookup_entry(&source_path, &entry_name).await?;
+    Ok(with_content_policy(stream_entry(source_path, entry_name, size), RESOURCE_CSP))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_package_and_nav() {
+        let container = r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+        let opf_path = parse_container(container).unwrap();
+        assert_eq!(opf_path, "OEBPS/content.opf");
+
+        let opf = r#"<package><metadata>
+            <dc:title>Tom &amp; Jerry</dc:title><dc:creator opf:role="aut">Someone</dc:creator><dc:language>en</dc:language>
+            <meta name="cover" content="img1"/></metadata>
+            <manifest>
+              <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+              <item id="img1" href="images/Cover%20Art.jpg" media-type="image/jpeg"/>
+              <item id="c1" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+              <item id="notes" href="text/notes.xhtml" media-type="application/xhtml+xml"/>
+            </manifest>
+            <spine toc="ncx"><itemref idref="c1"/><itemref idref="notes" linear="no"/></spine></package>"#;
+        let package = parse_opf(opf, &opf_path);
+        assert_eq!(package.title.as_deref(), Some("Tom & Jerry"));
+        assert_eq!(package.author.as_deref(), Some("Someone"));
+        assert_eq!(package.cover.as_deref(), Some("OEBPS/images/Cover Art.jpg"));
+        assert_eq!(package.spine.len(), 2);
+        assert_eq!(package.spine[0].href, "OEBPS/text/ch1.xhtml");
+        assert!(!package.spine[1].linear);
+        assert_eq!(package.toc, Some(("OEBPS/nav.xhtml".to_string(), true)));
+
+        let nav = r#"<html><body>
+            <nav epub:type="toc"><ol>
+              <li><a href="text/ch1.xhtml">Chapter
+                 <b>One</b></a>
+                <ol><li><a href="text/ch1.xhtml#s2">Part 2</a></li></ol></li>
+              <li><span>Appendix</span></li>
+            </ol></nav>
+            <nav epub:type="landmarks"><ol><li><a href="text/ch1.xhtml">Start</a></li></ol></nav>
+            </body></html>"#;
+        let toc = parse_toc(nav, "OEBPS/nav.xhtml", true);
+        assert_eq!(toc.len(), 2);
+        assert_eq!(toc[0].title, "Chapter One");
+        assert_eq!(toc[0].href.as_deref(), Some("OEBPS/text/ch1.xhtml"));
+        assert_eq!(toc[0].children[0].href.as_deref(), Some("OEBPS/text/ch1.xhtml#s2"));
+        assert_eq!(toc[1].title, "Appendix");
+        assert_eq!(toc[1].href, None);
+
+        let ncx = r#"<ncx><navMap><navPoint id="n1"><navLabel><text>Intro</text></navLabel><content src="../text/ch1.xhtml"/>
+            <navPoint id="n2"><navLabel><text>Sub</text></navLabel><content src="../text/ch1.xhtml#a"/></navPoint></navPoint></navMap></ncx>"#;
+        let toc = parse_toc(ncx, "OEBPS/toc/toc.ncx", false);
+        assert_eq!(toc[0].title, "Intro");
+        assert_eq!(toc[0].href.as_deref(), Some("OEBPS/text/ch1.xhtml"));
+        assert_eq!(toc[0].children[0].title, "Sub");
+    }
+}
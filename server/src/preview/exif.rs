@@ -0,0 +1,160 @@
+// EXIF 方向（Orientation, tag 0x0112）读取与校正
+// image 库解码时不会应用 EXIF 方向，手机竖拍的照片会横躺着显示
+// 支持 JPEG (APP1)、PNG (eXIf 块)、WebP (EXIF 块)；其他格式按正常方向处理
+use image::DynamicImage;
+use std::io::Read;
+use std::path::Path;
+
+/// EXIF 一般在文件开头，只读前 256KB
+const HEAD_LIMIT: u64 = 256 * 1024;
+
+/// 读取文件的 EXIF 方向（1–8），读不到时返回 1
+pub fn read_orientation(path: &Path) -> u16 {
+    let mut head = Vec::new();
+    let Ok(f) = std::fs::File::open(path) else { return 1 };
+    if f.take(HEAD_LIMIT).read_to_end(&mut head).is_err() {
+        return 1;
+    }
+    orientation_from_bytes(&head)
+}
+
+/// 从内存中的图片数据读取 EXIF 方向（压缩包条目等没有落盘的图片）
+pub fn orientation_from_bytes(data: &[u8]) -> u16 {
+    find_tiff(data)
+        .and_then(parse_orientation)
+        .filter(|o| (1..=8).contains(o))
+        .unwrap_or(1)
+}
+
+/// 按 EXIF 方向旋转 / 翻转，得到正常朝向的图像
+pub fn apply_orientation(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// 在容器中定位 TIFF 结构（EXIF 数据本体）
+pub(super) fn find_tiff(data: &[u8]) -> Option<&[u8]> {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        // JPEG: 逐个段扫描，找 APP1 "Exif\0\0"
+        let mut pos = 2;
+        while pos + 4 <= data.len() && data[pos] == 0xFF {
+            let marker = data[pos + 1];
+            // SOS 之后是图像数据，不会再有 EXIF
+            if marker == 0xDA {
+                break;
+            }
+            let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+            let body = data.get(pos + 4..pos + 2 + len)?;
+            if marker == 0xE1 && body.starts_with(b"Exif\0\0") {
+                return Some(&body[6..]);
+            }
+            pos += 2 + len;
+        }
+        None
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // PNG: 长度(4) + 类型(4) + 数据 + CRC(4)
+        let mut pos = 8;
+        while pos + 8 <= data.len() {
+            let len = u32::from_be_bytes(data[pos..pos + 4].try_into().ok()?) as usize;
+            let kind = &data[pos + 4..pos + 8];
+            if kind == b"eXIf" {
+                return data.get(pos + 8..pos + 8 + len);
+            }
+            if kind == b"IDAT" || kind == b"IEND" {
+                break;
+            }
+            pos += 12 + len;
+        }
+        None
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        // WebP: RIFF 子块，长度为小端序，奇数长度补齐
+        let mut pos = 12;
+        while pos + 8 <= data.len() {
+            let len = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+            if &data[pos..pos + 4] == b"EXIF" {
+                let body = data.get(pos + 8..pos + 8 + len)?;
+                // 部分编码器会把 "Exif\0\0" 前缀也写进去
+                return Some(body.strip_prefix(b"Exif\0\0").unwrap_or(body));
+            }
+            pos += 8 + len + (len & 1);
+        }
+        None
+    } else {
+        None
+    }
+}
+
+/// 解析 TIFF 头 + IFD0，取 Orientation
+fn parse_orientation(tiff: &[u8]) -> Option<u16> {
+    let little = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |p: usize| -> Option<u16> {
+        let b: [u8; 2] = tiff.get(p..p + 2)?.try_into().ok()?;
+        Some(if little { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) })
+    };
+    let u32_at = |p: usize| -> Option<u32> {
+        let b: [u8; 4] = tiff.get(p..p + 4)?.try_into().ok()?;
+        Some(if little { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let count = u16_at(ifd)? as usize;
+    for i in 0..count {
+        let entry = ifd + 2 + i * 12;
+        if u16_at(entry)? == 0x0112 {
+            // SHORT 类型，值直接存放在偏移字段的前 2 字节
+            return u16_at(entry + 8);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造只含 Orientation 一项的 TIFF
+    fn tiff(little: bool, orientation: u16) -> Vec<u8> {
+        let w16 = |v: u16| if little { v.to_le_bytes() } else { v.to_be_bytes() };
+        let w32 = |v: u32| if little { v.to_le_bytes() } else { v.to_be_bytes() };
+        let mut t = Vec::new();
+        t.extend_from_slice(if little { b"II" } else { b"MM" });
+        t.extend_from_slice(&w16(42));
+        t.extend_from_slice(&w32(8));
+        t.extend_from_slice(&w16(1));
+        t.extend_from_slice(&w16(0x0112));
+        t.extend_from_slice(&w16(3));
+        t.extend_from_slice(&w32(1));
+        t.extend_from_slice(&w16(orientation));
+        t.extend_from_slice(&[0, 0]);
+        t.extend_from_slice(&w32(0));
+        t
+    }
+
+    #[test]
+    fn test_orientation_from_jpeg_app1() {
+        for (little, orientation) in [(true, 6), (false, 8)] {
+            let exif = [b"Exif\0\0".as_slice(), &tiff(little, orientation)].concat();
+            let mut jpeg = vec![0xFF, 0xD8];
+            // 前面放一个无关的 APP0 段
+            jpeg.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00]);
+            jpeg.extend_from_slice(&[0xFF, 0xE1]);
+            jpeg.extend_from_slice(&((exif.len() + 2) as u16).to_be_bytes());
+            jpeg.extend_from_slice(&exif);
+            jpeg.extend_from_slice(&[0xFF, 0xDA]);
+            assert_eq!(find_tiff(&jpeg).and_then(parse_orientation), Some(orientation));
+        }
+        assert_eq!(find_tiff(&[0xFF, 0xD8, 0xFF, 0xDA]), None);
+    }
+}
@@ -43,8 +43,12 @@ pub async fn get_files(query: web::Query<std::collections::HashMap<String, Strin
                         FileType::Audio
                     } else if extension == PDF_EXTENSION {
                         FileType::Pdf
-                    } else if extension == CLIP_EXTENSION {
-                        // CLIP 文件内嵌 SQLite 预览图，归为图片类型
+                    } else if EBOOK_EXTENSIONS.contains(&extension.as_str()) {
+                        FileType::Ebook
+                    } else if DOCUMENT_EXTENSIONS.contains(&extension.as_str()) {
+                        FileType::Document
+                    } else if DESIGN_EXTENSIONS.contains(&extension.as_str()) || RAW_EXTENSIONS.contains(&extension.as_str()) {
+                        // 设计源文件 / 相机 RAW 预览取内嵌合成图或预览图，归为图片类型
                         FileType::Image
                     } else {
                         FileType::Other
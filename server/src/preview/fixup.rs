@@ -0,0 +1,240 @@
+// 问题 MP4 的后台修复任务
+//
+// 非 faststart (moov 在 mdat 之后) 或 HEVC hev1 tag 的 MP4, serve_file 每次播放都要先检测再按需重封装.
+// 这里对整个源文件夹批量无损 remux 一次 (-c copy -movflags +faststart [-tag:v hvc1]):
+// - in_place: 写同目录临时文件, 校验时长后原子替换原文件; 保留原 mtime, 索引 UUID 不变, .mov 仍封装为 MOV
+// - 否则写入 .transcoded/<stem>_<ext>_fixed.mp4, serve_file 有此文件时直接返回, 不再逐次检测
+use actix_web::{web, HttpResponse, Result};
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+use super::content::{is_cache_fresh, is_faststart, is_hevc_hev1, probe_video};
+use super::models::{FixupFailure, FixupRequest, FixupResult, FixupStatus};
+use super::utils::get_ffmpeg_path;
+use crate::indexer::models::IndexedFile;
+
+/// 需要检查的容器格式（与 serve_file 的 faststart / hvc1 检测一致）
+const FIXUP_EXTENSIONS: &[&str] = &["mp4", "mov", "m4v"];
+
+/// remux 前后时长允许的误差（秒），超出视为输出不完整，不替换原文件
+const DURATION_TOLERANCE: f64 = 1.0;
+
+fn status() -> &'static RwLock<FixupStatus> {
+    static STATUS: OnceLock<RwLock<FixupStatus>> = OnceLock::new();
+    STATUS.get_or_init(|| RwLock::new(FixupStatus::default()))
+}
+
+/// 缓存模式的输出路径（带上扩展名，同目录下的 a.mp4 和 a.mov 不会互相覆盖）
+pub(super) fn fixed_cache_path(source_path: &Path) -> Option<PathBuf> {
+    let stem = source_path.file_stem()?.to_str()?;
+    let ext = source_path.extension()?.to_str()?.to_lowercase();
+    Some(source_path.parent()?.join(".transcoded").join(format!("{}_{}_fixed.mp4", stem, ext)))
+}
+
+/// 按检测结果决定要做的修复；空列表表示无需处理
+fn plan_fixes(faststart: bool, hev1: bool) -> Vec<String> {
+    let mut fixes = Vec::new();
+    if !faststart {
+        fixes.push("faststart".to_string());
+    }
+    if hev1 {
+        fixes.push("hvc1".to_string());
+    }
+    fixes
+}
+
+/// 输出容器：原地替换时 .mov 保持 MOV，其余（含缓存输出）写 MP4
+fn output_format(source_path: &Path, in_place: bool) -> &'static str {
+    let is_mov = source_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("mov"));
+    if in_place && is_mov { "mov" } else { "mp4" }
+}
+
+/// 修复任务产出的缓存（存在且比源文件新）
+pub(super) fn fresh_fixed_path(source_path: &Path) -> Option<PathBuf> {
+    fixed_cache_path(source_path).filter(|p| is_cache_fresh(source_path, p))
+}
+
+/// 检测并修复单个文件；无需修复时返回 None
+fn fix_file(file: &IndexedFile, in_place: bool) -> std::result::Result<Option<FixupResult>, String> {
+    let path_str = file.current_path.as_deref().ok_or("文件已被删除或移动")?;
+    let path = Path::new(path_str);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    if !in_place && fresh_fixed_path(path).is_some() {
+        return Ok(None);
+    }
+
+    let ffmpeg = get_ffmpeg_path();
+    let hev1 = is_hevc_hev1(path_str, &ffmpeg);
+    let fixes = plan_fixes(is_faststart(path), hev1);
+    if fixes.is_empty() {
+        return Ok(None);
+    }
+
+    let metadata = std::fs::metadata(path).map_err(|e| format!("无法读取文件信息: {}", e))?;
+    let transcode_dir = path.parent().ok_or("无法获取文件所在目录")?.join(".transcoded");
+    let output = if in_place {
+        let name = path.file_name().and_then(|n| n.to_str()).ok_or("文件名含非 UTF-8")?;
+        path.with_file_name(format!(".{}.fixup", name))
+    } else {
+        std::fs::create_dir_all(&transcode_dir).map_err(|e| format!("无法创建转码目录: {}", e))?;
+        fixed_cache_path(path).ok_or("无法确定缓存路径")?
+    };
+    let output_str = output.to_str().ok_or("输出路径含非 UTF-8")?;
+
+    // -map 0 保留全部音轨 / 字幕轨, 原地替换时不能丢流
+    let mut args = vec!["-hide_banner", "-loglevel", "error", "-i", path_str, "-map", "0", "-map_metadata", "0", "-c", "copy"];
+    if hev1 {
+        args.extend(["-tag:v", "hvc1"]);
+    }
+    args.extend(["-movflags", "+faststart", "-f", output_format(path, in_place), "-y", output_str]);
+
+    let result = std::process::Command::new(&ffmpeg)
+        .args(&args)
+        .output()
+        .map_err(|e| format!("ffmpeg 启动失败: {}", e));
+    let verified = result.and_then(|o| {
+        if !o.status.success() {
+            return Err(format!("ffmpeg 重封装失败: {}", String::from_utf8_lossy(&o.stderr).trim()));
+        }
+        let before = probe_video(path_str, &ffmpeg).map(|p| p.duration);
+        let after = probe_video(output_str, &ffmpeg).map(|p| p.duration);
+        match (before, after) {
+            (Some(b), Some(a)) if (a - b).abs() <= DURATION_TOLERANCE => Ok(()),
+            _ => Err(format!("重封装结果校验失败 (时长 {:?} → {:?})", before, after)),
+        }
+    });
+    if let Err(e) = verified {
+        let _ = std::fs::remove_file(&output);
+        return Err(e);
+    }
+
+    if in_place {
+        // 保留原 mtime 和权限: 媒体内容未变, 缩略图 / 切片等按 mtime 命名的缓存继续有效, 扫描也不会视为变更
+        let replace = || -> std::io::Result<u64> {
+            let f = std::fs::File::options().write(true).open(&output)?;
+            if let Ok(mtime) = metadata.modified() {
+                f.set_modified(mtime)?;
+            }
+            drop(f);
+            std::fs::set_permissions(&output, metadata.permissions())?;
+            std::fs::rename(&output, path)?;
+            Ok(std::fs::metadata(path)?.len())
+        };
+        let new_size = replace().map_err(|e| {
+            let _ = std::fs::remove_file(&output);
+            format!("替换原文件失败: {}", e)
+        })?;
+        let _ = crate::indexer::storage::set_file_size(&file.uuid, new_size as i64);
+
+        // 播放时按需生成的重封装副本已无用 (mtime 未变, 不清掉会被当作有效缓存继续返回)
+        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+            for name in [format!("{}_faststart.mp4", stem), format!("{}_hvc1.mp4", stem)] {
+                let _ = std::fs::remove_file(transcode_dir.join(name));
+            }
+        }
+        if let Some(fixed) = fixed_cache_path(path) {
+            let _ = std::fs::remove_file(fixed);
+        }
+    }
+
+    Ok(Some(FixupResult {
+        uuid: file.uuid.clone(),
+        path: path_str.to_string(),
+        fixes,
+        output: if in_place { path_str.to_string() } else { output_str.to_string() },
+    }))
+}
+
+/// POST /api/preview/fixup — 后台修复源文件夹下的问题 MP4
+/// 参数 in_place=true 时原地替换原文件，否则写入 .transcoded/ 缓存
+pub async fn start_fixup(req: web::Json<FixupRequest>) -> Result<HttpResponse> {
+    let source_folder = req.source_folder.clone();
+    let in_place = req.in_place;
+
+    // 检查是否正在运行，并标记开始
+    {
+        let mut status = status().write().unwrap();
+        if status.is_running {
+            return Ok(HttpResponse::Ok().json(serde_json::json!({
+                "status": "already_running",
+                "processed": status.processed,
+                "total": status.total,
+            })));
+        }
+        *status = FixupStatus { is_running: true, in_place, ..Default::default() };
+    }
+
+    tokio::spawn(async move {
+        let result = tokio::task::spawn_blocking(move || {
+            let files = crate::indexer::storage::get_files_by_extensions(&source_folder, FIXUP_EXTENSIONS)
+                .map_err(|e| format!("数据库错误: {}", e))?;
+            status().write().unwrap().total = files.len() as u64;
+
+            for file in &files {
+                let outcome = fix_file(file, in_place);
+                let mut status = status().write().unwrap();
+                status.processed += 1;
+                match outcome {
+                    Ok(Some(fixed)) => {
+                        eprintln!("[fixup] {} ({})", fixed.path, fixed.fixes.join(", "));
+                        status.changed.push(fixed);
+                    }
+                    Ok(None) => {}
+                    Err(error) => {
+                        let path = file.current_path.clone().unwrap_or_default();
+                        eprintln!("[fixup] {} 失败: {}", path, error);
+                        status.failed.push(FixupFailure { path, error });
+                    }
+                }
+            }
+            Ok::<_, String>(())
+        }).await;
+
+        match result {
+            Ok(Err(e)) => eprintln!("修复任务失败: {}", e),
+            Err(e) => eprintln!("修复任务 panic: {}", e),
+            _ => {}
+        }
+        status().write().unwrap().is_running = false;
+    });
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "started"
+    })))
+}
+
+/// GET /api/preview/fixup/status — 返回修复进度和已修改的文件
+pub async fn fixup_status() -> Result<HttpResponse> {
+    let status = status().read().unwrap();
+    Ok(HttpResponse::Ok().json(&*status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_fixes() {
+        assert!(plan_fixes(true, false).is_empty());
+        assert_eq!(plan_fixes(false, false), ["faststart"]);
+        assert_eq!(plan_fixes(true, true), ["hvc1"]);
+        assert_eq!(plan_fixes(false, true), ["faststart", "hvc1"]);
+    }
+
+    #[test]
+    fn test_output_naming_and_format() {
+        let mp4 = fixed_cache_path(Path::new("/media/a.mp4")).unwrap();
+        let mov = fixed_cache_path(Path::new("/media/a.MOV")).unwrap();
+        assert_eq!(mp4, Path::new("/media/.transcoded/a_mp4_fixed.mp4"));
+        assert_eq!(mov, Path::new("/media/.transcoded/a_mov_fixed.mp4"));
+
+        assert_eq!(output_format(Path::new("/media/a.MOV"), true), "mov");
+        assert_eq!(output_format(Path::new("/media/a.mov"), false), "mp4");
+        assert_eq!(output_format(Path::new("/media/a.m4v"), true), "mp4");
+    }
+}
@@ -0,0 +1,208 @@
+// HLS 切片磁盘缓存
+//
+// 缓存位置: <app_dir>/cache/hls/<源路径 hash>/<mtime>_v<格式版本>_<档位>[_a<音轨>]_<序号>.ts
+// - key 含源文件 mtime、档位和音轨: 文件被修改后旧切片自然失配, 由 LRU 淘汰
+// - 切片的转码参数改变时递增 SEGMENT_VERSION, 旧参数生成的切片同样失配
+// - 同一切片同一时间只转码一次: 前台请求 / 预读任务都先登记 in-flight,
+//   后到的请求等前一个写完再读缓存; 已排队的预读也单独登记, 避免每次请求都重复排队
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{watch, Semaphore};
+use super::disk_cache::{self, DiskCache};
+
+/// 缓存容量默认 4096 MB，可在 app.json 中通过 hls_cache_mb 覆盖
+static CACHE: DiskCache = DiskCache::new("hls", "hls_cache_mb", 4096);
+
+/// 切片格式版本: 2 起切片带 -output_ts_offset 时间戳 (与 WebVTT 字幕对齐)
+const SEGMENT_VERSION: u32 = 2;
+
+/// 每次请求切片后预读转码的后续切片数，可在 app.json 中通过 hls_lookahead_segments 覆盖（0 关闭）
+const DEFAULT_LOOKAHEAD: u64 = 3;
+
+/// 预读任务的并发上限（前台请求不受限，优先保证正在播放的切片）
+const LOOKAHEAD_MAX_CONCURRENT: usize = 2;
+
+pub fn lookahead_count() -> usize {
+    crate::static_files::read_config_file("app.json")
+        .and_then(|data| serde_json::from_slice::<serde_json::Value>(&data).ok())
+        .and_then(|v| v.get("hls_lookahead_segments").and_then(|x| x.as_u64()))
+        .unwrap_or(DEFAULT_LOOKAHEAD) as usize
+}
+
+pub fn lookahead_permits() -> &'static Semaphore {
+    static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+    PERMITS.get_or_init(|| Semaphore::new(LOOKAHEAD_MAX_CONCURRENT))
+}
+
+/// 计算切片缓存路径；源文件 mtime 读不到时返回 None（不缓存）
+/// variant 为 None 表示源分辨率（旧版单一 playlist 的切片），audio 为 None 表示默认音轨
+pub fn segment_path(source: &Path, variant: Option<&str>, audio: Option<u32>, seg_idx: usize) -> Option<PathBuf> {
+    let variant = variant.unwrap_or("src");
+    // 档位名来自 query, 只允许字母数字, 防止拼路径时被注入
+    if !variant.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let mtime = std::fs::metadata(source)
+        .and_then(|m| m.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some(
+        CACHE.root()
+            .join(disk_cache::hash_name(&source.to_string_lossy()))
+            .join(match audio {
+                Some(a) => format!("{}_v{}_{}_a{}_{}.ts", mtime, SEGMENT_VERSION, variant, a, seg_idx),
+                None => format!("{}_v{}_{}_{}.ts", mtime, SEGMENT_VERSION, variant, seg_idx),
+            }),
+    )
+}
+
+/// 命中则刷新 LRU 访问时间
+pub fn touch(path: &Path) -> bool {
+    CACHE.touch(path)
+}
+
+// ============================================================================
+// in-flight 登记
+// ============================================================================
+
+/// 正在转码的切片 → 完成通知（sender 被 drop 时 receiver 收到 Err, 即"已结束"）
+fn inflight() -> &'static Mutex<HashMap<PathBuf, watch::Receiver<()>>> {
+    static INFLIGHT: OnceLock<Mutex<HashMap<PathBuf, watch::Receiver<()>>>> = OnceLock::new();
+    INFLIGHT.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 持有期间表示该切片正在转码；drop 时注销并唤醒等待者
+pub struct InflightGuard {
+    path: PathBuf,
+    _done: watch::Sender<()>,
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        inflight().lock().unwrap().remove(&self.path);
+    }
+}
+
+/// 登记转码；已有任务在转同一切片时返回其完成通知
+pub fn begin(path: &Path) -> Result<InflightGuard, watch::Receiver<()>> {
+    let mut map = inflight().lock().unwrap();
+    if let Some(rx) = map.get(path) {
+        return Err(rx.clone());
+    }
+    let (tx, rx) = watch::channel(());
+    map.insert(path.to_path_buf(), rx);
+    Ok(InflightGuard { path: path.to_path_buf(), _done: tx })
+}
+
+/// 等待另一个任务转完（不论成败）
+pub async fn wait(mut rx: watch::Receiver<()>) {
+    while rx.changed().await.is_ok() {}
+}
+
+/// 已排队（等待并发名额）的预读切片；前台请求不看这里，不会被排队中的预读挡住
+fn scheduled() -> &'static Mutex<HashSet<PathBuf>> {
+    static SCHEDULED: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+    SCHEDULED.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// 持有期间表示该切片的预读已排队；drop 时注销
+pub struct ScheduledGuard {
+    path: PathBuf,
+}
+
+impl Drop for ScheduledGuard {
+    fn drop(&mut self) {
+        scheduled().lock().unwrap().remove(&self.path);
+    }
+}
+
+/// 登记预读；该切片正在转码或已有预读排队时返回 None
+pub fn schedule(path: &Path) -> Option<ScheduledGuard> {
+    if inflight().lock().unwrap().contains_key(path) {
+        return None;
+    }
+    if !scheduled().lock().unwrap().insert(path.to_path_buf()) {
+        return None;
+    }
+    Some(ScheduledGuard { path: path.to_path_buf() })
+}
+
+// ============================================================================
+// 边转码边写缓存
+// ============================================================================
+
+/// 转码输出先写到同目录临时文件，成功结束后 finish() 移入缓存；
+/// 中途失败或客户端断开（未调用 finish 就被 drop）时删除临时文件
+/// 文件读写都走 tokio::fs，不阻塞推流所在的 async 任务
+pub struct PendingSegment {
+    file: Option<tokio::fs::File>,
+    tmp: PathBuf,
+    target: PathBuf,
+    committed: bool,
+    _inflight: InflightGuard,
+}
+
+impl PendingSegment {
+    pub async fn create(target: &Path, inflight: InflightGuard) -> Option<Self> {
+        tokio::fs::create_dir_all(target.parent()?).await.ok()?;
+        let tmp = CACHE.temp_path(target);
+        let file = tokio::fs::File::create(&tmp).await.ok()?;
+        Some(PendingSegment {
+            file: Some(file),
+            tmp,
+            target: target.to_path_buf(),
+            committed: false,
+            _inflight: inflight,
+        })
+    }
+
+    /// 临时文件路径（后台预读直接让 ffmpeg 写这里）
+    pub fn tmp_path(&self) -> &Path {
+        &self.tmp
+    }
+
+    /// 追加数据；写失败时放弃缓存（不影响正在进行的播放）
+    pub async fn write(&mut self, chunk: &[u8]) {
+        if let Some(f) = self.file.as_mut() {
+            if f.write_all(chunk).await.is_err() {
+                self.file = None;
+            }
+        }
+    }
+
+    /// 转码成功结束：落盘后移入缓存（可能触发淘汰扫描，放到阻塞线程）
+    pub async fn finish(mut self) {
+        let Some(mut f) = self.file.take() else { return };
+        if f.flush().await.is_err() {
+            return;
+        }
+        drop(f);
+        let (tmp, target) = (self.tmp.clone(), self.target.clone());
+        self.committed = tokio::task::spawn_blocking(move || CACHE.commit(&tmp, &target))
+            .await
+            .unwrap_or(false);
+    }
+}
+
+impl Drop for PendingSegment {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        // commit 失败时已自行删除临时文件, 这里 remove 是空操作
+        let tmp = std::mem::take(&mut self.tmp);
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(move || std::fs::remove_file(tmp));
+            }
+            Err(_) => {
+                let _ = std::fs::remove_file(tmp);
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+// 视频悬停预览：多个 1 秒片段拼接的静音短循环（MP4），缓存在缩略图磁盘缓存中
+use actix_files::NamedFile;
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+use tokio::sync::Semaphore;
+use super::models::VIDEO_EXTENSIONS;
+use super::thumb_cache;
+
+/// 片段数
+const HOVER_SNIPPETS: u32 = 5;
+
+/// 默认输出宽度（同样归一到缩略图档位）
+const HOVER_DEFAULT_WIDTH: u32 = 320;
+
+/// 同时生成的预览数上限。网格里鼠标划过一排视频会瞬间发出一串请求，
+/// 不限流的话会同时拉起几十个 ffmpeg
+const HOVER_MAX_CONCURRENT: usize = 2;
+
+fn generate_permits() -> &'static Semaphore {
+    static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+    PERMITS.get_or_init(|| Semaphore::new(HOVER_MAX_CONCURRENT))
+}
+
+/// GET /api/preview/hover?uuid=<uuid>&width=<px>
+/// 返回视频的悬停预览循环（静音 MP4，5 个 1 秒片段均匀分布在全片）
+/// 按 (uuid, 宽度档位, mtime) 缓存，响应与缩略图一样标记为 immutable
+pub async fn get_hover_preview(
+    query: web::Query<HashMap<String, String>>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let uuid = query.get("uuid")
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("缺少 uuid 参数"))?;
+    let file = crate::indexer::storage::get_file_by_uuid(uuid)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("数据库错误: {}", e)))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("UUID 对应的文件未找到"))?;
+    let file_path = file.current_path
+        .ok_or_else(|| actix_web::error::ErrorNotFound("文件已被删除或移动"))?;
+
+    let path = Path::new(&file_path);
+    if !path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest("仅支持视频文件"));
+    }
+
+    let width = query.get("width")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(HOVER_DEFAULT_WIDTH);
+    let width = thumb_cache::bucket_size(width);
+
+    let cache_file = thumb_cache::cache_path(&file.uuid, width, path, "hover.mp4")
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("无法确定缓存路径"))?;
+
+    if !thumb_cache::touch(&cache_file) {
+        let _permit = generate_permits()
+            .acquire()
+            .await
+            .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+
+        // 排队期间可能已被同一视频的其他请求生成
+        if !cache_file.exists() {
+            let source = path.to_path_buf();
+            let target = cache_file.clone();
+            tokio::task::spawn_blocking(move || {
+                let duration = super::content::get_duration_cached(&source)
+                    .ok_or_else(|| "无法探测视频时长".to_string())?;
+                let data = super::utils::generate_hover_preview(&source, duration, HOVER_SNIPPETS, width)
+                    .map_err(|e| e.to_string())?;
+                thumb_cache::store(&target, &data);
+                Ok::<_, String>(())
+            })
+            .await
+            .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+        }
+    }
+
+    // 以文件形式返回：Safari 播放 MP4 要求支持 Range
+    let f = NamedFile::open_async(&cache_file)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("打开文件失败: {}", e)))?;
+    let mut resp = f.into_response(&req);
+    resp.headers_mut().insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("public, max-age=31536000, immutable"),
+    );
+    Ok(resp)
+}
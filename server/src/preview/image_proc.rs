@@ -0,0 +1,157 @@
+// 图片处理（类似 imgproxy）：按需缩放 / 裁剪 / 转格式 / 调质量，结果缓存到磁盘
+// 手机全屏看图时不必下载几十 MB 的原图
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+use tokio::sync::Semaphore;
+
+use super::content::resolve_source;
+use super::disk_cache::{self, DiskCache};
+use super::models::{GIF_EXTENSION, IMAGE_EXTENSIONS};
+use super::thumbnail::{decode_image, encode_image, negotiate_format};
+
+/// 缓存容量默认 2048 MB，可在 app.json 中通过 image_cache_mb 覆盖
+pub(super) static CACHE: DiskCache = DiskCache::new("images", "image_cache_mb", 2048);
+
+/// 输出宽高上限
+const MAX_DIMENSION: u32 = 8192;
+
+const DEFAULT_QUALITY: u8 = 80;
+
+/// 同时处理的图片数上限：大图解码后动辄几百 MB 像素数据
+const IMAGE_MAX_CONCURRENT: usize = 2;
+
+pub(super) fn process_permits() -> &'static Semaphore {
+    static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+    PERMITS.get_or_init(|| Semaphore::new(IMAGE_MAX_CONCURRENT))
+}
+
+/// 缩放方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fit {
+    /// 等比缩放到框内（默认）
+    Contain,
+    /// 等比缩放填满框，居中裁掉多余部分（需要同时指定 w 和 h）
+    Cover,
+}
+
+/// 缩放 / 裁剪；只缩小不放大
+fn resize(img: DynamicImage, w: Option<u32>, h: Option<u32>, fit: Fit) -> DynamicImage {
+    let (sw, sh) = img.dimensions();
+    if let (Some(w), Some(h), Fit::Cover) = (w, h, fit) {
+        // 源图上取与目标框同宽高比的最大居中区域
+        let scale = (w as f64 / sw as f64).max(h as f64 / sh as f64);
+        let cw = ((w as f64 / scale).round() as u32).clamp(1, sw);
+        let ch = ((h as f64 / scale).round() as u32).clamp(1, sh);
+        let cropped = img.crop_imm((sw - cw) / 2, (sh - ch) / 2, cw, ch);
+        return if scale < 1.0 {
+            cropped.resize_exact(w, h, FilterType::CatmullRom)
+        } else {
+            cropped
+        };
+    }
+    let (bw, bh) = (w.unwrap_or(sw).min(sw), h.unwrap_or(sh).min(sh));
+    if (bw, bh) == (sw, sh) {
+        img
+    } else {
+        img.resize(bw, bh, FilterType::CatmullRom)
+    }
+}
+
+/// 缓存路径：<源路径 hash>/<mtime>_<处理参数>.<ext>
+pub(super) fn cache_path(source: &Path, params: &str, ext: &str) -> Option<PathBuf> {
+    let mtime = std::fs::metadata(source)
+        .and_then(|m| m.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some(
+        CACHE.root()
+            .join(disk_cache::hash_name(&source.to_string_lossy()))
+            .join(format!("{}_{}.{}", mtime, params, ext)),
+    )
+}
+
+pub(super) fn image_response(data: Vec<u8>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(super::thumbnail::ThumbFormat::sniff(&data).mime())
+        .insert_header(("Cache-Control", "public, max-age=31536000, immutable"))
+        .insert_header(("Vary", "Accept"))
+        .body(data)
+}
+
+/// GET /api/preview/image?uuid=<uuid>&w=<px>&h=<px>&fit=<contain|cover>&format=<webp|jpeg|png|avif>&q=<1-100>
+/// GET /api/preview/image?path=<file_path>&...
+/// 返回处理后的图片：按 EXIF 方向校正，缩放到 w × h 框内（cover 时裁剪填满），只缩小不放大
+/// 未指定 format 时按 Accept 头协商；结果按 (路径, mtime, 参数) 缓存到磁盘
+pub async fn get_image(req: HttpRequest, query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_source(&query)?;
+    if !source_path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let extension = source_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if extension != GIF_EXTENSION && !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest("仅支持图片文件"));
+    }
+
+    let dimension = |key: &str| query.get(key)
+        .and_then(|s| s.parse::<u32>().ok())
+        .filter(|v| *v > 0)
+        .map(|v| v.min(MAX_DIMENSION));
+    let (w, h) = (dimension("w"), dimension("h"));
+    let fit = match query.get("fit").map(|s| s.as_str()) {
+        Some("cover") => Fit::Cover,
+        Some("contain") | None => Fit::Contain,
+        Some(_) => return Err(actix_web::error::ErrorBadRequest("fit 只支持 contain / cover")),
+    };
+    let quality = query.get("q")
+        .and_then(|s| s.parse::<u32>().ok())
+        .map_or(DEFAULT_QUALITY, |q| q.clamp(1, 100) as u8);
+    let format = negotiate_format(&req, &query);
+
+    let fit_name = match fit {
+        Fit::Contain => "contain",
+        Fit::Cover => "cover",
+    };
+    let params = format!("{}x{}_{}_q{}", w.unwrap_or(0), h.unwrap_or(0), fit_name, quality);
+    let cache_file = cache_path(&source_path, &params, format.ext());
+    if let Some(data) = cache_file.as_deref().and_then(|p| CACHE.read(p)) {
+        return Ok(image_response(data));
+    }
+
+    let _permit = process_permits().acquire().await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+    // 排队期间可能已被同参数的请求生成
+    if let Some(data) = cache_file.as_deref().and_then(|p| CACHE.read(p)) {
+        return Ok(image_response(data));
+    }
+
+    let data = tokio::task::spawn_blocking(move || {
+        // ffmpeg 回退路径直接缩放到目标尺寸附近，避免超大图内存超限；
+        // cover 裁剪前短边也要够大，按 4:1 以内的宽高比预留
+        let decode_size = match fit {
+            Fit::Contain => w.max(h).unwrap_or(0),
+            Fit::Cover => w.max(h).map(|v| v.saturating_mul(4)).unwrap_or(0),
+        };
+        let img = decode_image(&source_path, decode_size).map_err(|e| e.to_string())?;
+        let img = resize(img, w, h, fit);
+        let data = encode_image(&img, format, Some(quality)).map_err(|e| e.to_string())?;
+        if let Some(ref cache_file) = cache_file {
+            CACHE.store(cache_file, &data);
+        }
+        Ok::<_, String>(data)
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(image_response(data))
+}
@@ -0,0 +1,200 @@
+// 响度分析后台任务
+//
+// 对源文件夹下的音频 / 视频逐个跑 ffmpeg ebur128 滤镜, 记录整体响度 (LUFS)、真峰值 (dBTP) 和响度范围 (LRA).
+// 结果按 UUID 存入 loudness 表, 文件列表 / 播放列表返回时附带建议增益, 客户端据此做音量归一.
+// 已分析过且 modified_at 未变的文件跳过; 没有音轨的文件也记录一行空结果, 避免每次都重试
+use actix_web::{web, HttpResponse, Result};
+use std::sync::{OnceLock, RwLock};
+
+use super::models::{FixupFailure, LoudnessRequest, LoudnessStatus};
+use super::utils::get_ffmpeg_path;
+use crate::indexer::models::IndexedFile;
+
+/// 归一目标响度（ReplayGain 2.0 参考电平）
+const TARGET_LUFS: f64 = -18.0;
+
+/// 增益后真峰值不超过此值，避免削波
+const MAX_TRUE_PEAK: f64 = -1.0;
+
+/// 建议增益的上下限（dB）
+const MAX_GAIN: f64 = 20.0;
+
+fn status() -> &'static RwLock<LoudnessStatus> {
+    static STATUS: OnceLock<RwLock<LoudnessStatus>> = OnceLock::new();
+    STATUS.get_or_init(|| RwLock::new(LoudnessStatus::default()))
+}
+
+/// ebur128 汇总结果
+#[derive(Debug, PartialEq)]
+struct Measurement {
+    integrated: f64,
+    true_peak: Option<f64>,
+    lra: Option<f64>,
+}
+
+/// 建议增益：拉到目标响度，但不让真峰值超过 -1 dBTP，并限制在 ±20 dB 内
+pub fn suggested_gain(integrated: f64, true_peak: Option<f64>) -> f64 {
+    let mut gain = TARGET_LUFS - integrated;
+    if let Some(peak) = true_peak {
+        gain = gain.min(MAX_TRUE_PEAK - peak);
+    }
+    (gain.clamp(-MAX_GAIN, MAX_GAIN) * 100.0).round() / 100.0
+}
+
+/// 从 "  I:  -23.0 LUFS" 这类行中取数值；-inf / nan 返回 None
+fn parse_value(line: &str, label: &str) -> Option<f64> {
+    let rest = line.trim().strip_prefix(label)?;
+    let value: f64 = rest.split_whitespace().next()?.parse().ok()?;
+    value.is_finite().then_some(value)
+}
+
+/// 解析 ebur128 滤镜在 stderr 末尾打印的 Summary 段
+fn parse_summary(stderr: &str) -> Option<Measurement> {
+    let summary = &stderr[stderr.rfind("Summary:")?..];
+    let mut integrated = None;
+    let mut true_peak = None;
+    let mut lra = None;
+    for line in summary.lines() {
+        integrated = integrated.or_else(|| parse_value(line, "I:"));
+        lra = lra.or_else(|| parse_value(line, "LRA:"));
+        true_peak = true_peak.or_else(|| parse_value(line, "Peak:"));
+    }
+    Some(Measurement { integrated: integrated?, true_peak, lra })
+}
+
+/// 分析单个文件；没有音轨时返回 None
+fn analyze_file(path: &str) -> std::result::Result<Option<Measurement>, String> {
+    // 只解码第一条音轨；逐帧日志用 verbose 级别输出，默认日志级别下不会刷屏
+    let output = std::process::Command::new(get_ffmpeg_path())
+        .args([
+            "-hide_banner", "-nostats", "-i", path,
+            "-map", "0:a:0", "-filter:a", "ebur128=peak=true:framelog=verbose",
+            "-f", "null", "-",
+        ])
+        .output()
+        .map_err(|e| format!("ffmpeg 启动失败: {}", e))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        if stderr.contains("matches no streams") {
+            return Ok(None);
+        }
+        let last = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("");
+        return Err(format!("ffmpeg 分析失败: {}", last.trim()));
+    }
+    parse_summary(&stderr).map(Some).ok_or_else(|| "未能解析 ebur128 输出".to_string())
+}
+
+fn process_file(file: &IndexedFile) -> std::result::Result<(), String> {
+    let path = file.current_path.as_deref().ok_or("文件已被删除或移动")?;
+    let measurement = analyze_file(path)?;
+    let (integrated, true_peak, lra) = match measurement {
+        Some(m) => (Some(m.integrated), m.true_peak, m.lra),
+        None => (None, None, None),
+    };
+    crate::indexer::storage::save_loudness(&file.uuid, &file.modified_at, integrated, true_peak, lra)
+        .map_err(|e| format!("数据库错误: {}", e))
+}
+
+/// POST /api/preview/loudness — 后台分析源文件夹下音频 / 视频的响度
+/// 参数 force=true 时忽略已有结果全部重新分析
+pub async fn start_loudness(req: web::Json<LoudnessRequest>) -> Result<HttpResponse> {
+    let source_folder = req.source_folder.clone();
+    let force = req.force;
+
+    {
+        let mut status = status().write().unwrap();
+        if status.is_running {
+            return Ok(HttpResponse::Ok().json(serde_json::json!({
+                "status": "already_running",
+                "processed": status.processed,
+                "total": status.total,
+            })));
+        }
+        *status = LoudnessStatus { is_running: true, ..Default::default() };
+    }
+
+    tokio::spawn(async move {
+        let result = tokio::task::spawn_blocking(move || {
+            if let Err(e) = crate::indexer::storage::delete_stale_loudness() {
+                eprintln!("清理失效的响度结果失败: {}", e);
+            }
+            let files = crate::indexer::storage::get_files_needing_loudness(&source_folder, force)
+                .map_err(|e| format!("数据库错误: {}", e))?;
+            status().write().unwrap().total = files.len() as u64;
+
+            for file in &files {
+                let outcome = process_file(file);
+                let mut status = status().write().unwrap();
+                status.processed += 1;
+                if let Err(error) = outcome {
+                    let path = file.current_path.clone().unwrap_or_default();
+                    eprintln!("[loudness] {} 失败: {}", path, error);
+                    status.failed.push(FixupFailure { path, error });
+                }
+            }
+            Ok::<_, String>(())
+        }).await;
+
+        match result {
+            Ok(Err(e)) => eprintln!("响度分析任务失败: {}", e),
+            Err(e) => eprintln!("响度分析任务 panic: {}", e),
+            _ => {}
+        }
+        status().write().unwrap().is_running = false;
+    });
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "started"
+    })))
+}
+
+/// GET /api/preview/loudness/status — 返回响度分析进度
+pub async fn loudness_status() -> Result<HttpResponse> {
+    let status = status().read().unwrap();
+    Ok(HttpResponse::Ok().json(&*status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary() {
+        let stderr = "\
+[Parsed_ebur128_0 @ 0x55d] Summary:
+
+  Integrated loudness:
+    I:         -14.2 LUFS
+    Threshold: -24.6 LUFS
+
+  Loudness range:
+    LRA:         6.1 LU
+    Threshold: -34.5 LUFS
+    LRA low:   -18.9 LUFS
+    LRA high:  -12.8 LUFS
+
+  True peak:
+    Peak:        0.4 dBFS
+";
+        assert_eq!(
+            parse_summary(stderr),
+            Some(Measurement { integrated: -14.2, true_peak: Some(0.4), lra: Some(6.1) })
+        );
+        let silent = "Summary:\n    I:         -70.0 LUFS\n    LRA:         0.0 LU\n    Peak:       -inf dBFS\n";
+        assert_eq!(
+            parse_summary(silent),
+            Some(Measurement { integrated: -70.0, true_peak: None, lra: Some(0.0) })
+        );
+        assert_eq!(parse_summary("no summary here"), None);
+    }
+
+    #[test]
+    fn test_suggested_gain() {
+        // 偏响的母带：按目标响度衰减
+        assert_eq!(suggested_gain(-14.2, Some(0.4)), -3.8);
+        // 偏轻但峰值已高：受真峰值限制
+        assert_eq!(suggested_gain(-24.0, Some(-3.0)), 2.0);
+        // 静音：限制在上限
+        assert_eq!(suggested_gain(-70.0, None), 20.0);
+    }
+}
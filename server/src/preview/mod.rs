@@ -2,7 +2,26 @@
 pub mod models;
 mod files;
 mod thumbnail;
+mod image_proc;
+mod eink;
+mod pdf;
+mod archive;
+mod epub;
+mod text;
+mod design;
+mod raw;
+mod exif;
 mod content;
+mod hover;
+mod waveform;
+mod fixup;
+pub mod loudness;
+mod subtitles;
+mod disk_cache;
+mod hls_cache;
+pub mod thumb_cache;
+pub mod animation;
+mod blurhash;
 pub mod utils;
 
 use actix_web::web;
@@ -11,5 +30,28 @@ use actix_web::web;
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/files").route(web::get().to(files::get_files)))
        .service(web::resource("/thumbnail").route(web::get().to(thumbnail::get_thumbnail)))
+       .service(web::resource("/image").route(web::get().to(image_proc::get_image)))
+       .service(web::resource("/eink").route(web::get().to(eink::get_eink)))
+       .service(web::resource("/pdf/info").route(web::get().to(pdf::get_pdf_info)))
+       .service(web::resource("/pdf/page").route(web::get().to(pdf::get_pdf_page)))
+       .service(web::resource("/pdf/outline").route(web::get().to(pdf::get_pdf_outline)))
+       .service(web::resource("/pdf/text").route(web::get().to(pdf::get_pdf_text)))
+       .service(web::resource("/archive").route(web::get().to(archive::list_archive)))
+       .service(web::resource("/archive/entry").route(web::get().to(archive::get_archive_entry)))
+       .service(web::resource("/epub/info").route(web::get().to(epub::get_epub_info)))
+       .service(web::resource("/epub/{uuid}/{path:.*}").route(web::get().to(epub::serve_epub_resource)))
+       .service(web::resource("/text").route(web::get().to(text::get_text)))
+       .service(web::resource("/raw/info").route(web::get().to(raw::get_raw_info)))
+       .service(web::resource("/hover").route(web::get().to(hover::get_hover_preview)))
+       .service(web::resource("/waveform").route(web::get().to(waveform::get_waveform)))
+       .service(web::resource("/sprite").route(web::get().to(content::serve_sprite)))
+       .service(web::resource("/sprite.vtt").route(web::get().to(content::serve_sprite_vtt)))
+       .service(web::resource("/media-info").route(web::get().to(content::get_media_info)))
+       .service(web::resource("/subtitles").route(web::get().to(subtitles::list_subtitles)))
+       .service(web::resource("/subtitle.vtt").route(web::get().to(subtitles::serve_subtitle)))
+       .service(web::resource("/fixup").route(web::post().to(fixup::start_fixup)))
+       .service(web::resource("/fixup/status").route(web::get().to(fixup::fixup_status)))
+       .service(web::resource("/loudness").route(web::post().to(loudness::start_loudness)))
+       .service(web::resource("/loudness/status").route(web::get().to(loudness::loudness_status)))
        .service(web::resource("/content/{path:.*}").route(web::get().to(content::serve_file)));
 }
@@ -10,6 +10,8 @@ pub enum FileType {
     Gif,     // GIF 动图
     Audio,   // 音频
     Pdf,     // PDF 文档
+    Ebook,   // 电子书（EPUB）
+    Document, // 文本文档（TXT / Markdown / 日志 / 字幕 / 代码等）
     Other,   // 其他文件
 }
 
@@ -49,6 +51,235 @@ pub struct FoldersResponse {
     pub folders: Vec<FolderInfo>,
 }
 
+/// 修复任务请求
+#[derive(Debug, Deserialize)]
+pub struct FixupRequest {
+    pub source_folder: String,
+    #[serde(default)]
+    pub in_place: bool,            // true: 原地替换原文件；false: 写入 .transcoded/ 缓存
+}
+
+/// 修复任务进度
+#[derive(Debug, Default, Serialize)]
+pub struct FixupStatus {
+    pub is_running: bool,
+    pub in_place: bool,
+    pub total: u64,                // 待检查的 MP4/MOV 数量
+    pub processed: u64,            // 已检查数量
+    pub changed: Vec<FixupResult>, // 已修复的文件
+    pub failed: Vec<FixupFailure>, // 修复失败的文件
+}
+
+/// 单个已修复文件
+#[derive(Debug, Serialize)]
+pub struct FixupResult {
+    pub uuid: String,
+    pub path: String,
+    pub fixes: Vec<String>,        // faststart / hvc1
+    pub output: String,            // 修复后的文件（原地替换时即原路径）
+}
+
+/// 单个修复失败的文件
+#[derive(Debug, Serialize)]
+pub struct FixupFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// 响度分析请求
+#[derive(Debug, Deserialize)]
+pub struct LoudnessRequest {
+    pub source_folder: String,
+    #[serde(default)]
+    pub force: bool,               // true: 忽略已有结果全部重新分析
+}
+
+/// 响度分析进度
+#[derive(Debug, Default, Serialize)]
+pub struct LoudnessStatus {
+    pub is_running: bool,
+    pub total: u64,                // 待分析的音频 / 视频数量
+    pub processed: u64,            // 已分析数量
+    pub failed: Vec<FixupFailure>, // 分析失败的文件
+}
+
+/// 文本文档预览响应
+#[derive(Debug, Serialize)]
+pub struct TextPreviewResponse {
+    pub encoding: String,          // 检测到（或指定）的字符集：UTF-8 / GBK / Shift_JIS / UTF-16LE ...
+    pub kind: String,              // markdown / text / code
+    pub language: String,          // 扩展名，供前端选择语法高亮
+    pub page: usize,               // 当前页（从 1 开始）
+    pub page_lines: usize,         // 每页行数
+    pub total_pages: usize,
+    pub total_lines: usize,
+    pub content: String,           // 当前页的 UTF-8 文本（换行统一为 \n）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,      // Markdown 渲染并净化后的 HTML（整篇，不分页）
+}
+
+/// 音轨信息
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AudioTrackInfo {
+    pub index: u32,                // 流序号（切换音轨时作为 hls_audio 参数）
+    pub language: Option<String>,  // 语言代码
+    pub title: Option<String>,     // 轨道标题
+    pub codec: String,             // 编码（aac / ac3 / flac ...）
+    pub channels: Option<String>,  // 声道布局（stereo / 5.1(side) ...）
+    pub default: bool,             // 是否为默认轨
+}
+
+/// 媒体信息响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MediaInfoResponse {
+    pub duration: f64,             // 时长（秒）
+    pub width: u32,                // 视频宽度（探测不到时为 0）
+    pub height: u32,               // 视频高度（探测不到时为 0）
+    pub audio_tracks: Vec<AudioTrackInfo>,
+}
+
+/// 字幕轨信息
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubtitleTrackInfo {
+    pub id: String,                // 轨道 ID（内嵌 e<流序号> / 外挂 s<序号>）
+    pub kind: String,              // embedded / sidecar
+    pub name: String,              // 显示名称（标题 > 语言 > Track N）
+    pub language: Option<String>,  // 语言代码（内嵌取流元数据，外挂取文件名中间段）
+    pub codec: String,             // 原始格式（ass / subrip / srt ...）
+    pub default: bool,             // 是否为默认轨
+    pub url: String,               // WebVTT 地址
+}
+
+/// 获取字幕轨列表的响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubtitleTracksResponse {
+    pub tracks: Vec<SubtitleTrackInfo>,
+}
+
+/// PDF 页面尺寸（单位：点，1/72 英寸）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfPageSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// PDF 页数与元数据
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfInfoResponse {
+    pub page_count: u32,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    pub creator: Option<String>,
+    pub producer: Option<String>,
+    pub creation_date: Option<String>,  // PDF 原始日期格式（D:YYYYMMDDHHmmSS...）
+    pub mod_date: Option<String>,
+    pub pages: Vec<PdfPageSize>,        // 各页尺寸，按页序
+}
+
+/// PDF 目录项
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfOutlineItem {
+    pub title: String,
+    pub page: Option<u32>,             // 跳转页码（从 1 开始），外部链接时为空
+    pub uri: Option<String>,           // 外部链接
+    pub children: Vec<PdfOutlineItem>,
+}
+
+/// PDF 目录响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfOutlineResponse {
+    pub items: Vec<PdfOutlineItem>,
+}
+
+/// PDF 页面上的一个词及其包围盒（页面坐标，单位：点，原点左上）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PdfWord {
+    pub text: String,
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+}
+
+/// PDF 单页文本响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfPageTextResponse {
+    pub page: u32,
+    pub width: f32,
+    pub height: f32,
+    pub text: String,                  // 纯文本，行间以换行分隔
+    pub words: Vec<PdfWord>,
+}
+
+/// 相机 RAW 的拍摄信息（读取不到的字段为 null）
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RawInfoResponse {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens: Option<String>,
+    pub iso: Option<u32>,
+    pub exposure_time: Option<String>,   // 快门速度（"1/250" / "2.5"，单位秒）
+    pub f_number: Option<f32>,
+    pub focal_length: Option<f32>,       // 焦距（mm）
+    pub taken_at: Option<String>,        // 拍摄时间（YYYY-MM-DD HH:MM:SS，相机本地时间）
+    pub orientation: u16,                // EXIF 方向（1–8）
+}
+
+/// 音频波形峰值（`/waveform` 的 JSON 响应）
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WaveformResponse {
+    pub duration: f64,                   // 时长（秒），探测不到时为 0
+    pub samples: usize,                  // 峰值个数（可能少于请求值，极短的音频没有那么多窗口）
+    pub peaks: Vec<f32>,                 // 每段的最大绝对振幅（0.0–1.0），按时间顺序均分全长
+}
+
+/// 压缩包内的条目
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub name: String,              // 包内完整路径（取单个条目时作为 entry 参数）
+    pub size: u64,                 // 解压后大小
+    pub compressed_size: u64,
+    pub is_dir: bool,
+    pub file_type: String,         // 按扩展名分类：image / gif / video / audio / pdf / other
+}
+
+/// 压缩包条目列表响应
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveListResponse {
+    pub entries: Vec<ArchiveEntry>,
+    pub total_size: u64,           // 解压后总大小
+}
+
+/// EPUB 书脊（阅读顺序）中的一项
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EpubSpineItem {
+    pub id: String,                // manifest 中的 id
+    pub href: String,              // 容器内完整路径
+    pub media_type: String,
+    pub linear: bool,              // linear="no" 的为附属内容（注释、弹窗等）
+}
+
+/// EPUB 目录项
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EpubTocItem {
+    pub title: String,
+    pub href: Option<String>,      // 容器内完整路径，可带 #锚点
+    pub children: Vec<EpubTocItem>,
+}
+
+/// EPUB 元数据、书脊和目录
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EpubInfoResponse {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub language: Option<String>,
+    pub cover: Option<String>,     // 封面图片在容器内的路径
+    pub spine: Vec<EpubSpineItem>,
+    pub toc: Vec<EpubTocItem>,
+}
+
 /// 支持的图片格式
 pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "bmp", "tiff", "svg", "heic", "heif", "avif"];
 
@@ -64,5 +295,26 @@ pub const AUDIO_EXTENSIONS: &[&str] = &["mp3", "wav", "aac", "flac", "m4a", "ogg
 /// PDF 格式
 pub const PDF_EXTENSION: &str = "pdf";
 
+/// 电子书格式
+pub const EBOOK_EXTENSIONS: &[&str] = &["epub"];
+
+/// 文本文档格式：预览时检测字符集转为 UTF-8，Markdown 渲染为 HTML
+/// 不含 ts：录像常见的 MPEG-TS 也用这个扩展名
+pub const DOCUMENT_EXTENSIONS: &[&str] = &[
+    "txt", "text", "md", "markdown", "log", "nfo",
+    "json", "xml", "yaml", "yml", "toml", "ini", "cfg", "conf", "csv", "tsv",
+    "srt", "ass", "ssa", "vtt", "lrc", "cue",
+    "rs", "py", "js", "c", "h", "cpp", "hpp", "java", "go", "sh", "sql", "css",
+];
+
+/// 支持浏览的压缩包格式
+pub const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "cbz"];
+
 /// CLIP (Clip Studio Paint) 格式
 pub const CLIP_EXTENSION: &str = "clip";
+
+/// 相机 RAW 格式：预览取文件内嵌的最大 JPEG，归为图片类型
+pub const RAW_EXTENSIONS: &[&str] = &["dng", "cr2", "cr3", "nef", "arw", "raf", "orf"];
+
+/// 设计源文件格式：浏览器无法直接显示，预览取内嵌合成图或栅格化，归为图片类型
+pub const DESIGN_EXTENSIONS: &[&str] = &["clip", "psd", "psb", "kra", "ora", "svg"];
@@ -0,0 +1,300 @@
+// PDF 逐页阅读：页数 / 元数据、任意页渲染（带缓存）、目录、带词框的页面文本
+// E-ink 和移动端不必内置 PDF 引擎，直接按页取图 + 文本
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::content::resolve_source;
+use super::image_proc::{cache_path, image_response, process_permits, CACHE};
+use super::models::{
+    PdfInfoResponse, PdfOutlineItem, PdfOutlineResponse, PdfPageSize, PdfPageTextResponse, PdfWord, PDF_EXTENSION,
+};
+use super::thumbnail::{encode_image, negotiate_format};
+use super::utils::render_pdf_page;
+
+/// 未指定 width / dpi 时的渲染分辨率
+const DEFAULT_DPI: u32 = 144;
+const DPI_RANGE: (u32, u32) = (36, 600);
+const MAX_WIDTH: u32 = 4096;
+/// 渲染结果长边上限，防止超大页面（海报 / 图纸）按高 DPI 渲染出上亿像素
+const MAX_DIMENSION: f32 = 8192.0;
+
+const DEFAULT_QUALITY: u8 = 85;
+
+/// 解析 path / uuid 并确认是 PDF 文件
+fn resolve_pdf(query: &HashMap<String, String>) -> Result<PathBuf> {
+    let source_path = resolve_source(query)?;
+    if !source_path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let is_pdf = source_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.eq_ignore_ascii_case(PDF_EXTENSION))
+        .unwrap_or(false);
+    if !is_pdf {
+        return Err(actix_web::error::ErrorBadRequest("仅支持 PDF 文件"));
+    }
+    Ok(source_path)
+}
+
+fn open_document(path: &Path) -> std::result::Result<mupdf::Document, String> {
+    let path_str = path.to_str().ok_or("无效的文件路径")?;
+    mupdf::Document::open(path_str).map_err(|e| format!("无法打开 PDF: {}", e))
+}
+
+/// 页码参数（从 1 开始），缺省为第 1 页
+fn page_param(query: &HashMap<String, String>) -> Result<u32> {
+    match query.get("page") {
+        None => Ok(1),
+        Some(s) => s.parse::<u32>()
+            .ok()
+            .filter(|p| *p >= 1)
+            .ok_or_else(|| actix_web::error::ErrorBadRequest("page 必须是从 1 开始的页码")),
+    }
+}
+
+/// 加载第 page 页（从 1 开始），越界时返回 None（超出 i32 的页码同样视为越界）
+fn load_page(doc: &mupdf::Document, page: u32) -> std::result::Result<Option<mupdf::Page>, String> {
+    let count = doc.page_count().map_err(|e| format!("无法读取页数: {}", e))?;
+    let Some(page) = i32::try_from(page).ok().filter(|p| (1..=count).contains(p)) else {
+        return Ok(None);
+    };
+    doc.load_page(page - 1)
+        .map(Some)
+        .map_err(|e| format!("无法加载 PDF 页面: {}", e))
+}
+
+/// 任务返回值：Ok(None) 表示页码越界
+fn block_result<T>(
+    result: std::result::Result<std::result::Result<Option<T>, String>, tokio::task::JoinError>,
+) -> Result<T> {
+    result
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+        .map_err(actix_web::error::ErrorInternalServerError)?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("页码超出范围"))
+}
+
+/// GET /api/preview/pdf/info?uuid=<uuid> — 页数、文档元数据和各页尺寸
+pub async fn get_pdf_info(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_pdf(&query)?;
+
+    let info = tokio::task::spawn_blocking(move || {
+        let doc = open_document(&source_path)?;
+        let page_count = doc.page_count().map_err(|e| format!("无法读取页数: {}", e))?.max(0) as u32;
+        let meta = |name: mupdf::MetadataName| doc.metadata(name).ok().filter(|s| !s.is_empty());
+
+        let mut pages = Vec::with_capacity(page_count as usize);
+        for i in 0..page_count {
+            let bounds = doc.load_page(i as i32)
+                .and_then(|p| p.bounds())
+                .map_err(|e| format!("无法获取第 {} 页尺寸: {}", i + 1, e))?;
+            pages.push(PdfPageSize { width: bounds.x1 - bounds.x0, height: bounds.y1 - bounds.y0 });
+        }
+
+        Ok::<_, String>(Some(PdfInfoResponse {
+            page_count,
+            title: meta(mupdf::MetadataName::Title),
+            author: meta(mupdf::MetadataName::Author),
+            subject: meta(mupdf::MetadataName::Subject),
+            keywords: meta(mupdf::MetadataName::Keywords),
+            creator: meta(mupdf::MetadataName::Creator),
+            producer: meta(mupdf::MetadataName::Producer),
+            creation_date: meta(mupdf::MetadataName::CreationDate),
+            mod_date: meta(mupdf::MetadataName::ModDate),
+            pages,
+        }))
+    })
+    .await;
+
+    Ok(HttpResponse::Ok().json(block_result(info)?))
+}
+
+/// GET /api/preview/pdf/page?uuid=<uuid>&page=<n>&width=<px>|dpi=<dpi>&format=<webp|jpeg|png|avif>&q=<1-100>
+/// 渲染第 n 页（从 1 开始）为图片。width 优先于 dpi，都不传时按 144 DPI；结果按 (路径, mtime, 参数) 缓存
+pub async fn get_pdf_page(req: HttpRequest, query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_pdf(&query)?;
+    let page = page_param(&query)?;
+    let width = query.get("width")
+        .and_then(|s| s.parse::<u32>().ok())
+        .filter(|v| *v > 0)
+        .map(|v| v.min(MAX_WIDTH));
+    let dpi = query.get("dpi")
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(DEFAULT_DPI)
+        .clamp(DPI_RANGE.0, DPI_RANGE.1);
+    let quality = query.get("q")
+        .and_then(|s| s.parse::<u32>().ok())
+        .map_or(DEFAULT_QUALITY, |q| q.clamp(1, 100) as u8);
+    let format = negotiate_format(&req, &query);
+
+    let size = match width {
+        Some(w) => format!("w{}", w),
+        None => format!("d{}", dpi),
+    };
+    let params = format!("pdf_p{}_{}_q{}", page, size, quality);
+    let cache_file = cache_path(&source_path, &params, format.ext());
+    if let Some(data) = cache_file.as_deref().and_then(|p| CACHE.read(p)) {
+        return Ok(image_response(data));
+    }
+
+    let _permit = process_permits().acquire().await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+    if let Some(data) = cache_file.as_deref().and_then(|p| CACHE.read(p)) {
+        return Ok(image_response(data));
+    }
+
+    let data = tokio::task::spawn_blocking(move || {
+        let doc = open_document(&source_path)?;
+        let Some(pdf_page) = load_page(&doc, page)? else { return Ok(None) };
+        let bounds = pdf_page.bounds().map_err(|e| format!("无法获取页面尺寸: {}", e))?;
+        let (pw, ph) = (bounds.x1 - bounds.x0, bounds.y1 - bounds.y0);
+        if pw <= 0.0 || ph <= 0.0 {
+            return Err("页面尺寸无效".to_string());
+        }
+
+        let mut scale = match width {
+            Some(w) => w as f32 / pw,
+            None => dpi as f32 / 72.0,
+        };
+        scale = scale.min(MAX_DIMENSION / pw.max(ph));
+
+        let img = render_pdf_page(&pdf_page, scale).map_err(|e| e.to_string())?;
+        let data = encode_image(&img, format, Some(quality)).map_err(|e| e.to_string())?;
+        if let Some(ref cache_file) = cache_file {
+            CACHE.store(cache_file, &data);
+        }
+        Ok(Some(data))
+    })
+    .await;
+
+    Ok(image_response(block_result(data)?))
+}
+
+fn convert_outline(outlines: Vec<mupdf::Outline>) -> Vec<PdfOutlineItem> {
+    outlines
+        .into_iter()
+        .map(|o| PdfOutlineItem {
+            title: o.title,
+            page: o.dest.map(|d| d.loc.page_number + 1),
+            uri: o.uri.filter(|u| o.dest.is_none() && !u.starts_with('#')),
+            children: convert_outline(o.down),
+        })
+        .collect()
+}
+
+/// GET /api/preview/pdf/outline?uuid=<uuid> — 文档目录（书签树），无目录时返回空列表
+pub async fn get_pdf_outline(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_pdf(&query)?;
+
+    let items = tokio::task::spawn_blocking(move || {
+        let doc = open_document(&source_path)?;
+        let outlines = doc.outlines().map_err(|e| format!("无法读取目录: {}", e))?;
+        Ok::<_, String>(Some(convert_outline(outlines)))
+    })
+    .await;
+
+    Ok(HttpResponse::Ok().json(PdfOutlineResponse { items: block_result(items)? }))
+}
+
+/// 中日韩文字之间没有空格，逐字作为一个"词"，便于搜索高亮
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF      // 平假名 / 片假名
+        | 0x3400..=0x4DBF    // 扩展 A
+        | 0x4E00..=0x9FFF    // 基本汉字
+        | 0xAC00..=0xD7AF    // 韩文音节
+        | 0xF900..=0xFAFF    // 兼容汉字
+        | 0x20000..=0x2FA1F) // 扩展 B 及以后
+}
+
+/// 把一行内的字符（及其包围盒 [x0, y0, x1, y1]）按空白切分成词，包围盒取并集
+pub fn group_words(chars: impl IntoIterator<Item = (char, [f32; 4])>) -> Vec<PdfWord> {
+    let mut words = Vec::new();
+    let mut current: Option<PdfWord> = None;
+    for (c, [x0, y0, x1, y1]) in chars {
+        if c.is_whitespace() {
+            words.extend(current.take());
+            continue;
+        }
+        if is_cjk(c) {
+            words.extend(current.take());
+            words.push(PdfWord { text: c.to_string(), x0, y0, x1, y1 });
+            continue;
+        }
+        match current.as_mut() {
+            Some(w) => {
+                w.text.push(c);
+                w.x0 = w.x0.min(x0);
+                w.y0 = w.y0.min(y0);
+                w.x1 = w.x1.max(x1);
+                w.y1 = w.y1.max(y1);
+            }
+            None => current = Some(PdfWord { text: c.to_string(), x0, y0, x1, y1 }),
+        }
+    }
+    words.extend(current);
+    words
+}
+
+/// GET /api/preview/pdf/text?uuid=<uuid>&page=<n>
+/// 返回第 n 页的纯文本和每个词的包围盒（页面坐标，单位：点），客户端按渲染比例换算后做搜索高亮
+pub async fn get_pdf_text(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_pdf(&query)?;
+    let page = page_param(&query)?;
+
+    let response = tokio::task::spawn_blocking(move || {
+        let doc = open_document(&source_path)?;
+        let Some(pdf_page) = load_page(&doc, page)? else { return Ok(None) };
+        let bounds = pdf_page.bounds().map_err(|e| format!("无法获取页面尺寸: {}", e))?;
+        let text_page = pdf_page
+            .to_text_page(mupdf::TextPageFlags::empty())
+            .map_err(|e| format!("无法提取页面文本: {}", e))?;
+
+        let mut text = String::new();
+        let mut words = Vec::new();
+        for block in text_page.blocks() {
+            for line in block.lines() {
+                let chars: Vec<(char, [f32; 4])> = line
+                    .chars()
+                    .filter_map(|ch| {
+                        let r = mupdf::Rect::from(ch.quad());
+                        ch.char().map(|c| (c, [r.x0 - bounds.x0, r.y0 - bounds.y0, r.x1 - bounds.x0, r.y1 - bounds.y0]))
+                    })
+                    .collect();
+                text.extend(chars.iter().map(|(c, _)| *c));
+                text.push('\n');
+                words.extend(group_words(chars));
+            }
+        }
+
+        Ok::<_, String>(Some(PdfPageTextResponse {
+            page,
+            width: bounds.x1 - bounds.x0,
+            height: bounds.y1 - bounds.y0,
+            text,
+            words,
+        }))
+    })
+    .await;
+
+    Ok(HttpResponse::Ok().json(block_result(response)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_words() {
+        let boxed = |s: &str| s.chars().enumerate()
+            .map(|(i, c)| (c, [i as f32 * 10.0, 0.0, i as f32 * 10.0 + 8.0, 12.0]))
+            .collect::<Vec<_>>();
+        let words = group_words(boxed("Hi  you中文"));
+        let texts: Vec<&str> = words.iter().map(|w| w.text.as_str()).collect();
+        assert_eq!(texts, ["Hi", "you", "中", "文"]);
+        assert_eq!((words[0].x0, words[0].x1), (0.0, 18.0));
+        assert_eq!((words[1].x0, words[1].x1), (40.0, 68.0));
+        assert!(group_words(boxed("   ")).is_empty());
+    }
+}
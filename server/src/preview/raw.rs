@@ -0,0 +1,393 @@
+// 相机 RAW 预览：不解码传感器数据，取文件内嵌的最大 JPEG 预览图（相机直出效果）
+// DNG / CR2 / NEF / ARW / ORF 是 TIFF 结构，RAF 有自己的文件头，CR3 是 ISO BMFF；
+// 预览图的存放位置各家不同（IFD、SubIFD、MakerNote、uuid box），统一按 JPEG 标记扫描整个文件
+use actix_web::{web, HttpResponse, Result};
+use image::DynamicImage;
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::content::resolve_source;
+use super::exif;
+use super::models::{RawInfoResponse, RAW_EXTENSIONS};
+use super::thumbnail::{encode_image, ThumbFormat};
+
+/// 拍摄信息都在文件开头（TIFF IFD / RAF 头部 JPEG 的 EXIF / CR3 的 CMT box），只读前 1MB
+const HEAD_LIMIT: usize = 1024 * 1024;
+
+/// 方向校正后重新编码预览图的 JPEG 质量
+const PREVIEW_QUALITY: u8 = 90;
+
+/// 文件中的一段 JPEG
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct EmbeddedJpeg {
+    start: usize,
+    end: usize,
+    width: u16,
+    height: u16,
+    /// SOF 标记：C0 基线 / C1 扩展 / C2 渐进可以解码；C3 是 DNG / CR2 的无损 RAW 数据
+    sof: u8,
+}
+
+impl EmbeddedJpeg {
+    fn is_preview(&self) -> bool {
+        matches!(self.sof, 0xC0..=0xC2) && self.width > 0 && self.height > 0
+    }
+}
+
+/// 从 data[start..] 的 SOI 开始逐段解析一个 JPEG，返回其范围和 SOF 信息
+fn parse_jpeg(data: &[u8], start: usize) -> Option<EmbeddedJpeg> {
+    let be16 = |p: usize| -> Option<usize> { Some(u16::from_be_bytes(data.get(p..p + 2)?.try_into().ok()?) as usize) };
+    if data.get(start..start + 2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let (mut width, mut height, mut sof) = (0u16, 0u16, 0u8);
+    let mut pos = start + 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        // 标记前允许有填充的 0xFF
+        while *data.get(pos + 1)? == 0xFF {
+            pos += 1;
+        }
+        let marker = data[pos + 1];
+        match marker {
+            0xD9 => return Some(EmbeddedJpeg { start, end: pos + 2, width, height, sof }),
+            0x01 | 0xD0..=0xD7 => {
+                pos += 2;
+                continue;
+            }
+            // 不是合法标记，说明只是碰巧出现的 FF D8 FF
+            0x00 | 0x02..=0xBF => return None,
+            _ => {}
+        }
+        let len = be16(pos + 2)?;
+        if len < 2 {
+            return None;
+        }
+        if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            height = be16(pos + 5)? as u16;
+            width = be16(pos + 7)? as u16;
+            sof = marker;
+        }
+        pos += 2 + len;
+        if marker == 0xDA {
+            // 熵编码数据：0xFF 后跟 0x00（转义）或 RST 标记时仍属于数据
+            loop {
+                let next = pos + data.get(pos..)?.iter().position(|&b| b == 0xFF)?;
+                match *data.get(next + 1)? {
+                    0x00 | 0xD0..=0xD7 => pos = next + 2,
+                    _ => {
+                        pos = next;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 扫描文件中所有内嵌 JPEG（解析成功的整段跳过，内部的 EXIF 缩略图不会重复计入）
+fn scan_jpegs(data: &[u8]) -> Vec<EmbeddedJpeg> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while pos + 3 <= data.len() {
+        let Some(offset) = data[pos..].windows(3).position(|w| w == [0xFF, 0xD8, 0xFF]) else { break };
+        let start = pos + offset;
+        match parse_jpeg(data, start) {
+            Some(jpeg) => {
+                pos = jpeg.end;
+                found.push(jpeg);
+            }
+            None => pos = start + 2,
+        }
+    }
+    found
+}
+
+/// 读取 RAW 文件，返回最大的可解码预览 JPEG 和拍摄信息
+fn load_preview(path: &Path) -> std::result::Result<(Vec<u8>, RawInfoResponse), String> {
+    let data = std::fs::read(path).map_err(|e| format!("无法读取 RAW 文件: {}", e))?;
+    let info = read_capture_info(&data[..data.len().min(HEAD_LIMIT)]);
+    let preview = scan_jpegs(&data)
+        .into_iter()
+        .filter(EmbeddedJpeg::is_preview)
+        .max_by_key(|j| j.width as u32 * j.height as u32)
+        .ok_or("RAW 文件中没有内嵌预览图")?;
+    Ok((data[preview.start..preview.end].to_vec(), info))
+}
+
+/// 内嵌预览图解码并按 RAW 的方向校正（缩略图用）
+pub fn extract_raw_preview(path: &Path) -> Result<DynamicImage> {
+    let (jpeg, info) = load_preview(path).map_err(actix_web::error::ErrorInternalServerError)?;
+    let img = image::load_from_memory_with_format(&jpeg, image::ImageFormat::Jpeg)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法解码 RAW 预览图: {}", e)))?;
+    Ok(exif::apply_orientation(img, info.orientation))
+}
+
+/// 全尺寸预览 JPEG（serve_file 的 .transcoded 缓存用）：正常朝向时原样返回，不重新编码
+pub fn raw_preview_jpeg(path: &Path) -> std::result::Result<Vec<u8>, String> {
+    let (jpeg, info) = load_preview(path)?;
+    if info.orientation == 1 {
+        return Ok(jpeg);
+    }
+    let img = image::load_from_memory_with_format(&jpeg, image::ImageFormat::Jpeg)
+        .map_err(|e| format!("无法解码 RAW 预览图: {}", e))?;
+    encode_image(&exif::apply_orientation(img, info.orientation), ThumbFormat::Jpeg, Some(PREVIEW_QUALITY))
+        .map_err(|e| e.to_string())
+}
+
+/// TIFF 结构的只读视图
+struct Tiff<'a> {
+    data: &'a [u8],
+    little: bool,
+}
+
+impl<'a> Tiff<'a> {
+    /// 解析 TIFF 头；ORF / RW2 的魔数不是 42，这里不校验
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little = match data.get(0..2)? {
+            b"II" => true,
+            b"MM" => false,
+            _ => return None,
+        };
+        Some(Tiff { data, little })
+    }
+
+    fn u16_at(&self, p: usize) -> Option<u16> {
+        let b: [u8; 2] = self.data.get(p..p + 2)?.try_into().ok()?;
+        Some(if self.little { u16::from_le_bytes(b) } else { u16::from_be_bytes(b) })
+    }
+
+    fn u32_at(&self, p: usize) -> Option<u32> {
+        let b: [u8; 4] = self.data.get(p..p + 4)?.try_into().ok()?;
+        Some(if self.little { u32::from_le_bytes(b) } else { u32::from_be_bytes(b) })
+    }
+
+    fn first_ifd(&self) -> Option<usize> {
+        self.u32_at(4).map(|o| o as usize)
+    }
+
+    /// 按条目类型读取整数值（SHORT / LONG）
+    fn int_value(&self, entry: usize) -> Option<u32> {
+        match self.u16_at(entry + 2)? {
+            3 => self.u16_at(entry + 8).map(u32::from),
+            4 => self.u32_at(entry + 8),
+            _ => None,
+        }
+    }
+
+    /// ASCII 值：不超过 4 字节时内联在条目里，否则为偏移
+    fn ascii_value(&self, entry: usize) -> Option<String> {
+        if self.u16_at(entry + 2)? != 2 {
+            return None;
+        }
+        let count = self.u32_at(entry + 4)? as usize;
+        let start = if count <= 4 { entry + 8 } else { self.u32_at(entry + 8)? as usize };
+        let raw = self.data.get(start..start + count)?;
+        let text = String::from_utf8_lossy(raw.split(|&b| b == 0).next().unwrap_or(raw)).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// RATIONAL / SRATIONAL 值，返回 (分子, 分母)
+    fn rational_value(&self, entry: usize) -> Option<(u32, u32)> {
+        if !matches!(self.u16_at(entry + 2)?, 5 | 10) {
+            return None;
+        }
+        let offset = self.u32_at(entry + 8)? as usize;
+        Some((self.u32_at(offset)?, self.u32_at(offset + 4)?))
+    }
+}
+
+/// 读取一个 IFD 中的拍摄信息字段，返回 EXIF 子 IFD 的偏移（如有）
+fn collect_ifd(tiff: &Tiff, ifd: usize, info: &mut RawInfoResponse) -> Option<usize> {
+    let mut exif_ifd = None;
+    let count = tiff.u16_at(ifd)? as usize;
+    for i in 0..count {
+        let entry = ifd + 2 + i * 12;
+        let Some(tag) = tiff.u16_at(entry) else { break };
+        match tag {
+            0x010F => info.make = info.make.take().or_else(|| tiff.ascii_value(entry)),
+            0x0110 => info.model = info.model.take().or_else(|| tiff.ascii_value(entry)),
+            0x0112 => {
+                if let Some(o) = tiff.int_value(entry).filter(|o| (1..=8).contains(o)) {
+                    info.orientation = o as u16;
+                }
+            }
+            // DateTime 只在没有 DateTimeOriginal 时使用
+            0x0132 if info.taken_at.is_none() => info.taken_at = tiff.ascii_value(entry).and_then(|s| format_exif_time(&s)),
+            0x9003 => info.taken_at = tiff.ascii_value(entry).and_then(|s| format_exif_time(&s)).or(info.taken_at.take()),
+            0x8769 => exif_ifd = tiff.int_value(entry).map(|o| o as usize),
+            0x829A => info.exposure_time = tiff.rational_value(entry).and_then(format_exposure),
+            0x829D => info.f_number = tiff.rational_value(entry).and_then(rational_to_f32),
+            0x8827 => info.iso = tiff.int_value(entry).filter(|v| *v > 0),
+            0x920A => info.focal_length = tiff.rational_value(entry).and_then(rational_to_f32),
+            0xA434 => info.lens = tiff.ascii_value(entry),
+            _ => {}
+        }
+    }
+    exif_ifd
+}
+
+/// 从文件头部读取拍摄信息
+fn read_capture_info(head: &[u8]) -> RawInfoResponse {
+    let mut info = RawInfoResponse { orientation: 1, ..Default::default() };
+
+    // 读取 IFD0，并跟随 ExifIFD 指针
+    let read_tiff = |data: &[u8], info: &mut RawInfoResponse| {
+        let Some(tiff) = Tiff::new(data) else { return };
+        if let Some(exif_ifd) = tiff.first_ifd().and_then(|ifd| collect_ifd(&tiff, ifd, info)) {
+            collect_ifd(&tiff, exif_ifd, info);
+        }
+    };
+
+    if head.starts_with(b"FUJIFILMCCD-RAW") {
+        // RAF：偏移 84 处为内嵌 JPEG 的位置，拍摄信息在该 JPEG 的 EXIF 中
+        let jpeg_offset = head.get(84..88)
+            .and_then(|b| b.try_into().ok())
+            .map(|b| u32::from_be_bytes(b) as usize);
+        if let Some(tiff) = jpeg_offset.and_then(|o| head.get(o..)).and_then(exif::find_tiff) {
+            read_tiff(tiff, &mut info);
+        }
+    } else if head.get(4..8) == Some(b"ftyp") && head.get(8..12) == Some(b"crx ") {
+        // CR3：CMT1 box 是 IFD0，CMT2 box 是 EXIF IFD，都是完整的 TIFF 结构
+        for name in [b"CMT1", b"CMT2"] {
+            if let Some(pos) = head.windows(4).position(|w| w == name) {
+                read_tiff(&head[pos + 4..], &mut info);
+            }
+        }
+    } else {
+        read_tiff(head, &mut info);
+    }
+    info
+}
+
+fn rational_to_f32((num, den): (u32, u32)) -> Option<f32> {
+    (den != 0 && num != 0).then(|| ((num as f32 / den as f32) * 10.0).round() / 10.0)
+}
+
+/// 快门速度：不足 1 秒写成 1/N，否则写秒数
+fn format_exposure((num, den): (u32, u32)) -> Option<String> {
+    if num == 0 || den == 0 {
+        return None;
+    }
+    let seconds = num as f64 / den as f64;
+    Some(if seconds < 1.0 {
+        format!("1/{}", (1.0 / seconds).round())
+    } else {
+        format!("{}", (seconds * 10.0).round() / 10.0)
+    })
+}
+
+/// "2024:05:01 12:00:00" → "2024-05-01 12:00:00"
+fn format_exif_time(s: &str) -> Option<String> {
+    let (date, time) = s.split_once(' ')?;
+    if date.len() != 10 || time.len() < 8 || date.starts_with("0000") {
+        return None;
+    }
+    Some(format!("{} {}", date.replace(':', "-"), &time[..8]))
+}
+
+/// GET /api/preview/raw/info?uuid=<uuid> 或 ?path=<file_path>
+/// 返回相机 RAW 的拍摄信息（机身、镜头、ISO、快门、光圈、焦距、拍摄时间、方向）
+pub async fn get_raw_info(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let source_path = resolve_source(&query)?;
+    if !source_path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let extension = source_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !RAW_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest("仅支持相机 RAW 文件"));
+    }
+
+    let info = tokio::task::spawn_blocking(move || {
+        use std::io::Read;
+        let file = std::fs::File::open(&source_path).map_err(|e| format!("无法读取 RAW 文件: {}", e))?;
+        let mut head = Vec::new();
+        file.take(HEAD_LIMIT as u64)
+            .read_to_end(&mut head)
+            .map_err(|e| format!("无法读取 RAW 文件: {}", e))?;
+        Ok::<_, String>(read_capture_info(&head))
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().json(info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jpeg(width: u16, height: u16, sof: u8) -> Vec<u8> {
+        let mut j = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00];
+        j.extend_from_slice(&[0xFF, sof, 0x00, 0x0B, 0x08]);
+        j.extend_from_slice(&height.to_be_bytes());
+        j.extend_from_slice(&width.to_be_bytes());
+        j.extend_from_slice(&[0x01, 0x01, 0x11, 0x00]);
+        j.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00]);
+        // 熵编码数据里的转义 0xFF00 和 RST 标记
+        j.extend_from_slice(&[0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD3, 0x56]);
+        j.extend_from_slice(&[0xFF, 0xD9]);
+        j
+    }
+
+    #[test]
+    fn test_scan_jpegs_picks_largest_preview() {
+        let mut data = b"II*\0junk".to_vec();
+        let small = data.len();
+        data.extend(jpeg(160, 120, 0xC0));
+        data.extend_from_slice(&[0xFF, 0xD8, 0xFF, 0x00, 0x00]); // 不完整的假 SOI
+        data.extend(jpeg(6000, 4000, 0xC3)); // 无损 RAW 数据
+        let large = data.len();
+        data.extend(jpeg(1620, 1080, 0xC2));
+        data.extend_from_slice(b"tail");
+
+        let found = scan_jpegs(&data);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].start, small);
+        let best = found.into_iter().filter(EmbeddedJpeg::is_preview).max_by_key(|j| j.width as u32 * j.height as u32).unwrap();
+        assert_eq!((best.start, best.width, best.height), (large, 1620, 1080));
+        assert_eq!(best.end, large + jpeg(1620, 1080, 0xC2).len());
+    }
+
+    #[test]
+    fn test_read_capture_info() {
+        // 大端 TIFF：IFD0 (Make, Orientation, ExifIFD) → EXIF IFD (ExposureTime, ISO, DateTimeOriginal)
+        let mut t = b"MM\0\x2a\0\0\0\x08".to_vec();
+        let entry = |t: &mut Vec<u8>, tag: u16, kind: u16, count: u32, value: u32| {
+            t.extend_from_slice(&tag.to_be_bytes());
+            t.extend_from_slice(&kind.to_be_bytes());
+            t.extend_from_slice(&count.to_be_bytes());
+            t.extend_from_slice(&value.to_be_bytes());
+        };
+        // IFD0 在 8，3 项 → 下一个 IFD 偏移之后为 50
+        t.extend_from_slice(&3u16.to_be_bytes());
+        entry(&mut t, 0x010F, 2, 4, u32::from_be_bytes(*b"NIK\0"));
+        entry(&mut t, 0x0112, 3, 1, 6 << 16);
+        entry(&mut t, 0x8769, 4, 1, 50);
+        t.extend_from_slice(&0u32.to_be_bytes());
+        // EXIF IFD 在 50，3 项 → 数据区从 92 开始
+        t.extend_from_slice(&3u16.to_be_bytes());
+        entry(&mut t, 0x829A, 5, 1, 92);
+        entry(&mut t, 0x8827, 3, 1, 400 << 16);
+        entry(&mut t, 0x9003, 2, 20, 100);
+        t.extend_from_slice(&0u32.to_be_bytes());
+        t.extend_from_slice(&10u32.to_be_bytes());
+        t.extend_from_slice(&2500u32.to_be_bytes());
+        t.extend_from_slice(b"2024:05:01 12:34:56\0");
+
+        let info = read_capture_info(&t);
+        assert_eq!(info.make.as_deref(), Some("NIK"));
+        assert_eq!(info.orientation, 6);
+        assert_eq!(info.exposure_time.as_deref(), Some("1/250"));
+        assert_eq!(info.iso, Some(400));
+        assert_eq!(info.taken_at.as_deref(), Some("2024-05-01 12:34:56"));
+        assert_eq!(info.lens, None);
+    }
+}
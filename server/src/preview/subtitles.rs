@@ -0,0 +1,416 @@
+// 字幕轨: 内嵌字幕流 (MKV/MP4) + 同名外挂字幕 (.srt/.ass/.ssa/.vtt), 统一转成 WebVTT 输出
+//
+// - 直接播放: 客户端先拉 /api/preview/subtitles 拿到轨道列表, 再按需加载 <track>
+// - HLS: master playlist 里以 EXT-X-MEDIA 字幕组引用 (见 content::build_master_m3u8)
+// - 转换结果缓存在源文件同级 .transcoded/ 下, 按 mtime 判断是否有效
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::content::{encode_key, get_probe_cached, is_cache_fresh, resolve_source, MediaStream};
+use super::models::{SubtitleTrackInfo, SubtitleTracksResponse, VIDEO_EXTENSIONS};
+use super::utils::get_ffmpeg_path;
+
+/// 支持的外挂字幕格式
+const SIDECAR_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "vtt"];
+
+/// 图形字幕 (PGS / VobSub 等) 无法转成 WebVTT, 不对外暴露
+const BITMAP_SUBTITLE_CODECS: &[&str] = &["hdmv_pgs_subtitle", "dvd_subtitle", "dvb_subtitle", "xsub"];
+
+enum TrackSource {
+    Embedded(u32),
+    Sidecar(PathBuf),
+}
+
+struct SubtitleTrack {
+    /// 内嵌: `e<流序号>`; 外挂: `s<按文件名排序的序号>`
+    id: String,
+    source: TrackSource,
+    language: Option<String>,
+    title: Option<String>,
+    codec: String,
+    default: bool,
+}
+
+/// 同目录下与视频同名的外挂字幕: `clip.srt` / `clip.en.srt` / `clip.chs.ass` ...
+/// 返回 (路径, 文件名中间段作为语言)
+fn find_sidecars(source_path: &Path) -> Vec<(PathBuf, Option<String>)> {
+    let (Some(dir), Some(stem)) = (source_path.parent(), source_path.file_stem().and_then(|s| s.to_str())) else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.", stem);
+    let mut sidecars: Vec<(PathBuf, Option<String>)> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .filter_map(|p| {
+            let name = p.file_name()?.to_str()?;
+            let rest = name.strip_prefix(&prefix)?;
+            let ext = p.extension()?.to_str()?.to_lowercase();
+            if !SIDECAR_EXTENSIONS.contains(&ext.as_str()) {
+                return None;
+            }
+            let middle = &rest[..rest.len() - ext.len() - 1];
+            let language = (!middle.is_empty()).then(|| middle.to_string());
+            Some((p, language))
+        })
+        .collect();
+    sidecars.sort();
+    sidecars
+}
+
+/// 列出视频的全部可用字幕轨 (内嵌在前, 外挂在后)
+fn list_tracks(source_path: &Path) -> Vec<SubtitleTrack> {
+    let mut tracks = embedded_tracks(get_probe_cached(source_path).map(|p| p.subtitles).unwrap_or_default());
+    for (i, (path, language)) in find_sidecars(source_path).into_iter().enumerate() {
+        let codec = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+        tracks.push(SubtitleTrack {
+            id: format!("s{}", i),
+            source: TrackSource::Sidecar(path),
+            language,
+            title: None,
+            codec,
+            default: false,
+        });
+    }
+    tracks
+}
+
+/// 探测到的内嵌字幕流 → 字幕轨, 去掉图形字幕
+fn embedded_tracks(streams: Vec<MediaStream>) -> Vec<SubtitleTrack> {
+    streams
+        .into_iter()
+        .filter(|s| !BITMAP_SUBTITLE_CODECS.contains(&s.codec.as_str()))
+        .map(|s| SubtitleTrack {
+            id: format!("e{}", s.index),
+            source: TrackSource::Embedded(s.index),
+            language: s.language,
+            title: s.title,
+            codec: s.codec,
+            default: s.default,
+        })
+        .collect()
+}
+
+impl SubtitleTrack {
+    /// 播放器菜单里显示的名称
+    fn display_name(&self, fallback_index: usize) -> String {
+        self.title
+            .clone()
+            .or_else(|| self.language.clone())
+            .unwrap_or_else(|| format!("Track {}", fallback_index + 1))
+    }
+}
+
+/// 把指定字幕轨转成 WebVTT 并缓存, 返回缓存路径
+fn ensure_vtt(source_path: &Path, track: &SubtitleTrack) -> std::result::Result<PathBuf, String> {
+    let parent = source_path.parent().ok_or("无法获取文件所在目录")?;
+    let transcode_dir = parent.join(".transcoded");
+    let stem = source_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+
+    // 外挂字幕的序号会随同目录增删字幕文件而变化, 缓存按字幕文件名命名
+    let (input, input_args, map_args, cache_path) = match &track.source {
+        TrackSource::Embedded(index) => (
+            source_path.to_path_buf(),
+            Vec::new(),
+            vec!["-map".to_string(), format!("0:{}", index)],
+            transcode_dir.join(format!("{}_sub_{}.vtt", stem, track.id)),
+        ),
+        TrackSource::Sidecar(path) => {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("sidecar");
+            (path.clone(), sidecar_charset_args(path), Vec::new(), transcode_dir.join(format!("{}.vtt", name)))
+        }
+    };
+    if cache_path.exists() && is_cache_fresh(&input, &cache_path) {
+        return Ok(cache_path);
+    }
+
+    std::fs::create_dir_all(&transcode_dir).map_err(|e| format!("无法创建转码目录: {}", e))?;
+    let input_str = input.to_str().ok_or("源路径含非 UTF-8")?;
+    let output = std::process::Command::new(get_ffmpeg_path())
+        .args(["-hide_banner", "-loglevel", "error"])
+        .args(&input_args)
+        .args(["-i", input_str])
+        .args(&map_args)
+        .args(["-c:s", "webvtt", "-f", "webvtt", "pipe:1"])
+        .output()
+        .map_err(|e| format!("ffmpeg 启动失败: {}", e))?;
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(format!("字幕转换失败: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    let vtt = add_timestamp_map(&String::from_utf8_lossy(&output.stdout));
+    // 同一轨道的并发请求各写各的临时文件, rename 保证读到的缓存是完整的
+    let tmp = transcode_dir.join(format!(".{}.vtt.tmp", uuid::Uuid::new_v4()));
+    std::fs::write(&tmp, vtt).map_err(|e| format!("无法写入字幕缓存: {}", e))?;
+    std::fs::rename(&tmp, &cache_path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        format!("无法写入字幕缓存: {}", e)
+    })?;
+    Ok(cache_path)
+}
+
+/// 外挂字幕常是 GBK / Big5 / Shift_JIS 等旧编码, ffmpeg 默认按 UTF-8 读会乱码:
+/// 检测字符集, 不是 UTF-8 时通过 -sub_charenc 告诉 ffmpeg
+fn sidecar_charset_args(path: &Path) -> Vec<String> {
+    let Ok(data) = std::fs::read(path) else {
+        return Vec::new();
+    };
+    sub_charenc_args(&data)
+}
+
+fn sub_charenc_args(data: &[u8]) -> Vec<String> {
+    let (encoding, _) = crate::charset::detect_encoding(data, true);
+    if encoding == encoding_rs::UTF_8 {
+        Vec::new()
+    } else {
+        vec!["-sub_charenc".to_string(), encoding.name().to_string()]
+    }
+}
+
+/// 在 WEBVTT 头部加上 X-TIMESTAMP-MAP, HLS 播放器据此把 cue 时间对齐到切片的 MPEG-TS 时间轴
+/// (切片用 -output_ts_offset 输出连续时间戳, 0 秒对应 PTS 0). 浏览器 <track> 会忽略这一行.
+fn add_timestamp_map(vtt: &str) -> String {
+    let body = vtt.trim_start_matches('\u{feff}');
+    let (header, rest) = body.split_once('\n').unwrap_or((body, ""));
+    format!("{}\nX-TIMESTAMP-MAP=MPEGTS:0,LOCAL:00:00:00.000\n{}", header.trim_end(), rest)
+}
+
+/// 校验源文件为视频, 返回路径
+fn resolve_video(query: &HashMap<String, String>) -> Result<PathBuf> {
+    let source_path = resolve_source(query)?;
+    if !source_path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let extension = source_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest("仅支持视频文件"));
+    }
+    Ok(source_path)
+}
+
+/// 字幕 WebVTT 的访问 URL (`?key` 透传, 与 m3u8 / sprite 一致)
+fn subtitle_url(source_param: &str, track_id: &str, key: Option<&str>) -> String {
+    let key_query = key.map(|k| format!("&key={}", encode_key(k))).unwrap_or_default();
+    format!("/api/preview/subtitle.vtt?{}&track={}{}", source_param, track_id, key_query)
+}
+
+/// master playlist 中一条 EXT-X-MEDIA 字幕轨
+pub(super) struct HlsSubtitle {
+    pub id: String,
+    pub name: String,
+    pub language: Option<String>,
+    pub default: bool,
+}
+
+pub(super) fn hls_subtitles(source_path: &Path) -> Vec<HlsSubtitle> {
+    list_tracks(source_path)
+        .iter()
+        .enumerate()
+        .map(|(i, t)| HlsSubtitle {
+            id: t.id.clone(),
+            name: t.display_name(i),
+            language: t.language.as_deref().and_then(hls_language),
+            default: t.default,
+        })
+        .collect()
+}
+
+/// HLS 的 LANGUAGE 要求 RFC 5646 标签: ffprobe 给的是 ISO 639-2 (`chi` / `eng`),
+/// 外挂字幕文件名里常见 `chs` / `zh-CN` 之类; 认不出的返回 None, 不写 LANGUAGE
+fn hls_language(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_ascii_lowercase();
+    let mapped = match tag.as_str() {
+        "chi" | "zho" | "chn" => "zh",
+        "chs" | "sc" | "zh-hans" | "zh-cn" | "zh-sg" => "zh-Hans",
+        "cht" | "tc" | "zh-hant" | "zh-tw" | "zh-hk" => "zh-Hant",
+        "eng" => "en",
+        "jpn" | "jap" => "ja",
+        "kor" => "ko",
+        "fre" | "fra" => "fr",
+        "ger" | "deu" => "de",
+        "spa" => "es",
+        "por" => "pt",
+        "ita" => "it",
+        "rus" => "ru",
+        "ara" => "ar",
+        "tha" => "th",
+        "vie" => "vi",
+        "ind" => "id",
+        "may" | "msa" => "ms",
+        "dut" | "nld" => "nl",
+        "pol" => "pl",
+        "tur" => "tr",
+        "ukr" => "uk",
+        "hin" => "hi",
+        "swe" => "sv",
+        // 已经是两字母语言代码 (可带地区), 原样保留
+        t if t.len() == 2 && t.chars().all(|c| c.is_ascii_lowercase()) => return Some(t.to_string()),
+        t if t.len() == 5 && t.as_bytes()[2] == b'-' && t[..2].chars().all(|c| c.is_ascii_lowercase())
+            && t[3..].chars().all(|c| c.is_ascii_alphabetic()) =>
+        {
+            return Some(format!("{}-{}", &t[..2], t[3..].to_ascii_uppercase()));
+        }
+        _ => return None,
+    };
+    Some(mapped.to_string())
+}
+
+/// 单条字幕轨的 HLS media playlist: 整条 WebVTT 作为一个切片
+pub(super) fn build_subtitle_m3u8(source_path: &Path, duration: f64, track_id: &str, key: Option<&str>) -> String {
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+    let source_param = format!(
+        "path={}",
+        utf8_percent_encode(&source_path.to_string_lossy(), NON_ALPHANUMERIC)
+    );
+    let mut m3u8 = String::from("#EXTM3U\n#EXT-X-VERSION:3\n");
+    m3u8.push_str(&format!("#EXT-X-TARGETDURATION:{}\n", duration.ceil().max(1.0) as u64));
+    m3u8.push_str("#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n");
+    m3u8.push_str(&format!("#EXTINF:{:.3},\n", duration));
+    m3u8.push_str(&subtitle_url(&source_param, track_id, key));
+    m3u8.push_str("\n#EXT-X-ENDLIST\n");
+    m3u8
+}
+
+/// 轨道 ID 是否存在 (serve_file 处理 hls_subs 前校验)
+pub(super) fn has_track(source_path: &Path, track_id: &str) -> bool {
+    list_tracks(source_path).iter().any(|t| t.id == track_id)
+}
+
+/// GET /api/preview/subtitles?uuid=<uuid>
+/// GET /api/preview/subtitles?path=<file_path>
+///
+/// 列出视频的字幕轨 (内嵌文本字幕 + 同名外挂字幕), 每条附带 WebVTT 地址
+pub async fn list_subtitles(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+    let source_path = resolve_video(&query)?;
+    let tracks = web::block(move || list_tracks(&source_path))
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+
+    let source_param = match query.get("uuid") {
+        Some(uuid) => format!("uuid={}", utf8_percent_encode(uuid, NON_ALPHANUMERIC)),
+        None => format!("path={}", utf8_percent_encode(query.get("path").map(|s| s.as_str()).unwrap_or(""), NON_ALPHANUMERIC)),
+    };
+    let key = query.get("key").map(|s| s.as_str());
+    let tracks = tracks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| SubtitleTrackInfo {
+            id: t.id.clone(),
+            kind: match t.source {
+                TrackSource::Embedded(_) => "embedded".to_string(),
+                TrackSource::Sidecar(_) => "sidecar".to_string(),
+            },
+            name: t.display_name(i),
+            language: t.language.clone(),
+            codec: t.codec.clone(),
+            default: t.default,
+            url: subtitle_url(&source_param, &t.id, key),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(SubtitleTracksResponse { tracks }))
+}
+
+/// GET /api/preview/subtitle.vtt?uuid=<uuid>&track=<id>
+/// GET /api/preview/subtitle.vtt?path=<file_path>&track=<id>
+///
+/// 返回转换为 WebVTT 的字幕轨
+pub async fn serve_subtitle(
+    query: web::Query<HashMap<String, String>>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let source_path = resolve_video(&query)?;
+    let track_id = query
+        .get("track")
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("缺少 track 参数"))?
+        .clone();
+
+    let vtt = tokio::task::spawn_blocking(move || {
+        let track = list_tracks(&source_path)
+            .into_iter()
+            .find(|t| t.id == track_id)
+            .ok_or(None)?;
+        ensure_vtt(&source_path, &track).map_err(Some)
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+    .map_err(|e| match e {
+        None => actix_web::error::ErrorNotFound("字幕轨不存在"),
+        Some(msg) => actix_web::error::ErrorInternalServerError(msg),
+    })?;
+
+    let f = actix_files::NamedFile::open_async(&vtt)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("打开文件失败: {}", e)))?
+        .set_content_type("text/vtt; charset=utf-8".parse().unwrap());
+    Ok(f.into_response(&req))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preview::content::parse_streams;
+
+    #[test]
+    fn test_parse_subtitle_streams() {
+        let stderr = "\
+Input #0, matroska,webm, from 'a.mkv':
+  Duration: 00:24:00.02, start: 0.000000, bitrate: 2000 kb/s
+  Stream #0:0: Video: h264 (High), yuv420p(progressive), 1920x1080, 23.98 fps
+  Stream #0:1(jpn): Audio: aac (LC), 48000 Hz, stereo, fltp (default)
+  Stream #0:2(chi): Subtitle: ass (default)
+    Metadata:
+      title           : 简体中文
+  Stream #0:3(eng): Subtitle: hdmv_pgs_subtitle
+    Metadata:
+      title           : English PGS
+  Stream #0:4(und): Subtitle: subrip
+";
+        let tracks = embedded_tracks(parse_streams(stderr, "Subtitle"));
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].id, "e2");
+        assert!(matches!(tracks[0].source, TrackSource::Embedded(2)));
+        assert_eq!(tracks[0].language.as_deref(), Some("chi"));
+        assert_eq!(tracks[0].title.as_deref(), Some("简体中文"));
+        assert_eq!(tracks[0].codec, "ass");
+        assert!(tracks[0].default);
+        // PGS 图形字幕被过滤
+        assert_eq!(tracks[1].id, "e4");
+        assert_eq!(tracks[1].language, None);
+        assert_eq!(tracks[1].codec, "subrip");
+        assert!(!tracks[1].default);
+    }
+
+    #[test]
+    fn test_hls_language() {
+        assert_eq!(hls_language("chi").as_deref(), Some("zh"));
+        assert_eq!(hls_language("eng").as_deref(), Some("en"));
+        assert_eq!(hls_language("chs").as_deref(), Some("zh-Hans"));
+        assert_eq!(hls_language("ja").as_deref(), Some("ja"));
+        assert_eq!(hls_language("pt-br").as_deref(), Some("pt-BR"));
+        assert_eq!(hls_language("xyz"), None);
+        assert_eq!(hls_language("forced"), None);
+    }
+
+    #[test]
+    fn test_sub_charenc_args() {
+        let srt = "1\n00:00:01,000 --> 00:00:03,000\n这是一段用于检测字符集的中文字幕，包含常见的汉字。\n";
+        assert!(sub_charenc_args(srt.as_bytes()).is_empty());
+        let (gbk, _, _) = encoding_rs::GBK.encode(srt);
+        assert_eq!(sub_charenc_args(&gbk), ["-sub_charenc", "GBK"]);
+    }
+}
@@ -0,0 +1,373 @@
+// 文本文档预览：检测字符集并转为 UTF-8，Markdown 渲染为净化后的 HTML，大文件按行分页
+//
+// 归档里的旧文本常见 GBK / Shift_JIS / UTF-16，直接按原始字节返回会乱码。
+// 字符集检测见 crate::charset（BOM → 无 BOM 的 UTF-16 → chardetng）；也可用 encoding 参数指定。
+// 分页时每 CHECKPOINT_LINES 行记一个字节偏移，翻到任意页只需从最近的检查点往后读，索引按 (路径, mtime, 大小) 缓存在内存中
+use actix_web::{web, HttpResponse, Result};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use super::content::resolve_source;
+use super::models::{TextPreviewResponse, DOCUMENT_EXTENSIONS};
+use crate::charset::detect_encoding;
+
+/// 字符集检测读取的字节数
+const SNIFF_BYTES: usize = 64 * 1024;
+
+/// 每页行数：默认 / 上限
+const DEFAULT_PAGE_LINES: usize = 1000;
+const MAX_PAGE_LINES: usize = 5000;
+
+/// 行索引的检查点间隔
+const CHECKPOINT_LINES: usize = 100;
+
+/// 内存中缓存的行索引个数
+const INDEX_CACHE_SIZE: usize = 16;
+
+/// 超过此大小的 Markdown 不渲染，按纯文本分页
+const MARKDOWN_MAX_BYTES: u64 = 4 * 1024 * 1024;
+
+/// 缩略图取前若干行，每行截断到若干字符
+const THUMBNAIL_LINES: usize = 40;
+const THUMBNAIL_LINE_CHARS: usize = 120;
+
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
+const PLAIN_EXTENSIONS: &[&str] = &["txt", "text", "log", "nfo"];
+
+/// 换行符在字节流中的形式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Newline {
+    Byte,      // UTF-8 及 GBK / Shift_JIS 等 ASCII 兼容编码：0x0A 不会出现在多字节字符中
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Newline {
+    fn for_encoding(encoding: &'static Encoding) -> Self {
+        if encoding == UTF_16LE {
+            Newline::Utf16Le
+        } else if encoding == UTF_16BE {
+            Newline::Utf16Be
+        } else {
+            Newline::Byte
+        }
+    }
+}
+
+/// 读一行原始字节（含换行符）追加到 out，返回读取的字节数，0 表示已到末尾
+fn read_line_raw(reader: &mut impl BufRead, newline: Newline, out: &mut Vec<u8>) -> std::io::Result<usize> {
+    if newline == Newline::Byte {
+        return reader.read_until(b'\n', out);
+    }
+    let target = if newline == Newline::Utf16Le { [b'\n', 0] } else { [0, b'\n'] };
+    let mut unit = [0u8; 2];
+    let mut read = 0;
+    loop {
+        match reader.read_exact(&mut unit) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(read),
+            Err(e) => return Err(e),
+        }
+        out.extend_from_slice(&unit);
+        read += 2;
+        if unit == target {
+            return Ok(read);
+        }
+    }
+}
+
+/// 读取开头一段并确定编码：指定了 encoding 时只剥掉与之相符的 BOM
+fn open_with_encoding(path: &Path, label: Option<&str>) -> std::result::Result<(&'static Encoding, usize, u64), String> {
+    let mut file = File::open(path).map_err(|e| format!("无法打开文件: {}", e))?;
+    let size = file.metadata().map_err(|e| format!("无法读取文件信息: {}", e))?.len();
+    let mut sample = Vec::with_capacity(SNIFF_BYTES);
+    file.by_ref().take(SNIFF_BYTES as u64).read_to_end(&mut sample)
+        .map_err(|e| format!("读取文件失败: {}", e))?;
+
+    match label {
+        Some(label) => {
+            let encoding = Encoding::for_label(label.trim().as_bytes())
+                .ok_or_else(|| format!("不支持的字符集: {}", label))?;
+            let bom_len = match Encoding::for_bom(&sample) {
+                Some((bom_encoding, len)) if bom_encoding == encoding => len,
+                _ => 0,
+            };
+            Ok((encoding, bom_len, size))
+        }
+        None => {
+            let (encoding, bom_len) = detect_encoding(&sample, sample.len() as u64 == size);
+            Ok((encoding, bom_len, size))
+        }
+    }
+}
+
+/// 行索引：checkpoints[i] 为第 i * CHECKPOINT_LINES 行（从 0 开始）的起始字节偏移
+struct LineIndex {
+    checkpoints: Vec<u64>,
+    total_lines: usize,
+}
+
+fn build_index(path: &Path, newline: Newline, bom_len: usize) -> std::io::Result<LineIndex> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(bom_len as u64))?;
+    let mut reader = BufReader::with_capacity(1 << 20, file);
+    let mut checkpoints = vec![bom_len as u64];
+    let mut offset = bom_len as u64;
+    let mut total_lines = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let n = read_line_raw(&mut reader, newline, &mut line)?;
+        if n == 0 {
+            break;
+        }
+        offset += n as u64;
+        total_lines += 1;
+        if total_lines % CHECKPOINT_LINES == 0 {
+            checkpoints.push(offset);
+        }
+    }
+    Ok(LineIndex { checkpoints, total_lines })
+}
+
+type IndexKey = (PathBuf, &'static str, SystemTime, u64);
+type IndexCache = Mutex<Vec<(IndexKey, Arc<LineIndex>)>>;
+
+/// 取（或建立）行索引；最近使用的排在最后，超出容量时淘汰最前面的
+fn line_index(path: &Path, encoding: &'static Encoding, bom_len: usize) -> std::io::Result<Arc<LineIndex>> {
+    static CACHE: OnceLock<IndexCache> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| Mutex::new(Vec::new()));
+
+    let metadata = std::fs::metadata(path)?;
+    let key: IndexKey = (path.to_path_buf(), encoding.name(), metadata.modified()?, metadata.len());
+    {
+        let mut cache = cache.lock().unwrap();
+        if let Some(pos) = cache.iter().position(|(k, _)| *k == key) {
+            let entry = cache.remove(pos);
+            let index = entry.1.clone();
+            cache.push(entry);
+            return Ok(index);
+        }
+    }
+
+    // 建索引要读完整个文件，不持锁
+    let index = Arc::new(build_index(path, Newline::for_encoding(encoding), bom_len)?);
+    let mut cache = cache.lock().unwrap();
+    cache.retain(|(k, _)| k.0 != key.0);
+    if cache.len() >= INDEX_CACHE_SIZE {
+        cache.remove(0);
+    }
+    cache.push((key, index.clone()));
+    Ok(index)
+}
+
+/// 读取第 start 行起的 count 行（原始字节）
+fn read_lines(path: &Path, newline: Newline, index: &LineIndex, start: usize, count: usize) -> std::io::Result<Vec<u8>> {
+    let checkpoint = start / CHECKPOINT_LINES;
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(index.checkpoints[checkpoint]))?;
+    let mut reader = BufReader::new(file);
+
+    let mut skipped = Vec::new();
+    for _ in 0..start % CHECKPOINT_LINES {
+        skipped.clear();
+        if read_line_raw(&mut reader, newline, &mut skipped)? == 0 {
+            break;
+        }
+    }
+    let mut out = Vec::new();
+    for _ in 0..count {
+        if read_line_raw(&mut reader, newline, &mut out)? == 0 {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+/// 按编码转为 UTF-8，统一换行为 \n
+fn decode(bytes: &[u8], encoding: &'static Encoding) -> String {
+    let (text, _) = encoding.decode_without_bom_handling(bytes);
+    if text.contains('\r') {
+        text.replace("\r\n", "\n")
+    } else {
+        text.into_owned()
+    }
+}
+
+/// Markdown → HTML，再用 ammonia 去掉脚本、事件属性和 javascript: 链接等
+pub fn render_markdown(source: &str) -> String {
+    use pulldown_cmark::{html, Options, Parser};
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(source, options));
+    ammonia::clean(&unsafe_html)
+}
+
+/// 文档类别：markdown / text / code（code 由前端按 language 做语法高亮）
+fn document_kind(extension: &str) -> &'static str {
+    if MARKDOWN_EXTENSIONS.contains(&extension) {
+        "markdown"
+    } else if PLAIN_EXTENSIONS.contains(&extension) {
+        "text"
+    } else {
+        "code"
+    }
+}
+
+fn build_preview(
+    path: &Path,
+    extension: &str,
+    label: Option<&str>,
+    page: usize,
+    page_lines: usize,
+) -> Result<TextPreviewResponse> {
+    let (encoding, bom_len, size) = open_with_encoding(path, label)
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let mut kind = document_kind(extension);
+    if kind == "markdown" && size > MARKDOWN_MAX_BYTES {
+        kind = "text";
+    }
+
+    // Markdown 整篇渲染，不分页
+    if kind == "markdown" {
+        let bytes = std::fs::read(path)
+            .map_err(|e| actix_web::error::ErrorInternalServerError(format!("读取文件失败: {}", e)))?;
+        let content = decode(&bytes[bom_len..], encoding);
+        let total_lines = content.lines().count();
+        return Ok(TextPreviewResponse {
+            encoding: encoding.name().to_string(),
+            kind: kind.to_string(),
+            language: extension.to_string(),
+            page: 1,
+            page_lines: total_lines,
+            total_pages: 1,
+            total_lines,
+            html: Some(render_markdown(&content)),
+            content,
+        });
+    }
+
+    let newline = Newline::for_encoding(encoding);
+    let index = line_index(path, encoding, bom_len)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("读取文件失败: {}", e)))?;
+    let total_pages = index.total_lines.div_ceil(page_lines).max(1);
+    if page > total_pages {
+        return Err(actix_web::error::ErrorBadRequest(format!("页码超出范围（共 {} 页）", total_pages)));
+    }
+    let bytes = read_lines(path, newline, &index, (page - 1) * page_lines, page_lines)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("读取文件失败: {}", e)))?;
+
+    Ok(TextPreviewResponse {
+        encoding: encoding.name().to_string(),
+        kind: kind.to_string(),
+        language: extension.to_string(),
+        page,
+        page_lines,
+        total_pages,
+        total_lines: index.total_lines,
+        content: decode(&bytes, encoding),
+        html: None,
+    })
+}
+
+/// GET /api/preview/text?path=<path>|uuid=<uuid>&page=<n>&page_lines=<n>&encoding=<label>
+/// 返回 UTF-8 文本（按行分页）；Markdown 额外返回渲染并净化后的 HTML
+pub async fn get_text(query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let path = resolve_source(&query)?;
+    if !path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !DOCUMENT_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest("仅支持文本文档"));
+    }
+
+    let page = match query.get("page") {
+        None => 1,
+        Some(s) => s.parse::<usize>()
+            .ok()
+            .filter(|p| *p >= 1)
+            .ok_or_else(|| actix_web::error::ErrorBadRequest("page 必须是从 1 开始的页码"))?,
+    };
+    let page_lines = query.get("page_lines")
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_PAGE_LINES)
+        .clamp(1, MAX_PAGE_LINES);
+    let label = query.get("encoding").cloned();
+    if let Some(label) = label.as_deref() {
+        if Encoding::for_label(label.trim().as_bytes()).is_none() {
+            return Err(actix_web::error::ErrorBadRequest(format!("不支持的字符集: {}", label)));
+        }
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        build_preview(&path, &extension, label.as_deref(), page, page_lines)
+    })
+    .await
+    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))??;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// 文本缩略图：前若干行排成等宽文本，交给 MuPDF 的 HTML 排版引擎渲染成图片
+pub fn render_text_thumbnail(path: &Path) -> Result<image::DynamicImage> {
+    let (encoding, bom_len, _) = open_with_encoding(path, None)
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let mut file = File::open(path)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法打开文件: {}", e)))?;
+    let mut head = Vec::new();
+    file.by_ref().take((SNIFF_BYTES / 4) as u64).read_to_end(&mut head)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("读取文件失败: {}", e)))?;
+    let text = decode(&head[bom_len.min(head.len())..], encoding);
+
+    let snippet: Vec<String> = text
+        .lines()
+        .take(THUMBNAIL_LINES)
+        .map(|line| escape_html(&line.chars().take(THUMBNAIL_LINE_CHARS).collect::<String>()))
+        .collect();
+    let html = format!(
+        "<html><head><style>\
+         body {{ margin: 10pt; background: #fff; color: #222; }}\
+         pre {{ margin: 0; font-family: monospace; font-size: 8pt; line-height: 1.3; white-space: pre-wrap; }}\
+         </style></head><body><pre>{}</pre></body></html>",
+        snippet.join("\n")
+    );
+
+    let mut doc = mupdf::Document::from_bytes(html.as_bytes(), "html")
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法排版文本: {}", e)))?;
+    // 3:4 竖版页面，与文档类缩略图比例一致
+    doc.layout(300.0, 400.0, 8.0)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法排版文本: {}", e)))?;
+    let page = doc.load_page(0)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法渲染文本: {}", e)))?;
+    super::utils::render_pdf_page(&page, 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_markdown_sanitized() {
+        let html = render_markdown("# Title\n\n<script>alert(1)</script>\n\n[x](javascript:alert(1))");
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("javascript:"));
+    }
+}
@@ -0,0 +1,233 @@
+// 缩略图磁盘缓存 + 后台预生成
+//
+// 缓存位置: <app_dir>/cache/thumbnails/<uuid 前两位>/<uuid>_<bucket>_<mtime>.jpg
+// - key 含源文件 mtime: 文件被修改后旧缓存自然失配, 由 LRU 淘汰
+// - 请求尺寸归一到固定档位 (bucket), 避免 299/300/301 各存一份
+// - 命中时刷新缓存文件 mtime, 淘汰时按 mtime 升序删除 (近似 LRU)
+// - 后台 worker 预生成新文件的缩略图; 队列空闲时回填索引里还没有 BlurHash 的文件
+//   (升级前的库、mtime 未变而不会入队的文件、超出队列上限被丢弃的文件)
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
+use super::disk_cache::DiskCache;
+
+/// 缩略图尺寸档位（请求尺寸向上取整到最近的档位）
+pub const SIZE_BUCKETS: &[u32] = &[160, 320, 640, 1280];
+
+/// 后台预生成使用的档位（客户端默认请求 300 → 320）
+const PREGEN_BUCKET: u32 = 320;
+
+/// 预生成队列上限，全量扫描超大库时超出部分直接丢弃（之后由 BlurHash 回填补上）
+const PREGEN_QUEUE_LIMIT: usize = 10_000;
+
+/// 需要缩略图 / BlurHash 的文件类型
+const PREGEN_TYPES: &[&str] = &["image", "gif", "video", "pdf", "ebook", "document"];
+
+/// BlurHash 回填每次从索引取的条数（每批之间先处理队列里的新文件）
+const BACKFILL_BATCH: i64 = 200;
+
+/// 缓存容量默认 2048 MB，可在 app.json 中通过 thumbnail_cache_mb 覆盖
+static CACHE: DiskCache = DiskCache::new("thumbnails", "thumbnail_cache_mb", 2048);
+
+/// 从 app.json 读取是否开启后台预生成（默认开启）
+fn pregen_enabled() -> bool {
+    crate::static_files::read_config_file("app.json")
+        .and_then(|data| serde_json::from_slice::<serde_json::Value>(&data).ok())
+        .and_then(|v| v.get("thumbnail_pregen").and_then(|x| x.as_bool()))
+        .unwrap_or(true)
+}
+
+/// 请求尺寸 → 档位尺寸
+pub fn bucket_size(size: u32) -> u32 {
+    SIZE_BUCKETS
+        .iter()
+        .copied()
+        .find(|b| *b >= size)
+        .unwrap_or(*SIZE_BUCKETS.last().unwrap())
+}
+
+/// 计算缓存文件路径；源文件 mtime 读不到时返回 None（不缓存）
+pub fn cache_path(uuid: &str, bucket: u32, source: &Path, ext: &str) -> Option<PathBuf> {
+    // UUID 只允许十六进制和连字符，防止拼路径时被注入 ../
+    if uuid.len() < 2 || !uuid.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        return None;
+    }
+    let mtime = std::fs::metadata(source)
+        .and_then(|m| m.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some(
+        CACHE.root()
+            .join(&uuid[..2])
+            .join(format!("{}_{}_{}.{}", uuid, bucket, mtime, ext)),
+    )
+}
+
+/// 读取缓存（命中时刷新 LRU 访问时间）
+pub fn read(path: &Path) -> Option<Vec<u8>> {
+    CACHE.read(path)
+}
+
+/// 刷新缓存文件的访问时间；文件不存在时返回 false
+pub fn touch(path: &Path) -> bool {
+    CACHE.touch(path)
+}
+
+/// 写入缓存，超出容量时触发淘汰
+pub fn store(path: &Path, data: &[u8]) {
+    CACHE.store(path, data)
+}
+
+// ============================================================================
+// 后台预生成
+// ============================================================================
+
+struct PregenQueue {
+    queue: Mutex<(VecDeque<String>, HashSet<String>)>,
+    ready: Condvar,
+}
+
+/// BlurHash 回填进度：Some(上一批最后的 UUID) 表示进行中，None 表示已完成
+/// 按 UUID 游标推进，解码失败的文件本轮不会重复处理
+static BACKFILL_CURSOR: Mutex<Option<String>> = Mutex::new(Some(String::new()));
+
+/// worker 未启动（app.json 关闭了预生成）时 enqueue 直接丢弃
+static PREGEN_ENABLED: AtomicBool = AtomicBool::new(false);
+
+fn pregen_queue() -> &'static PregenQueue {
+    static QUEUE: OnceLock<PregenQueue> = OnceLock::new();
+    QUEUE.get_or_init(|| PregenQueue {
+        queue: Mutex::new((VecDeque::new(), HashSet::new())),
+        ready: Condvar::new(),
+    })
+}
+
+/// 新索引的文件入队（按路径，UUID 由 worker 从索引中取，
+/// 因为 fast_upsert 冲突时保留的是旧 UUID 而非调用方生成的那个）
+pub fn enqueue(file_path: &str, file_type: &str) {
+    if !PREGEN_ENABLED.load(Ordering::Relaxed) || !PREGEN_TYPES.contains(&file_type) {
+        return;
+    }
+    let q = pregen_queue();
+    let mut guard = q.queue.lock().unwrap();
+    let (queue, pending) = &mut *guard;
+    if queue.len() >= PREGEN_QUEUE_LIMIT {
+        drop(guard);
+        request_backfill();
+        return;
+    }
+    if !pending.insert(file_path.to_string()) {
+        return;
+    }
+    queue.push_back(file_path.to_string());
+    q.ready.notify_one();
+}
+
+/// 启动后台预生成线程（单线程顺序处理，避免和前台请求抢 CPU）
+pub fn start_pregen_worker() {
+    if !pregen_enabled() {
+        eprintln!("[thumb-cache] background pre-generation disabled");
+        return;
+    }
+    PREGEN_ENABLED.store(true, Ordering::Relaxed);
+
+    std::thread::spawn(|| loop {
+        let file_path = {
+            let q = pregen_queue();
+            let mut guard = q.queue.lock().unwrap();
+            while guard.0.is_empty() && BACKFILL_CURSOR.lock().unwrap().is_none() {
+                guard = q.ready.wait(guard).unwrap();
+            }
+            let path = guard.0.pop_front();
+            if let Some(ref path) = path {
+                guard.1.remove(path);
+            }
+            path
+        };
+        match file_path {
+            Some(file_path) => pregen_one(&file_path),
+            None => backfill_batch(),
+        }
+    });
+
+    eprintln!("[thumb-cache] background pre-generation started");
+}
+
+/// 重新开始 BlurHash 回填（扫描结束、队列溢出时调用）
+pub fn request_backfill() {
+    if !PREGEN_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let q = pregen_queue();
+    // 持有队列锁再改游标，worker 检查条件和进入等待之间不会漏掉唤醒
+    let _guard = q.queue.lock().unwrap();
+    *BACKFILL_CURSOR.lock().unwrap() = Some(String::new());
+    q.ready.notify_one();
+}
+
+/// 回填一批没有 BlurHash 的文件；取不到时结束本轮回填
+fn backfill_batch() {
+    let Some(after) = BACKFILL_CURSOR.lock().unwrap().clone() else { return };
+    let batch = match crate::indexer::storage::get_files_missing_blurhash(&after, PREGEN_TYPES, BACKFILL_BATCH) {
+        Ok(batch) => batch,
+        Err(e) => {
+            eprintln!("[thumb-cache] blurhash backfill query failed: {}", e);
+            Vec::new()
+        }
+    };
+    let Some((last_uuid, _)) = batch.last() else {
+        let mut cursor = BACKFILL_CURSOR.lock().unwrap();
+        // 查询期间有新的回填请求（游标被重置）时不要结束
+        if cursor.as_deref() == Some(after.as_str()) {
+            *cursor = None;
+        }
+        return;
+    };
+    let last_uuid = last_uuid.clone();
+    for (_, path) in &batch {
+        pregen_one(path);
+    }
+    let mut cursor = BACKFILL_CURSOR.lock().unwrap();
+    if cursor.as_deref() == Some(after.as_str()) {
+        *cursor = Some(last_uuid);
+    }
+}
+
+/// 预生成：一次解码，产出最常用的 JPEG / WebP 两种格式，并顺带计算 BlurHash
+fn pregen_one(file_path: &str) {
+    use super::thumbnail::{encode_thumbnail, render_thumbnail, ThumbFormat};
+
+    let file = match crate::indexer::storage::get_file_by_path(file_path) {
+        Ok(Some(f)) => f,
+        _ => return,
+    };
+    let source = Path::new(file_path);
+    let targets: Vec<(ThumbFormat, PathBuf)> = [ThumbFormat::Jpeg, ThumbFormat::Webp]
+        .into_iter()
+        .filter_map(|fmt| cache_path(&file.uuid, PREGEN_BUCKET, source, fmt.ext()).map(|p| (fmt, p)))
+        .filter(|(_, p)| !p.exists())
+        .collect();
+    if targets.is_empty() && file.blurhash.is_some() {
+        return;
+    }
+
+    let img = match render_thumbnail(source, PREGEN_BUCKET) {
+        Ok(img) => img,
+        Err(e) => {
+            eprintln!("[thumb-cache] pre-generate failed ({}): {}", file_path, e);
+            return;
+        }
+    };
+    if file.blurhash.is_none() {
+        let _ = crate::indexer::storage::set_blurhash(&file.uuid, &super::blurhash::encode(&img));
+    }
+    for (fmt, cache_file) in targets {
+        if let Ok(data) = encode_thumbnail(&img, fmt) {
+            store(&cache_file, &data);
+        }
+    }
+}
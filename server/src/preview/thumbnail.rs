@@ -1,37 +1,241 @@
 // 缩略图生成功能（从gallery/handlers.rs迁移）
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpRequest, HttpResponse, Result};
 use std::path::Path;
 use std::io::Cursor;
-use image::ImageFormat;
+use image::{DynamicImage, ImageFormat};
 use super::models::*;
-use super::utils::{extract_video_first_frame, extract_audio_cover, extract_clip_thumbnail, extract_image_thumbnail_ffmpeg, extract_pdf_thumbnail};
+use super::thumb_cache;
+use super::utils::{extract_video_first_frame, extract_audio_cover, extract_image_thumbnail_ffmpeg, extract_pdf_thumbnail, encode_image_ffmpeg};
+
+/// 缩略图输出格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThumbFormat {
+    Jpeg,
+    Webp,
+    Avif,
+    Png,
+}
+
+impl ThumbFormat {
+    pub fn ext(self) -> &'static str {
+        match self {
+            ThumbFormat::Jpeg => "jpg",
+            ThumbFormat::Webp => "webp",
+            ThumbFormat::Avif => "avif",
+            ThumbFormat::Png => "png",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            ThumbFormat::Jpeg => "image/jpeg",
+            ThumbFormat::Webp => "image/webp",
+            ThumbFormat::Avif => "image/avif",
+            ThumbFormat::Png => "image/png",
+        }
+    }
+
+    /// 按文件头识别实际格式（AVIF 编码失败时会降级，缓存里存的未必是请求的格式）
+    pub fn sniff(data: &[u8]) -> Self {
+        if data.starts_with(b"\x89PNG") {
+            ThumbFormat::Png
+        } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            ThumbFormat::Webp
+        } else if data.len() >= 12 && &data[4..8] == b"ftyp" && (&data[8..12] == b"avif" || &data[8..12] == b"avis") {
+            ThumbFormat::Avif
+        } else {
+            ThumbFormat::Jpeg
+        }
+    }
+}
+
+/// 选择输出格式：?format= 显式指定优先，否则按 Accept 头协商（webp > jpeg）
+/// AVIF 编码慢、后台预生成也不产出 AVIF，只在显式 ?format=avif 时输出
+pub fn negotiate_format(req: &HttpRequest, query: &std::collections::HashMap<String, String>) -> ThumbFormat {
+    match query.get("format").map(|f| f.to_lowercase()).as_deref() {
+        Some("avif") => return ThumbFormat::Avif,
+        Some("webp") => return ThumbFormat::Webp,
+        Some("jpg") | Some("jpeg") => return ThumbFormat::Jpeg,
+        Some("png") => return ThumbFormat::Png,
+        _ => {}
+    }
+    let accept = req.headers()
+        .get(actix_web::http::header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    choose_format(accept)
+}
+
+fn choose_format(accept: &str) -> ThumbFormat {
+    if accept_quality(accept, "image/webp") > 0.0 {
+        ThumbFormat::Webp
+    } else {
+        ThumbFormat::Jpeg
+    }
+}
+
+/// Accept 头中指定 MIME 的 q 值；未列出时为 0
+/// 只认精确匹配：旧浏览器也发 image/*，但并不支持 AVIF / WebP
+fn accept_quality(accept: &str, mime: &str) -> f32 {
+    accept
+        .split(',')
+        .find_map(|item| {
+            let mut params = item.split(';');
+            if !params.next()?.trim().eq_ignore_ascii_case(mime) {
+                return None;
+            }
+            let q = params
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(1.0, |q| q.trim().parse().unwrap_or(0.0));
+            Some(q)
+        })
+        .unwrap_or(0.0)
+}
 
 /// GET /api/preview/thumbnail?path=<file_path>&size=<size>
-/// GET /api/preview/thumbnail?uuid=<uuid>&size=<size>
+/// GET /api/preview/thumbnail?uuid=<uuid>&size=<size>&format=<jpeg|webp|avif>
 /// 生成并返回图片/视频缩略图，支持通过 UUID 或路径查询
-pub async fn get_thumbnail(query: web::Query<std::collections::HashMap<String, String>>) -> Result<HttpResponse> {
+/// 尺寸归一到 thumb_cache::SIZE_BUCKETS 档位，结果按 (uuid, 档位, mtime, 格式) 缓存到磁盘
+/// 未指定 format 时按 Accept 头协商（支持则用 WebP）；WebP / AVIF 保留透明通道，JPEG 透明部分按白底合成
+pub async fn get_thumbnail(req: HttpRequest, query: web::Query<std::collections::HashMap<String, String>>) -> Result<HttpResponse> {
     // 优先使用 UUID 查询
-    let file_path_resolved = if let Some(uuid) = query.get("uuid") {
+    let (file_path_resolved, indexed) = if let Some(uuid) = query.get("uuid") {
         let file = crate::indexer::storage::get_file_by_uuid(uuid)
             .map_err(|e| actix_web::error::ErrorInternalServerError(format!("数据库错误: {}", e)))?
             .ok_or_else(|| actix_web::error::ErrorNotFound("UUID 对应的文件未找到"))?;
-        file.current_path
-            .ok_or_else(|| actix_web::error::ErrorNotFound("文件已被删除或移动"))?
+        let path = file.current_path.clone()
+            .ok_or_else(|| actix_web::error::ErrorNotFound("文件已被删除或移动"))?;
+        (path, Some(file))
     } else {
-        query.get("path")
+        let path = query.get("path")
             .ok_or_else(|| actix_web::error::ErrorBadRequest("缺少 path 或 uuid 参数"))?
-            .clone()
+            .clone();
+        // 路径请求：若文件已索引，同样走 UUID 缓存
+        let file = crate::indexer::storage::get_file_by_path(&path).ok().flatten();
+        (path, file)
     };
 
     let size: u32 = query.get("size")
         .and_then(|s| s.parse().ok())
         .unwrap_or(300); // 默认300px
+    let size = thumb_cache::bucket_size(size);
+    let format = negotiate_format(&req, &query);
 
     let path = Path::new(&file_path_resolved);
     if !path.exists() || !path.is_file() {
         return Err(actix_web::error::ErrorNotFound("文件不存在"));
     }
 
+    let cache_file = indexed.as_ref().and_then(|f| thumb_cache::cache_path(&f.uuid, size, path, format.ext()));
+    if let Some(ref cache_file) = cache_file {
+        if let Some(data) = thumb_cache::read(cache_file) {
+            return Ok(thumbnail_response(data));
+        }
+    }
+
+    let img = render_thumbnail(path, size)?;
+    if let Some(file) = indexed.as_ref().filter(|f| f.blurhash.is_none()) {
+        let _ = crate::indexer::storage::set_blurhash(&file.uuid, &super::blurhash::encode(&img));
+    }
+    let data = encode_thumbnail(&img, format)?;
+    if let Some(ref cache_file) = cache_file {
+        thumb_cache::store(cache_file, &data);
+    }
+
+    Ok(thumbnail_response(data))
+}
+
+fn thumbnail_response(data: Vec<u8>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ThumbFormat::sniff(&data).mime())
+        // UUID-based thumbnails are immutable — cache aggressively
+        // Browser won't re-request the same UUID+size combination
+        .insert_header(("Cache-Control", "public, max-age=31536000, immutable"))
+        .insert_header(("Vary", "Accept"))
+        .body(data)
+}
+
+/// 将缩略图编码为指定格式（默认质量）
+pub fn encode_thumbnail(img: &DynamicImage, format: ThumbFormat) -> Result<Vec<u8>> {
+    encode_image(img, format, None)
+}
+
+/// 将图像编码为指定格式，quality 为 1–100（None 时用各格式的默认质量）
+/// - WebP：优先 ffmpeg libwebp 有损编码，失败时退回 image 库的无损编码（纯 Rust，总能成功）
+/// - AVIF：ffmpeg libaom，失败时降级为 WebP；质量换算为 crf（默认 32）
+pub fn encode_image(img: &DynamicImage, format: ThumbFormat, quality: Option<u8>) -> Result<Vec<u8>> {
+    let pix_fmt = if img.color().has_alpha() { "yuva420p" } else { "yuv420p" };
+    match format {
+        ThumbFormat::Jpeg => {
+            // JPEG 不支持 alpha 通道，透明部分按白底合成（直接 into_rgb8 会露出黑底）
+            let rgb = flatten_on_white(img);
+            let mut buffer = Vec::new();
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality.unwrap_or(75))
+                .encode_image(&rgb)
+                .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法编码图片: {}", e)))?;
+            Ok(buffer)
+        }
+        ThumbFormat::Png => {
+            let mut buffer = Cursor::new(Vec::new());
+            img.write_to(&mut buffer, ImageFormat::Png)
+                .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法编码图片: {}", e)))?;
+            Ok(buffer.into_inner())
+        }
+        ThumbFormat::Webp => {
+            let q = quality.unwrap_or(80).to_string();
+            match encode_image_ffmpeg(img, &["-c:v", "libwebp", "-quality", &q, "-pix_fmt", pix_fmt], "webp") {
+                Ok(data) if ThumbFormat::sniff(&data) == ThumbFormat::Webp => Ok(data),
+                _ => {
+                    let rgba = img.to_rgba8();
+                    let mut buffer = Vec::new();
+                    image::codecs::webp::WebPEncoder::new_lossless(&mut buffer)
+                        .encode(rgba.as_raw(), rgba.width(), rgba.height(), image::ColorType::Rgba8)
+                        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法编码图片: {}", e)))?;
+                    Ok(buffer)
+                }
+            }
+        }
+        ThumbFormat::Avif => {
+            let crf = quality.map(|q| 63 * (100 - q.min(100) as u32) / 100).unwrap_or(32).to_string();
+            match encode_image_ffmpeg(img, &["-c:v", "libaom-av1", "-still-picture", "1", "-crf", &crf, "-cpu-used", "6", "-pix_fmt", pix_fmt], "avif") {
+                Ok(data) if ThumbFormat::sniff(&data) == ThumbFormat::Avif => Ok(data),
+                _ => encode_image(img, ThumbFormat::Webp, quality),
+            }
+        }
+    }
+}
+
+fn flatten_on_white(img: &DynamicImage) -> image::RgbImage {
+    if !img.color().has_alpha() {
+        return img.to_rgb8();
+    }
+    let rgba = img.to_rgba8();
+    image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let p = rgba.get_pixel(x, y);
+        let a = p[3] as u32;
+        let blend = |c: u8| ((c as u32 * a + 255 * (255 - a)) / 255) as u8;
+        image::Rgb([blend(p[0]), blend(p[1]), blend(p[2])])
+    })
+}
+
+/// 解码图片文件并按 EXIF 方向校正（缩略图和 /api/preview/image 共用）
+/// 先尝试 image 库直接打开，失败则 fallback 到 ffmpeg（用于 HEIC/AVIF 等格式，以及超大图内存超限的情况）；
+/// max_size 仅作用于 ffmpeg 回退路径（0 表示不缩放）
+pub fn decode_image(path: &Path, max_size: u32) -> Result<DynamicImage> {
+    let img = match image::open(path) {
+        Ok(img) => img,
+        Err(e) => {
+            eprintln!("[thumbnail] image::open 失败 ({:?}): {}", path, e);
+            // 直接让 ffmpeg 缩放到目标尺寸，避免超大图再次触发内存超限
+            extract_image_thumbnail_ffmpeg(path, max_size)?
+        }
+    };
+    Ok(super::exif::apply_orientation(img, super::exif::read_orientation(path)))
+}
+
+/// 解码源文件并缩放到缩略图尺寸（同步；视频/PDF 等会调用 ffmpeg / MuPDF）
+/// 前台请求和后台预生成共用
+pub fn render_thumbnail(path: &Path, size: u32) -> Result<DynamicImage> {
     // 获取扩展名判断文件类型
     let extension = path
         .extension()
@@ -42,11 +246,14 @@ pub async fn get_thumbnail(query: web::Query<std::collections::HashMap<String, S
     // 判断文件类型
     let is_video = VIDEO_EXTENSIONS.contains(&extension.as_str());
     let is_image = extension == GIF_EXTENSION || IMAGE_EXTENSIONS.contains(&extension.as_str());
-    let is_clip = extension == CLIP_EXTENSION;
+    let is_design = DESIGN_EXTENSIONS.contains(&extension.as_str());
+    let is_raw = RAW_EXTENSIONS.contains(&extension.as_str());
     let is_pdf = extension == PDF_EXTENSION;
     let is_audio = AUDIO_EXTENSIONS.contains(&extension.as_str());
+    let is_ebook = EBOOK_EXTENSIONS.contains(&extension.as_str());
+    let is_document = DOCUMENT_EXTENSIONS.contains(&extension.as_str());
 
-    if !is_video && !is_image && !is_clip && !is_pdf && !is_audio {
+    if !is_video && !is_image && !is_design && !is_raw && !is_pdf && !is_audio && !is_ebook && !is_document {
         return Err(actix_web::error::ErrorBadRequest("不支持的媒体格式"));
     }
 
@@ -59,9 +266,18 @@ pub async fn get_thumbnail(query: web::Query<std::collections::HashMap<String, S
         }
     } else if is_video {
         extract_video_first_frame(path)?
-    } else if is_clip {
-        // CLIP 文件：从内嵌 SQLite 提取缩略图
-        extract_clip_thumbnail(path)?
+    } else if is_raw {
+        // 相机 RAW：取内嵌的最大 JPEG 预览并按 RAW 的方向校正
+        super::raw::extract_raw_preview(path)?
+    } else if is_design {
+        // 设计源文件：CLIP 内嵌 SQLite 预览图 / PSD 合成图 / Krita、OpenRaster 合成图 / SVG 栅格化
+        super::design::extract_design_image(path)?
+    } else if is_document {
+        // 文本文档：前若干行的文字快照
+        super::text::render_text_thumbnail(path)?
+    } else if is_ebook {
+        // EPUB：取 OPF 中声明的封面图片
+        super::epub::extract_epub_cover(path)?
     } else if is_pdf {
         // PDF 文件：用 MuPDF 渲染第一页
         match extract_pdf_thumbnail(path) {
@@ -72,32 +288,32 @@ pub async fn get_thumbnail(query: web::Query<std::collections::HashMap<String, S
             }
         }
     } else {
-        // 先尝试 image 库直接打开，失败则 fallback 到 ffmpeg
-        // （用于 HEIC/AVIF 等格式，以及超大图内存超限的情况）
-        match image::open(path) {
-            Ok(img) => img,
-            Err(e) => {
-                eprintln!("[thumbnail] image::open 失败 ({:?}): {}", path, e);
-                // 直接让 ffmpeg 缩放到目标尺寸，避免超大图再次触发内存超限
-                extract_image_thumbnail_ffmpeg(path, size)?
-            }
+        // 动图取中间帧作为代表帧，静图 / 无法逐帧解码时按普通图片解码
+        let frame = super::animation::probe_frame_count(path)
+            .filter(|&n| n > 1)
+            .and_then(|n| super::animation::representative_frame(path, n));
+        match frame {
+            Some(frame) => frame,
+            None => decode_image(path, size)?,
         }
     };
 
     // 生成缩略图 (保持宽高比)
-    let thumbnail = img.thumbnail(size, size);
+    Ok(img.thumbnail(size, size))
+}
 
-    // 将缩略图编码为JPEG格式
-    // JPEG 不支持 alpha 通道，统一转为 RGB8（兼容 RGBA/RGBA16 等带透明度的图像）
-    let thumbnail_rgb = image::DynamicImage::ImageRgb8(thumbnail.into_rgb8());
-    let mut buffer = Cursor::new(Vec::new());
-    thumbnail_rgb.write_to(&mut buffer, ImageFormat::Jpeg)
-        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法编码图片: {}", e)))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(HttpResponse::Ok()
-        .content_type("image/jpeg")
-        // UUID-based thumbnails are immutable — cache aggressively
-        // Browser won't re-request the same UUID+size combination
-        .insert_header(("Cache-Control", "public, max-age=31536000, immutable"))
-        .body(buffer.into_inner()))
+    #[test]
+    fn test_choose_format() {
+        let chrome = "image/avif,image/webp,image/apng,image/svg+xml,image/*,*/*;q=0.8";
+        assert_eq!(choose_format(chrome), ThumbFormat::Webp);
+        // AVIF 只能显式请求
+        assert_eq!(choose_format("image/avif, image/webp;q=0"), ThumbFormat::Jpeg);
+        assert_eq!(choose_format("image/avif;q=0, image/webp"), ThumbFormat::Webp);
+        assert_eq!(choose_format("image/*,*/*;q=0.8"), ThumbFormat::Jpeg);
+        assert_eq!(choose_format(""), ThumbFormat::Jpeg);
+    }
 }
@@ -263,6 +263,11 @@ pub fn extract_pdf_thumbnail(pdf_path: &Path) -> Result<image::DynamicImage> {
     } else {
         2.0
     };
+    render_pdf_page(&page, scale)
+}
+
+/// 按缩放比例（相对 72 DPI）把 PDF 页面渲染为 RGB 图像
+pub fn render_pdf_page(page: &mupdf::Page, scale: f32) -> Result<image::DynamicImage> {
     let matrix = mupdf::Matrix::new_scale(scale, scale);
 
     let pixmap = page.to_pixmap(&matrix, &mupdf::Colorspace::device_rgb(), false, true)
@@ -278,3 +283,103 @@ pub fn extract_pdf_thumbnail(pdf_path: &Path) -> Result<image::DynamicImage> {
 
     Ok(image::DynamicImage::ImageRgb8(img))
 }
+
+/// 用 ffmpeg 将已解码的图像编码为 WebP / AVIF 等 image 库无法（有损）编码的格式
+/// codec_args: 编码参数，如 ["-c:v", "libwebp", "-quality", "80"]
+/// ext: 输出扩展名，ffmpeg 据此选择封装格式
+pub fn encode_image_ffmpeg(img: &image::DynamicImage, codec_args: &[&str], ext: &str) -> Result<Vec<u8>> {
+    let ffmpeg_path = get_ffmpeg_path();
+
+    let id = uuid::Uuid::new_v4();
+    let temp_input = std::env::temp_dir().join(format!("enc_{}.png", id));
+    let temp_output = std::env::temp_dir().join(format!("enc_{}.{}", id, ext));
+
+    img.save_with_format(&temp_input, image::ImageFormat::Png)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法写入临时图片: {}", e)))?;
+
+    let mut args: Vec<&str> = vec!["-i", temp_input.to_str().unwrap()];
+    args.extend_from_slice(codec_args);
+    args.extend_from_slice(&["-frames:v", "1", "-y", temp_output.to_str().unwrap()]);
+
+    let output = Command::new(&ffmpeg_path)
+        .args(&args)
+        .output();
+    let _ = fs::remove_file(&temp_input);
+    let output = output
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("FFmpeg 执行失败: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let _ = fs::remove_file(&temp_output);
+        return Err(actix_web::error::ErrorInternalServerError(
+            format!("FFmpeg 编码 {} 失败: {}", ext, stderr)
+        ));
+    }
+
+    let data = fs::read(&temp_output)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法读取编码结果: {}", e)));
+    let _ = fs::remove_file(&temp_output);
+    data
+}
+
+/// 生成视频悬停预览：沿时长均匀截取若干个 1 秒片段，拼接成静音的小尺寸 MP4，返回文件字节
+/// snippets: 片段数；width: 输出宽度（高度按宽高比）
+pub fn generate_hover_preview(video_path: &Path, duration: f64, snippets: u32, width: u32) -> Result<Vec<u8>> {
+    let ffmpeg_path = get_ffmpeg_path();
+    let source = video_path.to_str()
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("无效的文件路径"))?;
+    let temp_output = std::env::temp_dir().join(format!("hover_{}.mp4", uuid::Uuid::new_v4()));
+
+    // 短视频：片段数不超过秒数，不足 1 秒的直接整段截取
+    let snippet_len = duration.min(1.0);
+    let count = snippets.min(duration.floor().max(1.0) as u32).max(1);
+
+    // 每个片段作为一个独立输入（-ss 在 -i 之前，快速定位），再用 concat filter 拼接
+    let mut args: Vec<String> = vec!["-hide_banner".into(), "-loglevel".into(), "error".into()];
+    let mut filter = String::new();
+    for i in 0..count {
+        // 每段取所在区间的中点附近
+        let start = (duration * (i as f64 + 0.5) / count as f64 - snippet_len / 2.0).max(0.0);
+        args.extend([
+            "-ss".into(), format!("{:.3}", start),
+            "-t".into(), format!("{:.3}", snippet_len),
+            "-i".into(), source.to_string(),
+        ]);
+        filter.push_str(&format!("[{}:v]scale={}:-2,setsar=1,fps=24[v{}];", i, width, i));
+    }
+    for i in 0..count {
+        filter.push_str(&format!("[v{}]", i));
+    }
+    filter.push_str(&format!("concat=n={}:v=1:a=0[out]", count));
+
+    args.extend([
+        "-filter_complex".into(), filter,
+        "-map".into(), "[out]".into(),
+        "-an".into(),
+        "-c:v".into(), "libx264".into(),
+        "-preset".into(), "veryfast".into(),
+        "-crf".into(), "28".into(),
+        "-pix_fmt".into(), "yuv420p".into(),
+        "-movflags".into(), "+faststart".into(),
+        "-y".into(),
+        temp_output.to_string_lossy().to_string(),
+    ]);
+
+    let output = Command::new(&ffmpeg_path)
+        .args(&args)
+        .output()
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("FFmpeg 执行失败: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let _ = fs::remove_file(&temp_output);
+        return Err(actix_web::error::ErrorInternalServerError(
+            format!("FFmpeg 生成悬停预览失败: {}", stderr)
+        ));
+    }
+
+    let data = fs::read(&temp_output)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("无法读取悬停预览: {}", e)));
+    let _ = fs::remove_file(&temp_output);
+    data
+}
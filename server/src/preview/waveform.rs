@@ -0,0 +1,271 @@
+// 音频波形峰值：ffmpeg 解码为单声道 8 kHz 浮点 PCM，按固定窗口取峰值，
+// 再降采样到请求的分辨率。基准峰值按 (uuid, mtime) 缓存在缩略图磁盘缓存中
+use actix_web::{http::header, web, HttpRequest, HttpResponse, Result};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use tokio::sync::Semaphore;
+use super::models::{WaveformResponse, AUDIO_EXTENSIONS, VIDEO_EXTENSIONS};
+use super::thumb_cache;
+
+/// 解码采样率：只看包络，8 kHz 足够且解码量小
+const SAMPLE_RATE: usize = 8000;
+
+/// 基准峰值个数，即可请求的最高分辨率（缓存 32 KB）
+const BASE_PEAKS: usize = 8192;
+
+/// 默认 / 最低分辨率
+const DEFAULT_SAMPLES: usize = 800;
+const MIN_SAMPLES: usize = 16;
+
+/// 最小窗口 10ms，极短的音频峰值个数会少于 BASE_PEAKS
+const MIN_WINDOW: usize = SAMPLE_RATE / 100;
+
+/// 同时解码的文件数上限（长音频 / 视频解码一次要几秒）
+const WAVEFORM_MAX_CONCURRENT: usize = 2;
+
+fn generate_permits() -> &'static Semaphore {
+    static PERMITS: OnceLock<Semaphore> = OnceLock::new();
+    PERMITS.get_or_init(|| Semaphore::new(WAVEFORM_MAX_CONCURRENT))
+}
+
+/// GET /api/preview/waveform?uuid=<uuid>&samples=<n>&format=json|f32
+/// 返回音频 / 视频音轨的波形峰值，samples 为峰值个数（16–8192，默认 800）
+/// - json（默认）：WaveformResponse
+/// - f32：小端 f32 数组（application/octet-stream），时长放在 X-Waveform-Duration 头
+///
+/// URL 只含 uuid，文件被替换后内容会变：不标记 immutable，改用按 mtime 计算的 ETag 协商缓存
+pub async fn get_waveform(req: HttpRequest, query: web::Query<HashMap<String, String>>) -> Result<HttpResponse> {
+    let uuid = query.get("uuid")
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("缺少 uuid 参数"))?;
+    let file = crate::indexer::storage::get_file_by_uuid(uuid)
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("数据库错误: {}", e)))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("UUID 对应的文件未找到"))?;
+    let file_path = file.current_path
+        .ok_or_else(|| actix_web::error::ErrorNotFound("文件已被删除或移动"))?;
+
+    let path = Path::new(&file_path);
+    if !path.is_file() {
+        return Err(actix_web::error::ErrorNotFound("文件不存在"));
+    }
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    if !AUDIO_EXTENSIONS.contains(&extension.as_str()) && !VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(actix_web::error::ErrorBadRequest("仅支持音频和视频文件"));
+    }
+
+    let samples = query.get("samples")
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_SAMPLES)
+        .clamp(MIN_SAMPLES, BASE_PEAKS);
+    let binary = match query.get("format").map(|s| s.as_str()) {
+        None | Some("json") => false,
+        Some("f32") => true,
+        Some(_) => return Err(actix_web::error::ErrorBadRequest("format 仅支持 json / f32")),
+    };
+
+    let mtime = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let etag = format!("\"{}-{}-{}-{}\"", file.uuid, mtime, samples, if binary { "f32" } else { "json" });
+    if etag_matches(&req, &etag) {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .finish());
+    }
+
+    let cache_file = thumb_cache::cache_path(&file.uuid, BASE_PEAKS as u32, path, "peaks")
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("无法确定缓存路径"))?;
+
+    let base = match thumb_cache::read(&cache_file) {
+        Some(data) => decode_peaks(&data),
+        None => {
+            let _permit = generate_permits()
+                .acquire()
+                .await
+                .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+
+            // 排队期间可能已被同一文件的其他请求生成
+            match thumb_cache::read(&cache_file) {
+                Some(data) => decode_peaks(&data),
+                None => {
+                    let source = path.to_path_buf();
+                    let target = cache_file.clone();
+                    tokio::task::spawn_blocking(move || {
+                        let peaks = compute_base_peaks(&source)?;
+                        thumb_cache::store(&target, &encode_peaks(&peaks));
+                        Ok::<_, String>(peaks)
+                    })
+                    .await
+                    .map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?
+                    .map_err(actix_web::error::ErrorInternalServerError)?
+                }
+            }
+        }
+    };
+
+    let peaks = reduce_peaks(&base, samples);
+    let source = path.to_path_buf();
+    let duration = tokio::task::spawn_blocking(move || super::content::get_duration_cached(&source))
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(0.0);
+
+    if binary {
+        return Ok(HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .insert_header(("X-Waveform-Duration", format!("{:.3}", duration)))
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .body(encode_peaks(&peaks)));
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .json(WaveformResponse {
+            duration,
+            samples: peaks.len(),
+            // 保留 4 位小数，JSON 体积减半
+            peaks: peaks.iter().map(|p| (p * 10000.0).round() / 10000.0).collect(),
+        }))
+}
+
+/// If-None-Match 是否包含当前 ETag（或为 *）
+fn etag_matches(req: &HttpRequest, etag: &str) -> bool {
+    req.headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').map(str::trim).any(|t| t == "*" || t.trim_start_matches("W/") == etag))
+}
+
+/// 解码整条音轨并计算基准峰值（同步，需在 spawn_blocking 中调用）
+///
+/// 先按 10ms 窗口取峰值，解码完再降到 BASE_PEAKS 个；窗口数组一小时约 1.4 MB
+fn compute_base_peaks(path: &Path) -> Result<Vec<f32>, String> {
+    let ffmpeg = super::utils::get_ffmpeg_path();
+    let mut child = Command::new(&ffmpeg)
+        .args(["-hide_banner", "-loglevel", "error", "-nostdin", "-i"])
+        .arg(path)
+        .args(["-map", "0:a:0", "-vn", "-ac", "1", "-ar", &SAMPLE_RATE.to_string(), "-f", "f32le", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("启动 ffmpeg 失败: {}", e))?;
+
+    // stderr 单独线程读取，避免管道写满阻塞 ffmpeg
+    let mut stderr = child.stderr.take().ok_or("无法读取 ffmpeg 输出")?;
+    let stderr_reader = std::thread::spawn(move || {
+        let mut s = String::new();
+        let _ = stderr.read_to_string(&mut s);
+        s
+    });
+
+    let mut stdout = child.stdout.take().ok_or("无法读取 ffmpeg 输出")?;
+    let mut windows = Vec::new();
+    let mut current = 0f32;
+    let mut in_window = 0usize;
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut carry = Vec::with_capacity(4);
+    loop {
+        let n = stdout.read(&mut buf).map_err(|e| format!("读取 PCM 失败: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        // read 不保证按 4 字节对齐，上次剩下的半个样本拼到这次开头
+        carry.extend_from_slice(&buf[..n]);
+        let whole = carry.len() / 4 * 4;
+        for chunk in carry[..whole].chunks_exact(4) {
+            let sample = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]).abs();
+            if sample > current {
+                current = sample;
+            }
+            in_window += 1;
+            if in_window == MIN_WINDOW {
+                windows.push(current);
+                current = 0.0;
+                in_window = 0;
+            }
+        }
+        carry.drain(..whole);
+    }
+    if in_window > 0 {
+        windows.push(current);
+    }
+
+    let status = child.wait().map_err(|e| format!("等待 ffmpeg 失败: {}", e))?;
+    let stderr = stderr_reader.join().unwrap_or_default();
+    if !status.success() {
+        if stderr.contains("matches no streams") {
+            return Err("文件没有音轨".to_string());
+        }
+        return Err(format!("ffmpeg 解码失败: {}", stderr.trim()));
+    }
+    if windows.is_empty() {
+        return Err("音轨为空".to_string());
+    }
+
+    Ok(reduce_peaks(&windows, BASE_PEAKS))
+}
+
+/// 把峰值数组降采样到至多 n 个：每个输出取对应区间内的最大值（峰值不能取平均，否则瞬态会被抹平）
+fn reduce_peaks(peaks: &[f32], n: usize) -> Vec<f32> {
+    if peaks.len() <= n {
+        return peaks.iter().map(|p| p.min(1.0)).collect();
+    }
+    (0..n)
+        .map(|i| {
+            let start = i * peaks.len() / n;
+            let end = ((i + 1) * peaks.len() / n).max(start + 1);
+            peaks[start..end].iter().copied().fold(0.0f32, f32::max).min(1.0)
+        })
+        .collect()
+}
+
+fn encode_peaks(peaks: &[f32]) -> Vec<u8> {
+    peaks.iter().flat_map(|p| p.to_le_bytes()).collect()
+}
+
+fn decode_peaks(data: &[u8]) -> Vec<f32> {
+    data.chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reduce_peaks() {
+        let peaks = [0.1, 0.5, 0.2, 0.9, 0.3, 0.0, 1.5];
+        // 7 → 3：区间 [0,2) [2,4) [4,7)，超过 1.0 的削顶
+        assert_eq!(reduce_peaks(&peaks, 3), vec![0.5, 0.9, 1.0]);
+        // 不足请求个数时原样返回
+        assert_eq!(reduce_peaks(&peaks[..2], 8), vec![0.1, 0.5]);
+        assert_eq!(decode_peaks(&encode_peaks(&peaks[..3])), peaks[..3].to_vec());
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        let etag = "\"abc-1700000000000-800-json\"";
+        let req = |v: &str| actix_web::test::TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, v))
+            .to_http_request();
+        assert!(etag_matches(&req("\"other\", \"abc-1700000000000-800-json\""), etag));
+        assert!(etag_matches(&req("W/\"abc-1700000000000-800-json\""), etag));
+        assert!(etag_matches(&req("*"), etag));
+        assert!(!etag_matches(&req("\"abc-1700000000001-800-json\""), etag));
+        assert!(!etag_matches(&actix_web::test::TestRequest::default().to_http_request(), etag));
+    }
+}
@@ -0,0 +1,143 @@
+// 阅读进度模块 - API 处理函数
+use actix_web::{web, HttpResponse, Result};
+use super::models::*;
+use super::storage;
+
+/// 文件是否在索引中
+fn file_exists(file_uuid: &str) -> Result<bool, rusqlite::Error> {
+    crate::indexer::storage::get_file_by_uuid(file_uuid).map(|f| f.is_some())
+}
+
+fn file_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({ "error": "文件未找到" }))
+}
+
+/// 获取文件的阅读进度和书签
+pub async fn get_progress(query: web::Query<FileUuidQuery>) -> Result<HttpResponse> {
+    let file_uuid = query.file_uuid.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        Ok::<_, rusqlite::Error>(ReadingStateResponse {
+            progress: storage::get_progress(&file_uuid)?,
+            bookmarks: storage::get_bookmarks(&file_uuid)?,
+            file_uuid,
+        })
+    }).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+
+    match result {
+        Ok(state) => Ok(HttpResponse::Ok().json(state)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("获取阅读进度失败: {}", e)
+        }))),
+    }
+}
+
+/// 保存阅读进度
+pub async fn save_progress(body: web::Json<SaveProgressRequest>) -> Result<HttpResponse> {
+    if body.page == 0 {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "page 从 1 开始" })));
+    }
+    let file_uuid = body.file_uuid.clone();
+    let page = body.page;
+    let total_pages = body.total_pages;
+    let finished = body.finished.unwrap_or(total_pages > 0 && page >= total_pages);
+    let position = body.position.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        if !file_exists(&file_uuid)? {
+            return Ok(None);
+        }
+        storage::save_progress(&file_uuid, page, total_pages, finished, position.as_deref()).map(Some)
+    }).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+
+    match result {
+        Ok(Some(progress)) => Ok(HttpResponse::Ok().json(progress)),
+        Ok(None) => Ok(file_not_found()),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("保存阅读进度失败: {}", e)
+        }))),
+    }
+}
+
+/// 清除阅读进度（重新从头读）
+pub async fn clear_progress(query: web::Query<FileUuidQuery>) -> Result<HttpResponse> {
+    let file_uuid = query.file_uuid.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        storage::clear_progress(&file_uuid)
+    }).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+
+    match result {
+        Ok(()) => Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("清除阅读进度失败: {}", e)
+        }))),
+    }
+}
+
+/// 正在阅读：未读完的文件，最近阅读的在前
+pub async fn currently_reading(query: web::Query<CurrentQuery>) -> Result<HttpResponse> {
+    let source_folder = query.source_folder.clone();
+    let limit = query.limit.unwrap_or(20).clamp(1, 200);
+
+    let result = tokio::task::spawn_blocking(move || {
+        storage::get_currently_reading(source_folder.as_deref(), limit)
+    }).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+
+    match result {
+        Ok(rows) => {
+            let items: Vec<CurrentReadingItem> = rows.into_iter()
+                .map(|(file, progress)| CurrentReadingItem { file, progress })
+                .collect();
+            Ok(HttpResponse::Ok().json(items))
+        }
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("获取正在阅读列表失败: {}", e)
+        }))),
+    }
+}
+
+/// 添加书签
+pub async fn add_bookmark(body: web::Json<AddBookmarkRequest>) -> Result<HttpResponse> {
+    if body.page == 0 {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": "page 从 1 开始" })));
+    }
+    let file_uuid = body.file_uuid.clone();
+    let page = body.page;
+    let name = body.name.clone()
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| format!("第 {} 页", page));
+    let position = body.position.clone();
+
+    let result = tokio::task::spawn_blocking(move || {
+        if !file_exists(&file_uuid)? {
+            return Ok(None);
+        }
+        storage::add_bookmark(&file_uuid, page, &name, position.as_deref()).map(Some)
+    }).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+
+    match result {
+        Ok(Some(bookmark)) => Ok(HttpResponse::Ok().json(bookmark)),
+        Ok(None) => Ok(file_not_found()),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("添加书签失败: {}", e)
+        }))),
+    }
+}
+
+/// 删除书签
+pub async fn delete_bookmark(path: web::Path<i64>) -> Result<HttpResponse> {
+    let id = path.into_inner();
+
+    let result = tokio::task::spawn_blocking(move || {
+        storage::delete_bookmark(id)
+    }).await.map_err(|e| actix_web::error::ErrorInternalServerError(format!("任务执行失败: {}", e)))?;
+
+    match result {
+        Ok(true) => Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true }))),
+        Ok(false) => Ok(HttpResponse::NotFound().json(serde_json::json!({ "error": "书签不存在" }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+            "error": format!("删除书签失败: {}", e)
+        }))),
+    }
+}
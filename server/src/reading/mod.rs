@@ -0,0 +1,16 @@
+// 阅读进度模块 — PDF / CBZ / EPUB 等分页文档的阅读位置、已读标记与书签
+pub mod models;
+pub mod storage;
+mod handlers;
+
+use actix_web::web;
+
+/// 注册所有阅读进度相关路由
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/progress").route(web::get().to(handlers::get_progress))
+                                          .route(web::post().to(handlers::save_progress))
+                                          .route(web::delete().to(handlers::clear_progress)))
+       .service(web::resource("/current").route(web::get().to(handlers::currently_reading)))
+       .service(web::resource("/bookmark").route(web::post().to(handlers::add_bookmark)))
+       .service(web::resource("/bookmark/{id}").route(web::delete().to(handlers::delete_bookmark)));
+}
@@ -0,0 +1,73 @@
+// 阅读进度模块 - 数据模型
+use serde::{Deserialize, Serialize};
+
+use crate::indexer::models::IndexedFile;
+
+/// 阅读进度（文件列表中附带的精简信息）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadingProgress {
+    pub page: u32,                 // 当前页（从 1 开始）
+    pub total_pages: u32,          // 总页数（客户端上报，0 表示未知）
+    pub finished: bool,            // 是否已读完
+    pub position: Option<String>,  // 页内位置（EPUB CFI、滚动比例等，由客户端定义）
+    pub updated_at: String,
+}
+
+/// 书签
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub id: i64,
+    pub file_uuid: String,
+    pub page: u32,
+    pub name: String,
+    pub position: Option<String>,
+    pub created_at: String,
+}
+
+/// 单个文件的完整阅读状态
+#[derive(Debug, Serialize)]
+pub struct ReadingStateResponse {
+    pub file_uuid: String,
+    pub progress: Option<ReadingProgress>,
+    pub bookmarks: Vec<Bookmark>,
+}
+
+/// 保存阅读进度请求
+#[derive(Debug, Deserialize)]
+pub struct SaveProgressRequest {
+    pub file_uuid: String,
+    pub page: u32,
+    pub total_pages: u32,
+    /// 不传时按 page >= total_pages 判断
+    pub finished: Option<bool>,
+    pub position: Option<String>,
+}
+
+/// 添加书签请求
+#[derive(Debug, Deserialize)]
+pub struct AddBookmarkRequest {
+    pub file_uuid: String,
+    pub page: u32,
+    pub name: Option<String>,
+    pub position: Option<String>,
+}
+
+/// 按文件 UUID 查询
+#[derive(Debug, Deserialize)]
+pub struct FileUuidQuery {
+    pub file_uuid: String,
+}
+
+/// "正在阅读"列表查询参数
+#[derive(Debug, Deserialize)]
+pub struct CurrentQuery {
+    pub source_folder: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// "正在阅读"列表条目
+#[derive(Debug, Serialize)]
+pub struct CurrentReadingItem {
+    pub file: IndexedFile,
+    pub progress: ReadingProgress,
+}
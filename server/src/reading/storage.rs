@@ -0,0 +1,155 @@
+// 阅读进度模块 - SQLite 操作
+use crate::database::get_connection;
+use crate::indexer::models::IndexedFile;
+use crate::indexer::storage::map_file_row;
+use super::models::{Bookmark, ReadingProgress};
+use std::collections::HashMap;
+
+const FILE_COLUMNS: &str = "f.uuid, f.fingerprint, f.current_path, f.folder_path, f.file_name, f.file_type, f.extension, f.file_size, f.created_at, f.modified_at, f.indexed_at, f.source_url, f.blurhash, f.animated, f.frame_count";
+
+/// 子目录匹配模式：转义路径中的 LIKE 通配符（配合 ESCAPE '\'），文件夹名含 % / _ 时不会误配
+fn subfolder_pattern(folder: &str) -> String {
+    let escaped = folder.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("{}/%", escaped)
+}
+
+fn map_progress_row(row: &rusqlite::Row, offset: usize) -> Result<ReadingProgress, rusqlite::Error> {
+    Ok(ReadingProgress {
+        page: row.get(offset)?,
+        total_pages: row.get(offset + 1)?,
+        finished: row.get::<_, i64>(offset + 2)? != 0,
+        position: row.get(offset + 3)?,
+        updated_at: row.get(offset + 4)?,
+    })
+}
+
+/// 保存阅读进度（覆盖）
+pub fn save_progress(file_uuid: &str, page: u32, total_pages: u32, finished: bool, position: Option<&str>) -> Result<ReadingProgress, rusqlite::Error> {
+    let conn = get_connection()?;
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO reading_progress (file_uuid, page, total_pages, finished, position, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(file_uuid) DO UPDATE SET
+            page = excluded.page, total_pages = excluded.total_pages, finished = excluded.finished,
+            position = excluded.position, updated_at = excluded.updated_at",
+        rusqlite::params![file_uuid, page, total_pages, finished as i64, position, now],
+    )?;
+    Ok(ReadingProgress {
+        page,
+        total_pages,
+        finished,
+        position: position.map(|s| s.to_string()),
+        updated_at: now,
+    })
+}
+
+/// 获取文件的阅读进度
+pub fn get_progress(file_uuid: &str) -> Result<Option<ReadingProgress>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let mut stmt = conn.prepare(
+        "SELECT page, total_pages, finished, position, updated_at FROM reading_progress WHERE file_uuid = ?1"
+    )?;
+    let mut rows = stmt.query_map(rusqlite::params![file_uuid], |row| map_progress_row(row, 0))?;
+    rows.next().transpose()
+}
+
+/// 批量获取多个文件的阅读进度（文件列表附带用）
+pub fn get_progress_map(file_uuids: &[String]) -> Result<HashMap<String, ReadingProgress>, rusqlite::Error> {
+    if file_uuids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let conn = get_connection()?;
+    let placeholders = file_uuids.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+    let query = format!(
+        "SELECT file_uuid, page, total_pages, finished, position, updated_at FROM reading_progress WHERE file_uuid IN ({})",
+        placeholders
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(file_uuids.iter()), |row| {
+        Ok((row.get::<_, String>(0)?, map_progress_row(row, 1)?))
+    })?;
+    rows.collect()
+}
+
+/// 清除阅读进度（书签保留）
+pub fn clear_progress(file_uuid: &str) -> Result<(), rusqlite::Error> {
+    let conn = get_connection()?;
+    conn.execute("DELETE FROM reading_progress WHERE file_uuid = ?1", rusqlite::params![file_uuid])?;
+    Ok(())
+}
+
+/// 正在阅读（未读完）的文件，按最近阅读时间倒序；已删除 / 移出源文件夹的文件不返回
+pub fn get_currently_reading(source_folder: Option<&str>, limit: i64) -> Result<Vec<(IndexedFile, ReadingProgress)>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let mut query = format!(
+        "SELECT {}, r.page, r.total_pages, r.finished, r.position, r.updated_at
+         FROM reading_progress r INNER JOIN file_index f ON f.uuid = r.file_uuid
+         WHERE r.finished = 0 AND f.current_path IS NOT NULL",
+        FILE_COLUMNS
+    );
+    let mut params: Vec<String> = Vec::new();
+    if let Some(source) = source_folder {
+        query.push_str(" AND (f.folder_path = ? OR f.folder_path LIKE ? ESCAPE '\\')");
+        params.push(source.to_string());
+        params.push(subfolder_pattern(source));
+    }
+    query.push_str(&format!(" ORDER BY r.updated_at DESC LIMIT {}", limit));
+
+    // 进度列紧跟在文件列之后
+    let progress_offset = FILE_COLUMNS.split(',').count();
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+        Ok((map_file_row(row)?, map_progress_row(row, progress_offset)?))
+    })?;
+    rows.collect()
+}
+
+fn map_bookmark_row(row: &rusqlite::Row) -> Result<Bookmark, rusqlite::Error> {
+    Ok(Bookmark {
+        id: row.get(0)?,
+        file_uuid: row.get(1)?,
+        page: row.get(2)?,
+        name: row.get(3)?,
+        position: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+/// 添加书签
+pub fn add_bookmark(file_uuid: &str, page: u32, name: &str, position: Option<&str>) -> Result<Bookmark, rusqlite::Error> {
+    let conn = get_connection()?;
+    let now = chrono::Utc::now().to_rfc3339();
+    conn.execute(
+        "INSERT INTO reading_bookmarks (file_uuid, page, name, position, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![file_uuid, page, name, position, now],
+    )?;
+    Ok(Bookmark {
+        id: conn.last_insert_rowid(),
+        file_uuid: file_uuid.to_string(),
+        page,
+        name: name.to_string(),
+        position: position.map(|s| s.to_string()),
+        created_at: now,
+    })
+}
+
+/// 获取文件的书签，按页码排序
+pub fn get_bookmarks(file_uuid: &str) -> Result<Vec<Bookmark>, rusqlite::Error> {
+    let conn = get_connection()?;
+    let mut stmt = conn.prepare(
+        "SELECT id, file_uuid, page, name, position, created_at FROM reading_bookmarks
+         WHERE file_uuid = ?1 ORDER BY page ASC, id ASC"
+    )?;
+    let bookmarks = stmt.query_map(rusqlite::params![file_uuid], map_bookmark_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(bookmarks)
+}
+
+/// 删除书签，返回是否存在
+pub fn delete_bookmark(id: i64) -> Result<bool, rusqlite::Error> {
+    let conn = get_connection()?;
+    let count = conn.execute("DELETE FROM reading_bookmarks WHERE id = ?1", rusqlite::params![id])?;
+    Ok(count > 0)
+}
@@ -4,4 +4,4 @@ pub mod downloader;
 pub mod ugoira;
 
 pub use downloader::download_illust;
-pub use ugoira::{download_ugoira_zip, convert_ugoira_to_gif};
+pub use ugoira::{download_ugoira_zip, convert_ugoira, UgoiraFormat};
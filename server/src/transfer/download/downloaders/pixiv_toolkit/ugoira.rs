@@ -1,6 +1,6 @@
 // Pixiv 动图 (Ugoira) 下载和转换
-use super::parser::{PixivParser, UgoiraMeta};
-use std::path::PathBuf;
+use super::parser::{PixivParser, UgoiraFrame, UgoiraMeta};
+use std::path::{Path, PathBuf};
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
@@ -70,29 +70,92 @@ pub async fn download_ugoira_zip(
     Ok((zip_path.to_string_lossy().to_string(), meta))
 }
 
-/// 将动图 ZIP 转换为 GIF
+/// 动图输出格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UgoiraFormat {
+    /// 256 色 GIF（兼容性最好，但有色带且体积大）
+    Gif,
+    /// H.264 MP4（ffmpeg）
+    Mp4,
+    /// VP9 WebM（ffmpeg）
+    Webm,
+    /// 动画 WebP（ffmpeg libwebp_anim）
+    Webp,
+    /// 无损 APNG
+    Apng,
+}
+
+impl UgoiraFormat {
+    /// 解析下载请求的 format 字段；未传或 `best` 时保持 GIF
+    pub fn parse(format: Option<&str>) -> Result<Self, String> {
+        let format = format.map(|f| f.trim().to_ascii_lowercase()).unwrap_or_default();
+        match format.as_str() {
+            "" | "best" | "gif" => Ok(Self::Gif),
+            "mp4" => Ok(Self::Mp4),
+            "webm" => Ok(Self::Webm),
+            "webp" => Ok(Self::Webp),
+            "apng" | "png" => Ok(Self::Apng),
+            other => Err(format!("不支持的动图格式: {}（可选 gif / mp4 / webm / webp / apng）", other)),
+        }
+    }
+
+    /// 输出文件扩展名
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gif => "gif",
+            Self::Mp4 => "mp4",
+            Self::Webm => "webm",
+            Self::Webp => "webp",
+            Self::Apng => "png",
+        }
+    }
+
+    /// 交给 ffmpeg 的编码参数（GIF / APNG 不走 ffmpeg）
+    fn ffmpeg_args(self) -> &'static [&'static str] {
+        match self {
+            // x264 要求宽高为偶数，奇数尺寸补一像素边
+            Self::Mp4 => &[
+                "-c:v", "libx264", "-crf", "18", "-preset", "slow", "-pix_fmt", "yuv420p",
+                "-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2", "-movflags", "+faststart",
+            ],
+            Self::Webm => &["-c:v", "libvpx-vp9", "-crf", "24", "-b:v", "0", "-row-mt", "1"],
+            Self::Webp => &["-c:v", "libwebp_anim", "-quality", "90", "-loop", "0"],
+            Self::Gif | Self::Apng => &[],
+        }
+    }
+}
+
+/// 将动图 ZIP 转换为指定格式
 ///
 /// # 参数
 /// - zip_path: ZIP 文件路径
 /// - meta: 动图元数据
 /// - output_dir: 输出目录
 /// - illust_id: 作品 ID
+/// - format: 输出格式
+/// - keep_zip: 是否保留帧 ZIP 作为原始存档（否则转换成功后删除）
 /// - progress_callback: 进度回调 (0.0 - 1.0)
 ///
 /// # 返回
-/// - Ok(gif_path): GIF 文件路径
+/// - Ok(output_path): 输出文件路径
 /// - Err(错误信息): 转换失败
-pub async fn convert_ugoira_to_gif<F>(
+pub async fn convert_ugoira<F>(
     zip_path: String,
     meta: UgoiraMeta,
     output_dir: String,
     illust_id: String,
+    format: UgoiraFormat,
+    keep_zip: bool,
     mut progress_callback: F,
 ) -> Result<String, String>
 where
     F: FnMut(f32) + Send + 'static,
 {
-    eprintln!("[Pixiv Ugoira] 开始转换为 GIF: {}", zip_path);
+    eprintln!("[Pixiv Ugoira] 开始转换为 {}: {}", format.extension(), zip_path);
+
+    if meta.frames.is_empty() {
+        return Err("动图元数据中没有帧".to_string());
+    }
 
     // 1. 创建临时目录用于解压帧
     let temp_dir = PathBuf::from(&output_dir).join(format!("{}_frames", illust_id));
@@ -101,7 +164,48 @@ where
         .map_err(|e| format!("创建临时目录失败: {}", e))?;
 
     // 2. 解压 ZIP 文件
-    let zip_file = std::fs::File::open(&zip_path)
+    extract_frames(&zip_path, &temp_dir, &mut progress_callback)?;
+
+    // 3. 按格式编码，帧间隔取 UgoiraFrame.delay
+    let output_path = PathBuf::from(&output_dir)
+        .join(format!("{}_ugoira.{}", illust_id, format.extension()));
+
+    let result = match format {
+        UgoiraFormat::Gif => encode_gif(&meta.frames, &temp_dir, &output_path, &mut progress_callback),
+        UgoiraFormat::Apng => encode_apng(&meta.frames, &temp_dir, &output_path, &mut progress_callback),
+        _ => encode_ffmpeg(&meta.frames, &temp_dir, &output_path, format).await,
+    };
+
+    // 4. 清理临时目录（转换失败也要清理）
+    if let Err(e) = fs::remove_dir_all(&temp_dir).await {
+        eprintln!("[Pixiv Ugoira] 清理临时目录失败: {}", e);
+    }
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&output_path).await;
+        return Err(e);
+    }
+
+    eprintln!("[Pixiv Ugoira] 已生成: {}", output_path.display());
+
+    // 5. 不保留原始 ZIP 时删除
+    if !keep_zip {
+        if let Err(e) = fs::remove_file(&zip_path).await {
+            eprintln!("[Pixiv Ugoira] 删除 ZIP 失败: {}", e);
+        }
+    }
+
+    progress_callback(1.0);
+
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+/// 解压帧 ZIP 到临时目录（进度 0.0 - 0.3）
+fn extract_frames<F>(zip_path: &str, temp_dir: &Path, progress_callback: &mut F) -> Result<(), String>
+where
+    F: FnMut(f32),
+{
+    let zip_file = std::fs::File::open(zip_path)
         .map_err(|e| format!("打开 ZIP 文件失败: {}", e))?;
 
     let mut archive = zip::ZipArchive::new(zip_file)
@@ -115,7 +219,11 @@ where
             .by_index(i)
             .map_err(|e| format!("读取 ZIP 文件条目失败: {}", e))?;
 
-        let outpath = temp_dir.join(file.name());
+        // 拒绝带 `..` / 绝对路径的条目
+        let Some(name) = file.enclosed_name().map(|p| p.to_path_buf()) else {
+            continue;
+        };
+        let outpath = temp_dir.join(name);
 
         if file.is_dir() {
             std::fs::create_dir_all(&outpath)
@@ -123,7 +231,7 @@ where
         } else {
             if let Some(p) = outpath.parent() {
                 if !p.exists() {
-                    std::fs::create_dir_all(&p)
+                    std::fs::create_dir_all(p)
                         .map_err(|e| format!("创建父目录失败: {}", e))?;
                 }
             }
@@ -136,28 +244,37 @@ where
         progress_callback(0.3 * (i as f32 / archive_len as f32));
     }
 
-    // 3. 使用 image 和 gif crate 生成 GIF
-    use image::{io::Reader as ImageReader, RgbaImage};
-    use gif::{Encoder, Frame, Repeat};
-
-    eprintln!("[Pixiv Ugoira] 开始生成 GIF...");
+    Ok(())
+}
 
-    // 读取第一帧以获取尺寸
-    let first_frame_path = temp_dir.join(&meta.frames[0].file);
-    let first_img = ImageReader::open(&first_frame_path)
-        .map_err(|e| format!("打开第一帧失败: {}", e))?
+/// 解码单帧为 RGBA
+fn decode_frame(temp_dir: &Path, frame: &UgoiraFrame) -> Result<image::RgbaImage, String> {
+    let img = image::io::Reader::open(temp_dir.join(&frame.file))
+        .map_err(|e| format!("打开帧 {} 失败: {}", frame.file, e))?
         .decode()
-        .map_err(|e| format!("解码第一帧失败: {}", e))?;
+        .map_err(|e| format!("解码帧 {} 失败: {}", frame.file, e))?;
+    Ok(img.to_rgba8())
+}
 
-    let (width, height) = (first_img.width() as u16, first_img.height() as u16);
+/// 使用 gif crate 生成 GIF（进度 0.3 - 1.0）
+fn encode_gif<F>(
+    frames: &[UgoiraFrame],
+    temp_dir: &Path,
+    output_path: &Path,
+    progress_callback: &mut F,
+) -> Result<(), String>
+where
+    F: FnMut(f32),
+{
+    use gif::{Encoder, Frame, Repeat};
 
-    eprintln!("[Pixiv Ugoira] GIF 尺寸: {}x{}", width, height);
+    // 读取第一帧以获取尺寸
+    let first = decode_frame(temp_dir, &frames[0])?;
+    let (width, height) = (first.width() as u16, first.height() as u16);
 
-    // 创建 GIF 编码器
-    let gif_filename = format!("{}_ugoira.gif", illust_id);
-    let gif_path = PathBuf::from(&output_dir).join(&gif_filename);
+    eprintln!("[Pixiv Ugoira] GIF 尺寸: {}x{}", width, height);
 
-    let gif_file = std::fs::File::create(&gif_path)
+    let gif_file = std::fs::File::create(output_path)
         .map_err(|e| format!("创建 GIF 文件失败: {}", e))?;
 
     let mut encoder = Encoder::new(gif_file, width, height, &[])
@@ -168,19 +285,8 @@ where
         .map_err(|e| format!("设置 GIF 循环失败: {}", e))?;
 
     // 逐帧添加到 GIF
-    for (i, frame_info) in meta.frames.iter().enumerate() {
-        let frame_path = temp_dir.join(&frame_info.file);
-
-        let img = ImageReader::open(&frame_path)
-            .map_err(|e| format!("打开帧 {} 失败: {}", frame_info.file, e))?
-            .decode()
-            .map_err(|e| format!("解码帧 {} 失败: {}", frame_info.file, e))?;
-
-        // 转换为 RGBA
-        let rgba_img: RgbaImage = img.to_rgba8();
-
-        // 创建 GIF 帧
-        let mut rgba_raw = rgba_img.into_raw();
+    for (i, frame_info) in frames.iter().enumerate() {
+        let mut rgba_raw = decode_frame(temp_dir, frame_info)?.into_raw();
         let mut frame = Frame::from_rgba_speed(width, height, &mut rgba_raw, 10);
 
         // 设置延迟时间（GIF 使用 1/100 秒为单位）
@@ -190,19 +296,143 @@ where
             .write_frame(&frame)
             .map_err(|e| format!("写入 GIF 帧失败: {}", e))?;
 
-        progress_callback(0.3 + 0.7 * ((i + 1) as f32 / meta.frames.len() as f32));
+        progress_callback(0.3 + 0.7 * ((i + 1) as f32 / frames.len() as f32));
+    }
+
+    Ok(())
+}
+
+/// 使用 png crate 生成无损 APNG（进度 0.3 - 1.0）
+fn encode_apng<F>(
+    frames: &[UgoiraFrame],
+    temp_dir: &Path,
+    output_path: &Path,
+    progress_callback: &mut F,
+) -> Result<(), String>
+where
+    F: FnMut(f32),
+{
+    let first = decode_frame(temp_dir, &frames[0])?;
+    let (width, height) = first.dimensions();
+
+    let file = std::fs::File::create(output_path)
+        .map_err(|e| format!("创建 APNG 文件失败: {}", e))?;
+
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(frames.len() as u32, 0)
+        .map_err(|e| format!("设置 APNG 动画失败: {}", e))?;
+
+    let mut writer = encoder
+        .write_header()
+        .map_err(|e| format!("写入 APNG 头失败: {}", e))?;
+
+    for (i, frame_info) in frames.iter().enumerate() {
+        let rgba = if i == 0 { first.clone() } else { decode_frame(temp_dir, frame_info)? };
+        if rgba.dimensions() != (width, height) {
+            return Err(format!("帧 {} 尺寸与首帧不一致", frame_info.file));
+        }
+
+        let (num, den) = apng_delay(frame_info.delay);
+        writer
+            .set_frame_delay(num, den)
+            .map_err(|e| format!("设置 APNG 帧延迟失败: {}", e))?;
+        writer
+            .write_image_data(&rgba)
+            .map_err(|e| format!("写入 APNG 帧失败: {}", e))?;
+
+        progress_callback(0.3 + 0.7 * ((i + 1) as f32 / frames.len() as f32));
+    }
+
+    writer
+        .finish()
+        .map_err(|e| format!("完成 APNG 写入失败: {}", e))
+}
+
+/// 毫秒延迟换算为 APNG 的 delay_num / delay_den（均为 u16），超出范围时退到 1/100 秒
+fn apng_delay(delay_ms: u32) -> (u16, u16) {
+    match u16::try_from(delay_ms) {
+        Ok(ms) => (ms, 1000),
+        Err(_) => ((delay_ms / 10).min(u16::MAX as u32) as u16, 100),
     }
+}
 
-    drop(encoder);
+/// 生成 ffmpeg concat demuxer 清单，每帧按自己的 delay 显示。
+/// concat 会忽略最后一条的 duration，所以末帧需要再列一次
+fn build_concat_list(frames: &[UgoiraFrame]) -> String {
+    let mut list = String::from("ffconcat version 1.0\n");
+    for frame in frames {
+        let file = frame.file.replace('\'', "'\\''");
+        list.push_str(&format!("file '{}'\nduration {:.3}\n", file, frame.delay as f64 / 1000.0));
+    }
+    if let Some(last) = frames.last() {
+        list.push_str(&format!("file '{}'\n", last.file.replace('\'', "'\\''")));
+    }
+    list
+}
 
-    eprintln!("[Pixiv Ugoira] GIF 已生成: {}", gif_path.display());
+/// 通过 ffmpeg concat demuxer 编码 MP4 / WebM / 动画 WebP（可变帧率，时间戳直接透传）
+async fn encode_ffmpeg(
+    frames: &[UgoiraFrame],
+    temp_dir: &Path,
+    output_path: &Path,
+    format: UgoiraFormat,
+) -> Result<(), String> {
+    // 清单里的相对路径以清单所在目录为基准
+    let list_path = temp_dir.join("frames.ffconcat");
+    fs::write(&list_path, build_concat_list(frames))
+        .await
+        .map_err(|e| format!("写入帧清单失败: {}", e))?;
 
-    // 4. 清理临时目录
-    fs::remove_dir_all(&temp_dir)
+    let ffmpeg = tokio::task::spawn_blocking(crate::preview::utils::get_ffmpeg_path)
         .await
-        .map_err(|e| format!("清理临时目录失败: {}", e))?;
+        .map_err(|e| format!("获取 ffmpeg 失败: {}", e))?;
 
-    progress_callback(1.0);
+    eprintln!("[Pixiv Ugoira] ffmpeg 编码 {} ...", format.extension());
+
+    let output = tokio::process::Command::new(&ffmpeg)
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-f", "concat", "-safe", "0", "-i"])
+        .arg(&list_path)
+        .args(["-fps_mode", "passthrough"])
+        .args(format.ffmpeg_args())
+        .arg(output_path)
+        .output()
+        .await
+        .map_err(|e| format!("启动 ffmpeg 失败: {}", e))?;
 
-    Ok(gif_path.to_string_lossy().to_string())
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffmpeg 编码失败: {}", stderr.trim()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(file: &str, delay: u32) -> UgoiraFrame {
+        UgoiraFrame { file: file.to_string(), delay }
+    }
+
+    #[test]
+    fn test_build_concat_list() {
+        let list = build_concat_list(&[frame("000000.jpg", 60), frame("000001.jpg", 1500)]);
+        assert_eq!(
+            list,
+            "ffconcat version 1.0\n\
+             file '000000.jpg'\nduration 0.060\n\
+             file '000001.jpg'\nduration 1.500\n\
+             file '000001.jpg'\n"
+        );
+    }
+
+    #[test]
+    fn test_apng_delay() {
+        assert_eq!(apng_delay(40), (40, 1000));
+        assert_eq!(apng_delay(70_000), (7000, 100));
+    }
 }
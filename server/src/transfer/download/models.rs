@@ -82,7 +82,20 @@ pub struct DownloadRequest {
     pub url: String,
     pub downloader: Option<DownloaderType>, // 用户可选择覆盖自动检测
     pub save_folder: String, // 相对于 source_folder 的路径，空字符串表示根目录
-    pub format: Option<String>, // 格式选项：best, mp4, mp3 等
+    pub format: Option<String>, // 格式选项：best, mp4, mp3 等；Pixiv 动图为 gif / mp4 / webm / webp / apng
+    #[serde(default = "default_keep_original")]
+    pub keep_original: bool, // Pixiv 动图：转换后是否保留帧 ZIP（默认保留，转换有损时还能从原始帧重做）
+}
+
+fn default_keep_original() -> bool {
+    true
+}
+
+// 传给下载器的选项
+#[derive(Debug, Clone, Default)]
+pub struct DownloadOptions {
+    pub format: Option<String>,
+    pub keep_original: bool,
 }
 
 // 下载任务信息
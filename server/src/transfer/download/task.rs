@@ -29,6 +29,13 @@ pub async fn create_task(
     let downloader = req.downloader.clone()
         .unwrap_or(detect_result.downloader.clone());
 
+    // Pixiv 的 format 指动图输出格式，提前拒绝不支持的值
+    if downloader == DownloaderType::PixivToolkit {
+        if let Err(e) = super::downloaders::pixiv_toolkit::UgoiraFormat::parse(req.format.as_deref()) {
+            return Ok(HttpResponse::BadRequest().json(serde_json::json!({ "error": e })));
+        }
+    }
+
     // 3. 验证 save_folder（如果非空）
     if !req.save_folder.is_empty() {
         let config = storage::load_config()
@@ -85,7 +92,10 @@ pub async fn create_task(
             detect_result.platform,
             downloader,
             save_path,
-            req.format.clone(),
+            DownloadOptions {
+                format: req.format.clone(),
+                keep_original: req.keep_original,
+            },
         )
         .await
         .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
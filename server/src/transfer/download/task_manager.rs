@@ -2,7 +2,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use super::models::{DownloadTask, Platform, DownloaderType, TaskStatus};
+use super::models::{DownloadOptions, DownloadTask, Platform, DownloaderType, TaskStatus};
 use super::storage;
 
 /// 任务管理器
@@ -25,7 +25,7 @@ impl TaskManager {
         platform: Platform,
         downloader: DownloaderType,
         save_folder: String,
-        format: Option<String>,
+        options: DownloadOptions,
     ) -> Result<String, String> {
         // 1. 生成唯一任务 ID
         let task_id = uuid::Uuid::new_v4().to_string();
@@ -61,7 +61,7 @@ impl TaskManager {
                 platform,
                 downloader,
                 save_folder,
-                format,
+                options,
                 tasks_clone,
             )
             .await;
@@ -122,7 +122,7 @@ impl TaskManager {
         platform: Platform,
         downloader: DownloaderType,
         save_folder: String,
-        format: Option<String>,
+        options: DownloadOptions,
         tasks: Arc<Mutex<HashMap<String, DownloadTask>>>,
     ) {
         // 1. 更新状态为 Downloading
@@ -165,7 +165,7 @@ impl TaskManager {
                     url.clone(),
                     platform,
                     save_folder.clone(),
-                    format,
+                    options.format.clone(),
                     file_uuid.clone(),
                     move |progress, speed, eta| {
                         let task_id = task_id_clone.clone();
@@ -194,7 +194,9 @@ impl TaskManager {
                                     Ok(illust_meta) => {
                                         // 3. 根据作品类型选择下载方式
                                         if illust_meta.illust_type == 2 {
-                                            // 动图：下载 ZIP + 转换 GIF
+                                            // 动图：下载 ZIP + 按请求格式转换（默认 GIF）
+                                            let ugoira_format = super::downloaders::pixiv_toolkit::UgoiraFormat::parse(options.format.as_deref())
+                                                .unwrap_or(super::downloaders::pixiv_toolkit::UgoiraFormat::Gif);
                                             let task_id_clone = task_id.clone();
                                             let tasks_clone = tasks.clone();
 
@@ -211,12 +213,14 @@ impl TaskManager {
                                                         task.progress = 50.0;
                                                     }
 
-                                                    // 转换为 GIF
-                                                    match super::downloaders::pixiv_toolkit::convert_ugoira_to_gif(
+                                                    // 转换为目标格式
+                                                    match super::downloaders::pixiv_toolkit::convert_ugoira(
                                                         zip_path.clone(),
                                                         meta,
                                                         save_folder.clone(),
                                                         parser.illust_id.clone(),
+                                                        ugoira_format,
+                                                        options.keep_original,
                                                         move |progress| {
                                                             let task_id = task_id_clone.clone();
                                                             let tasks = tasks_clone.clone();
@@ -230,8 +234,8 @@ impl TaskManager {
                                                     )
                                                     .await
                                                     {
-                                                        Ok(gif_path) => Ok(gif_path),
-                                                        Err(e) => Err(format!("GIF 转换失败: {}", e)),
+                                                        Ok(output_path) => Ok(output_path),
+                                                        Err(e) => Err(format!("动图转换失败: {}", e)),
                                                     }
                                                 }
                                                 Err(e) => Err(format!("ZIP 下载失败: {}", e)),